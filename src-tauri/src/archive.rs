@@ -0,0 +1,399 @@
+use anyhow::{anyhow, Context, Result};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::{info, warn};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::database::{now_iso, restore_from_file, snapshot_for_transfer, validate_restore_source};
+use crate::settings::{load_file_settings, save_settings_to_dir, Settings};
+
+/// Bump when the archive layout changes; imports refuse formats they don't know.
+pub const ARCHIVE_FORMAT: u32 = 1;
+
+const MANIFEST_NAME: &str = "manifest.json";
+const DB_NAME: &str = "toonana.sqlite";
+const SETTINGS_NAME: &str = "settings.json";
+// Data-dir folders copied file by file
+const TREE_DIRS: &[&str] = &["images", "avatars"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub format: u32,
+    pub app_version: String,
+    pub created_at: String,
+    /// Data dir of the exporting machine; stored paths under it are rewritten on import
+    pub data_dir: String,
+    pub api_keys_redacted: bool,
+    /// Entry bodies are stored decrypted (see `export_backup`)
+    #[serde(default)]
+    pub plaintext_bodies: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum ArchiveStage {
+    Preparing,
+    Copying { done: u64, total: u64 },
+    Restoring,
+    Done { path: String },
+    Cancelled,
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveJobStatus {
+    pub job_id: String,
+    /// "export" or "import"
+    pub kind: String,
+    pub stage: ArchiveStage,
+    pub updated_at: String,
+}
+
+/// Progress reporting and cancellation shared between a backup job and its commands.
+#[derive(Clone)]
+pub struct ArchiveJob {
+    pub job_id: String,
+    pub kind: &'static str,
+    pub status: Arc<DashMap<String, ArchiveJobStatus>>,
+    pub cancel: Arc<AtomicBool>,
+}
+
+impl ArchiveJob {
+    pub fn set(&self, stage: ArchiveStage) {
+        self.status.insert(self.job_id.clone(), ArchiveJobStatus {
+            job_id: self.job_id.clone(),
+            kind: self.kind.to_string(),
+            stage,
+            updated_at: now_iso(),
+        });
+    }
+
+    fn check_cancelled(&self) -> Result<()> {
+        if self.cancel.load(Ordering::Relaxed) {
+            return Err(anyhow!(Cancelled));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Record the job's outcome: done, cancelled (nothing left half-written), or failed.
+pub fn finish(job: &ArchiveJob, result: Result<PathBuf>) {
+    match result {
+        Ok(path) => job.set(ArchiveStage::Done { path: path.display().to_string() }),
+        Err(e) if e.downcast_ref::<Cancelled>().is_some() => {
+            info!(job_id = %job.job_id, kind = job.kind, "backup job cancelled");
+            job.set(ArchiveStage::Cancelled);
+        }
+        Err(e) => {
+            warn!(job_id = %job.job_id, kind = job.kind, error = %format!("{:#}", e), "backup job failed");
+            job.set(ArchiveStage::Failed { error: format!("{:#}", e) });
+        }
+    }
+}
+
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(read) = fs::read_dir(dir) else { return };
+    for entry in read.flatten() {
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(t) if t.is_dir() => collect_files(&path, out),
+            Ok(t) if t.is_file() => out.push(path),
+            _ => {}
+        }
+    }
+}
+
+// Archive names always use '/' regardless of platform
+fn archive_name(data_dir: &Path, file: &Path) -> Option<String> {
+    let rel = file.strip_prefix(data_dir).ok()?;
+    let parts: Vec<String> = rel.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+    Some(parts.join("/"))
+}
+
+fn redact_api_keys(settings: &mut Settings) {
+    settings.gemini_api_key = None;
+    settings.nano_banana_api_key = None;
+    settings.openai_api_key = None;
+}
+
+/// Zip the database, settings and image folders into `dest`. Files are streamed into the
+/// archive one at a time; the archive is written to `<dest>.part` and only renamed into
+/// place once complete, so a cancelled or failed export leaves nothing behind.
+/// Entry bodies stay encrypted with this machine's vault key unless `plaintext_bodies` is
+/// set, which makes the archive restorable elsewhere but readable by anyone who has it.
+pub async fn export_backup(
+    pool: &Pool<Sqlite>,
+    data_dir: &Path,
+    dest: &Path,
+    redact_keys: bool,
+    plaintext_bodies: bool,
+    app_version: String,
+    job: &ArchiveJob,
+) -> Result<PathBuf> {
+    job.set(ArchiveStage::Preparing);
+    let snapshot = data_dir.join(format!("export-{}.sqlite", job.job_id));
+    let _ = fs::remove_file(&snapshot);
+    snapshot_for_transfer(pool, &snapshot, plaintext_bodies).await.map_err(|e| anyhow!(e))?;
+
+    let (data_dir, dest, job) = (data_dir.to_path_buf(), dest.to_path_buf(), job.clone());
+    let snapshot_for_task = snapshot.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        write_archive(&data_dir, &dest, &snapshot_for_task, redact_keys, plaintext_bodies, app_version, &job)
+    })
+    .await
+    .context("export task failed")
+    .and_then(|r| r);
+    let _ = fs::remove_file(&snapshot);
+    result
+}
+
+fn write_archive(
+    data_dir: &Path,
+    dest: &Path,
+    snapshot: &Path,
+    redact_keys: bool,
+    plaintext_bodies: bool,
+    app_version: String,
+    job: &ArchiveJob,
+) -> Result<PathBuf> {
+    let mut files = Vec::new();
+    for dir in TREE_DIRS {
+        collect_files(&data_dir.join(dir), &mut files);
+    }
+    let total = files.len() as u64 + 2;
+    job.set(ArchiveStage::Copying { done: 0, total });
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).context("create backup folder")?;
+    }
+    let part = dest.with_extension("zip.part");
+    let result = (|| -> Result<()> {
+        let mut zip = ZipWriter::new(BufWriter::new(File::create(&part).context("create archive")?));
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .large_file(true);
+        // Images are already compressed; deflating them again only costs time
+        let stored = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .large_file(true);
+
+        let manifest = ArchiveManifest {
+            format: ARCHIVE_FORMAT,
+            app_version,
+            created_at: now_iso(),
+            data_dir: data_dir.display().to_string(),
+            api_keys_redacted: redact_keys,
+            plaintext_bodies,
+        };
+        zip.start_file(MANIFEST_NAME, options)?;
+        serde_json::to_writer_pretty(&mut zip, &manifest)?;
+
+        let mut settings = load_file_settings(data_dir);
+        if redact_keys {
+            redact_api_keys(&mut settings);
+        }
+        zip.start_file(SETTINGS_NAME, options)?;
+        serde_json::to_writer_pretty(&mut zip, &settings)?;
+
+        zip.start_file(DB_NAME, options)?;
+        std::io::copy(&mut BufReader::new(File::open(snapshot).context("open snapshot")?), &mut zip)?;
+        let mut done = 2u64;
+        job.set(ArchiveStage::Copying { done, total });
+
+        for file in &files {
+            job.check_cancelled()?;
+            let Some(name) = archive_name(data_dir, file) else { continue };
+            match File::open(file) {
+                Ok(f) => {
+                    zip.start_file(name, stored)?;
+                    std::io::copy(&mut BufReader::new(f), &mut zip).with_context(|| format!("copy {}", file.display()))?;
+                }
+                // Deleted since we listed the folder
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e).with_context(|| format!("open {}", file.display())),
+            }
+            done += 1;
+            job.set(ArchiveStage::Copying { done, total });
+        }
+        zip.finish()?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(&part);
+        return Err(e);
+    }
+    fs::rename(&part, dest).context("finalize archive")?;
+    info!(path = %dest.display(), files = total, "backup archive written");
+    Ok(dest.to_path_buf())
+}
+
+// Only the files an export writes are accepted; anything else (or a name escaping the
+// data dir) means this isn't a toonana archive
+fn validate_archive(archive: &mut ZipArchive<File>) -> Result<ArchiveManifest> {
+    let manifest: ArchiveManifest = serde_json::from_reader(
+        archive.by_name(MANIFEST_NAME).context("not a toonana backup: manifest.json missing")?,
+    )
+    .context("manifest.json is invalid")?;
+    if manifest.format > ARCHIVE_FORMAT {
+        return Err(anyhow!(
+            "backup format {} is newer than this version of toonana supports ({}); update the app first",
+            manifest.format,
+            ARCHIVE_FORMAT
+        ));
+    }
+    archive.by_name(DB_NAME).context("backup has no database")?;
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        let name = file.name().to_string();
+        let known = [MANIFEST_NAME, DB_NAME, SETTINGS_NAME].contains(&name.as_str())
+            || TREE_DIRS.iter().any(|d| name.starts_with(&format!("{}/", d)));
+        if !known || file.enclosed_name().is_none() {
+            return Err(anyhow!("backup contains an unexpected file: {}", name));
+        }
+    }
+    Ok(manifest)
+}
+
+fn extract_to(archive: &mut ZipArchive<File>, name: &str, dest: &Path) -> Result<()> {
+    let mut file = archive.by_name(name)?;
+    let mut out = BufWriter::new(File::create(dest).with_context(|| format!("create {}", dest.display()))?);
+    std::io::copy(&mut file, &mut out)?;
+    Ok(())
+}
+
+fn rebase_path(path: &mut Option<String>, old: &str, new: &str) {
+    if let Some(p) = path.as_mut() {
+        if let Some(rest) = p.strip_prefix(old) {
+            *p = format!("{}{}", new, rest);
+        }
+    }
+}
+
+// Move every file under `staging` to the same place under `data_dir`, replacing files
+// with the same name
+fn move_staged_files(staging: &Path, data_dir: &Path) -> Result<()> {
+    let mut files = Vec::new();
+    collect_files(staging, &mut files);
+    for file in files {
+        let Ok(rel) = file.strip_prefix(staging) else { continue };
+        let dest = data_dir.join(rel);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // rename doesn't replace an existing file on every platform
+        let _ = fs::remove_file(&dest);
+        fs::rename(&file, &dest).with_context(|| format!("move {}", dest.display()))?;
+    }
+    Ok(())
+}
+
+/// Restore an archive made by `export_backup`. Everything is validated before anything is
+/// replaced, and the current database is backed up first (`backups/`). Redacted API keys
+/// keep the values configured on this machine. Images are extracted to a staging folder
+/// first; once the database restore starts the import can no longer be cancelled, and the
+/// staged images then replace files with the same name.
+pub async fn import_backup(pool: &Pool<Sqlite>, data_dir: &Path, src: &Path, job: &ArchiveJob) -> Result<PathBuf> {
+    job.set(ArchiveStage::Preparing);
+    let staged_db = data_dir.join(format!("import-{}.sqlite", job.job_id));
+    let staging = data_dir.join(format!("import-{}", job.job_id));
+    let (src_path, staged, staging_dir, job_for_task) = (src.to_path_buf(), staged_db.clone(), staging.clone(), job.clone());
+    let extracted = tokio::task::spawn_blocking(move || -> Result<(ArchiveManifest, Option<Settings>)> {
+        let mut archive = ZipArchive::new(File::open(&src_path).context("open backup")?).context("backup is not a zip archive")?;
+        let manifest = validate_archive(&mut archive)?;
+        let settings = match archive.by_name(SETTINGS_NAME) {
+            Ok(f) => Some(serde_json::from_reader::<_, Settings>(f).context("settings.json is invalid")?),
+            Err(_) => None,
+        };
+        extract_to(&mut archive, DB_NAME, &staged)?;
+
+        let total = archive.len() as u64;
+        for i in 0..archive.len() {
+            job_for_task.check_cancelled()?;
+            let mut file = archive.by_index(i)?;
+            let Some(rel) = file.enclosed_name() else { continue };
+            if file.is_dir() || !TREE_DIRS.iter().any(|d| rel.starts_with(d)) {
+                continue;
+            }
+            let dest = staging_dir.join(rel);
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut out = BufWriter::new(File::create(&dest).with_context(|| format!("create {}", dest.display()))?);
+            std::io::copy(&mut file, &mut out)?;
+            job_for_task.set(ArchiveStage::Copying { done: i as u64 + 1, total });
+        }
+        Ok((manifest, settings))
+    })
+    .await
+    .context("import task failed")
+    .and_then(|r| r);
+
+    let result = async {
+        let (manifest, archived_settings) = extracted?;
+        validate_restore_source(&staged_db).await.map_err(|e| anyhow!(e))?;
+        job.check_cancelled()?;
+
+        // From here on the import runs to the end; a cancel would leave the restored
+        // database pointing at images that were never moved into place
+        job.set(ArchiveStage::Restoring);
+        let safety = crate::backup::backup_database(pool, data_dir).await.context("back up current database")?;
+        info!(path = %safety.display(), "current database backed up before restore");
+        let new_dir = data_dir.display().to_string();
+        let entries = restore_from_file(pool, &staged_db, Some(&manifest.data_dir), &new_dir)
+            .await
+            .map_err(|e| anyhow!("restore database: {}", e))?;
+        info!(entries, from = %manifest.data_dir, "database restored from backup");
+
+        let (staging_dir, data_root) = (staging.clone(), data_dir.to_path_buf());
+        tokio::task::spawn_blocking(move || move_staged_files(&staging_dir, &data_root))
+            .await
+            .context("import task failed")?
+            .context("move restored images into place")?;
+
+        if let Some(mut settings) = archived_settings {
+            let current = load_file_settings(data_dir);
+            if settings.gemini_api_key.is_none() {
+                settings.gemini_api_key = current.gemini_api_key;
+            }
+            if settings.nano_banana_api_key.is_none() {
+                settings.nano_banana_api_key = current.nano_banana_api_key;
+            }
+            if settings.openai_api_key.is_none() {
+                settings.openai_api_key = current.openai_api_key;
+            }
+            for path in [
+                &mut settings.avatar_image_path,
+                &mut settings.style_reference_image_path,
+                &mut settings.caption_font_path,
+            ] {
+                rebase_path(path, &manifest.data_dir, &new_dir);
+            }
+            save_settings_to_dir(data_dir, &settings)?;
+        }
+        Ok::<(), anyhow::Error>(())
+    }
+    .await;
+    let _ = fs::remove_file(&staged_db);
+    let _ = fs::remove_dir_all(&staging);
+    result?;
+    info!(path = %src.display(), "backup imported");
+    Ok(src.to_path_buf())
+}
@@ -0,0 +1,200 @@
+use anyhow::{Context, Result};
+use dashmap::DashMap;
+use sqlx::{Pool, Sqlite};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use time::{macros::format_description, OffsetDateTime};
+use tracing::{info, warn};
+
+use crate::comic::{ComicJobStatus, ComicStage};
+use crate::settings::load_settings_from_dir;
+
+// How long to wait before re-checking when a backup was skipped because a job is running
+const BUSY_RETRY: Duration = Duration::from_secs(5 * 60);
+
+pub fn backups_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("backups")
+}
+
+/// Snapshot the database with `VACUUM INTO` (consistent even while the app is open).
+pub async fn backup_database(pool: &Pool<Sqlite>, data_dir: &Path) -> Result<PathBuf> {
+    let dir = backups_dir(data_dir);
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .context("create backups dir")?;
+    let stamp = OffsetDateTime::now_utc()
+        .format(format_description!("[year][month][day]-[hour][minute][second]"))
+        .unwrap_or_default();
+    let path = dir.join(format!("toonana-{}.sqlite", stamp));
+    let target = path.display().to_string().replace('\'', "''");
+    sqlx::query(&format!("VACUUM INTO '{}'", target))
+        .execute(pool)
+        .await
+        .context("vacuum into backup")?;
+    info!(path = %path.display(), "database backup written");
+    Ok(path)
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct SqlDumpResult {
+    pub path: String,
+    pub tables: usize,
+    pub rows: u64,
+}
+
+fn quote_literal(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Write the schema and every row as plain SQL (`CREATE` + `INSERT`, BLOBs as `X'..'`
+/// literals) to `path`. Everything is read inside one transaction so the dump is
+/// consistent, and rows are streamed straight to the file.
+pub async fn export_sql_dump(pool: &Pool<Sqlite>, path: &Path) -> Result<SqlDumpResult> {
+    use futures_util::TryStreamExt;
+    use tokio::io::{AsyncWriteExt, BufWriter};
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await.context("create dump dir")?;
+    }
+    let mut out = BufWriter::new(tokio::fs::File::create(path).await.context("create dump file")?);
+    // Rolled back on drop, so an error part-way can't leave the connection mid-transaction
+    let mut conn = pool.begin().await.context("begin read transaction")?;
+
+    // Tables first so indexes and triggers can follow the data
+    let objects: Vec<(String, String, String)> = sqlx::query_as(
+        r#"SELECT type, name, sql FROM sqlite_master
+           WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%'
+           ORDER BY type <> 'table', name"#,
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .context("read schema")?;
+
+    let stamp = OffsetDateTime::now_utc()
+        .format(&time::format_description::well_known::Rfc3339)
+        .unwrap_or_default();
+    out.write_all(format!("-- toonana SQL dump {}\nPRAGMA foreign_keys=OFF;\nBEGIN TRANSACTION;\n", stamp).as_bytes())
+        .await?;
+
+    let (mut tables, mut rows) = (0usize, 0u64);
+    for (_, name, sql) in objects.iter().filter(|o| o.0 == "table") {
+        out.write_all(format!("\n{};\n", sql).as_bytes()).await?;
+        let columns: Vec<String> = sqlx::query_scalar(&format!("SELECT name FROM pragma_table_info({})", quote_literal(name)))
+            .fetch_all(&mut *conn)
+            .await
+            .with_context(|| format!("read columns of {}", name))?;
+        if columns.is_empty() {
+            continue;
+        }
+        // quote() renders each value as a SQL literal (strings escaped, BLOBs as X'..')
+        let select = format!(
+            "SELECT {} FROM {}",
+            columns.iter().map(|c| format!("quote({})", quote_ident(c))).collect::<Vec<_>>().join(" || ',' || "),
+            quote_ident(name)
+        );
+        let insert_prefix = format!(
+            "INSERT INTO {} ({}) VALUES (",
+            quote_ident(name),
+            columns.iter().map(|c| quote_ident(c)).collect::<Vec<_>>().join(", ")
+        );
+        let mut stream = sqlx::query_scalar::<_, String>(&select).fetch(&mut *conn);
+        while let Some(values) = stream.try_next().await.with_context(|| format!("read rows of {}", name))? {
+            out.write_all(insert_prefix.as_bytes()).await?;
+            out.write_all(values.as_bytes()).await?;
+            out.write_all(b");\n").await?;
+            rows += 1;
+        }
+        tables += 1;
+    }
+    for (_, _, sql) in objects.iter().filter(|o| o.0 != "table") {
+        out.write_all(format!("\n{};\n", sql).as_bytes()).await?;
+    }
+    out.write_all(b"\nCOMMIT;\n").await?;
+    out.flush().await.context("write dump file")?;
+
+    if let Err(e) = conn.rollback().await {
+        warn!(error = %e, "sql dump: ending read transaction failed");
+    }
+    info!(path = %path.display(), tables, rows, "sql dump written");
+    Ok(SqlDumpResult { path: path.display().to_string(), tables, rows })
+}
+
+/// Keep only the newest `keep` backups. Returns how many were deleted.
+pub fn prune_backups(data_dir: &Path, keep: usize) -> usize {
+    let Ok(rd) = std::fs::read_dir(backups_dir(data_dir)) else {
+        return 0;
+    };
+    let mut files: Vec<PathBuf> = rd
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.is_file()
+                && p.file_name()
+                    .and_then(|n| n.to_str())
+                    .map(|n| n.starts_with("toonana-") && n.ends_with(".sqlite"))
+                    .unwrap_or(false)
+        })
+        .collect();
+    // Timestamped names sort chronologically
+    files.sort();
+    let excess = files.len().saturating_sub(keep);
+    let mut removed = 0;
+    for p in files.into_iter().take(excess) {
+        if std::fs::remove_file(&p).is_ok() {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+// A comic job counts as running while its task is alive and it has started (queued jobs
+// may wait indefinitely on a paused queue) but not reached a terminal stage
+pub fn any_job_running(
+    jobs: &DashMap<String, JoinHandle<()>>,
+    comic_status: &DashMap<String, ComicJobStatus>,
+) -> bool {
+    comic_status.iter().any(|s| {
+        !s.stage.is_terminal()
+            && !matches!(s.stage, ComicStage::Queued)
+            && jobs.get(s.key()).map(|h| !h.is_finished()).unwrap_or(false)
+    })
+}
+
+/// Background loop for the `auto_backup` setting. Settings are re-read every cycle so
+/// enabling/disabling or changing the interval takes effect without a restart.
+pub fn spawn_auto_backup(
+    pool: Pool<Sqlite>,
+    data_dir: PathBuf,
+    jobs: Arc<DashMap<String, JoinHandle<()>>>,
+    comic_status: Arc<DashMap<String, ComicJobStatus>>,
+) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let cfg = load_settings_from_dir(&data_dir).auto_backup.unwrap_or_default();
+            let interval = Duration::from_secs(u64::from(cfg.interval_hours.max(1)) * 3600);
+            tokio::time::sleep(interval).await;
+
+            let cfg = load_settings_from_dir(&data_dir).auto_backup.unwrap_or_default();
+            if !cfg.enabled {
+                continue;
+            }
+            while any_job_running(&jobs, &comic_status) {
+                info!("auto-backup: comic job running, postponing");
+                tokio::time::sleep(BUSY_RETRY).await;
+            }
+            match backup_database(&pool, &data_dir).await {
+                Ok(path) => {
+                    let pruned = prune_backups(&data_dir, cfg.keep_count.max(1) as usize);
+                    info!(path = %path.display(), pruned, "auto-backup: completed");
+                }
+                Err(e) => warn!(error = %e, "auto-backup: failed"),
+            }
+        }
+    });
+}
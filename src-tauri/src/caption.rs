@@ -0,0 +1,181 @@
+use ab_glyph::{FontVec, PxScale};
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
+use image::{ImageFormat, Rgb, RgbImage};
+use imageproc::drawing::{draw_filled_rect_mut, draw_text_mut, text_size};
+use imageproc::rect::Rect;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::settings::Settings;
+
+const CARD_SIZE: u32 = 1080;
+const MARGIN: u32 = 120;
+const MAX_FONT_PX: f32 = 84.0;
+const MIN_FONT_PX: f32 = 28.0;
+const LINE_SPACING: f32 = 1.3;
+
+// Fonts tried when `caption_font_path` isn't set; the first readable one wins
+const SYSTEM_FONT_CANDIDATES: &[&str] = &[
+    "/System/Library/Fonts/Supplemental/Georgia.ttf",
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+    "/Library/Fonts/Arial.ttf",
+    "C:\\Windows\\Fonts\\georgia.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSerif.ttf",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf",
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CaptionCard {
+    pub image_base64: String,
+    pub mime: String,
+    pub width: u32,
+    pub height: u32,
+    pub saved_path: Option<String>,
+}
+
+struct CardTheme {
+    background: Rgb<u8>,
+    frame: Rgb<u8>,
+    text: Rgb<u8>,
+}
+
+// Pick colors from keywords in the style hint; unknown hints get the light default
+fn theme_for(style_hint: Option<&str>) -> CardTheme {
+    let hint = style_hint.unwrap_or_default().to_ascii_lowercase();
+    let has = |words: &[&str]| words.iter().any(|w| hint.contains(w));
+    if has(&["noir", "dark", "night", "moody"]) {
+        CardTheme { background: Rgb([24, 24, 28]), frame: Rgb([70, 70, 80]), text: Rgb([240, 240, 240]) }
+    } else if has(&["vintage", "sepia", "retro", "old"]) {
+        CardTheme { background: Rgb([236, 222, 196]), frame: Rgb([150, 115, 80]), text: Rgb([70, 50, 35]) }
+    } else if has(&["pastel", "soft", "cute", "kawaii"]) {
+        CardTheme { background: Rgb([252, 232, 240]), frame: Rgb([200, 170, 220]), text: Rgb([80, 60, 100]) }
+    } else if has(&["bold", "pop", "vibrant", "manga", "comic"]) {
+        CardTheme { background: Rgb([255, 214, 0]), frame: Rgb([20, 20, 20]), text: Rgb([20, 20, 20]) }
+    } else {
+        CardTheme { background: Rgb([250, 247, 240]), frame: Rgb([210, 200, 185]), text: Rgb([40, 40, 40]) }
+    }
+}
+
+pub fn load_font(settings: &Settings) -> Result<FontVec> {
+    let candidates = settings
+        .caption_font_path
+        .iter()
+        .map(|s| s.as_str())
+        .chain(SYSTEM_FONT_CANDIDATES.iter().copied());
+    for path in candidates {
+        if let Ok(bytes) = std::fs::read(path) {
+            if let Ok(font) = FontVec::try_from_vec(bytes) {
+                return Ok(font);
+            }
+        }
+    }
+    Err(anyhow!("no usable font found; set caption_font_path in settings"))
+}
+
+// Greedy word wrap; a single word wider than the line is kept on its own line
+fn wrap_lines(text: &str, font: &FontVec, scale: PxScale, max_width: u32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut current = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if current.is_empty() { word.to_string() } else { format!("{} {}", current, word) };
+            if current.is_empty() || text_size(scale, font, &candidate).0 <= max_width {
+                current = candidate;
+            } else {
+                lines.push(std::mem::replace(&mut current, word.to_string()));
+            }
+        }
+        if !current.is_empty() {
+            lines.push(current);
+        }
+    }
+    lines
+}
+
+/// Draw `text` centered on a themed square card and return it as a PNG.
+pub fn render_caption_card(text: &str, style_hint: Option<&str>, settings: &Settings) -> Result<(Vec<u8>, u32, u32)> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(anyhow!("caption text is empty"));
+    }
+    let font = load_font(settings)?;
+    let theme = theme_for(style_hint);
+    let max_width = CARD_SIZE - 2 * MARGIN;
+    let max_height = CARD_SIZE - 2 * MARGIN;
+
+    // Shrink the font until the wrapped text fits inside the margins
+    let mut px = MAX_FONT_PX;
+    let (scale, lines, line_height) = loop {
+        let scale = PxScale::from(px);
+        let lines = wrap_lines(text, &font, scale, max_width);
+        let line_height = (px * LINE_SPACING).round() as u32;
+        let fits_width = lines.iter().all(|l| text_size(scale, &font, l).0 <= max_width);
+        if (fits_width && line_height * lines.len() as u32 <= max_height) || px <= MIN_FONT_PX {
+            if !fits_width || line_height * lines.len() as u32 > max_height {
+                return Err(anyhow!("caption is too long to fit on a card"));
+            }
+            break (scale, lines, line_height);
+        }
+        px -= 4.0;
+    };
+
+    let mut img = RgbImage::from_pixel(CARD_SIZE, CARD_SIZE, theme.background);
+    // Thin inset frame
+    let inset = (MARGIN / 3) as i32;
+    let frame_size = CARD_SIZE - 2 * inset as u32;
+    draw_filled_rect_mut(&mut img, Rect::at(inset, inset).of_size(frame_size, 4), theme.frame);
+    draw_filled_rect_mut(&mut img, Rect::at(inset, CARD_SIZE as i32 - inset - 4).of_size(frame_size, 4), theme.frame);
+    draw_filled_rect_mut(&mut img, Rect::at(inset, inset).of_size(4, frame_size), theme.frame);
+    draw_filled_rect_mut(&mut img, Rect::at(CARD_SIZE as i32 - inset - 4, inset).of_size(4, frame_size), theme.frame);
+
+    let block_height = line_height * lines.len() as u32;
+    let mut y = ((CARD_SIZE - block_height) / 2) as i32;
+    for line in &lines {
+        let (w, _) = text_size(scale, &font, line);
+        let x = ((CARD_SIZE - w.min(CARD_SIZE)) / 2) as i32;
+        draw_text_mut(&mut img, theme.text, x, y, scale, &font, line);
+        y += line_height as i32;
+    }
+
+    let mut out = Cursor::new(Vec::new());
+    img.write_to(&mut out, ImageFormat::Png).context("encode caption card")?;
+    Ok((out.into_inner(), CARD_SIZE, CARD_SIZE))
+}
+
+pub fn captions_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("captions")
+}
+
+/// Render a caption card and optionally save it under `captions/`.
+pub async fn caption_card(
+    text: &str,
+    style_hint: Option<&str>,
+    save: bool,
+    settings: &Settings,
+    data_dir: &Path,
+) -> Result<CaptionCard> {
+    let (bytes, width, height) = render_caption_card(text, style_hint, settings)?;
+    let saved_path = if save {
+        let dir = captions_dir(data_dir);
+        tokio::fs::create_dir_all(&dir).await.context("create captions dir")?;
+        let path = dir.join(format!("caption-{}.png", uuid::Uuid::new_v4()));
+        tokio::fs::write(&path, &bytes).await.context("write caption card")?;
+        info!(path = %path.display(), "caption card saved");
+        Some(path.display().to_string())
+    } else {
+        None
+    };
+    Ok(CaptionCard {
+        image_base64: B64.encode(&bytes),
+        mime: "image/png".to_string(),
+        width,
+        height,
+        saved_path,
+    })
+}
@@ -3,14 +3,23 @@ use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 use sqlx::{Pool, Sqlite};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
-use crate::database::{get_entry_body, now_iso};
-use crate::gemini::{generate_image_with_progress, nano_banana_generate_image};
-use crate::ollama::generate_streaming;
-use crate::settings::load_settings_from_dir;
+use crate::database::{
+    get_entry_body, get_style_preset, register_asset, insert_panels, insert_storyboard, latest_storyboard, now_iso, record_job_metric, set_panel_image, upsert_comic_job,
+    upsert_storyboard, StylePreset,
+};
+use crate::error::{ErrorKind, ToonanaError};
+use crate::gemini::{generate_image_with_progress, nano_banana_generate_image, Cancelled, NanoBananaError};
+use crate::sd::a1111_generate_image;
+use crate::ollama::{resolve_model, OllamaOptions, StreamSummary};
+use crate::settings::{load_settings_from_dir, ImageProvider, Settings};
+use crate::utils::{ensure_free_space, DEFAULT_MIN_FREE_DISK_MB};
+use tauri::{AppHandle, Emitter};
 use tracing::{info, warn, error, debug, instrument};
 
 pub type JobId = String;
@@ -23,9 +32,29 @@ pub enum ComicStage {
     Storyboarding,
     Prompting,
     Rendering { completed: u32, total: u32 },
+    /// The image provider answered 429/503; the request is retried after `retry_in_secs`
+    RateLimited { retry_in_secs: u64, attempt: u32 },
     Saving,
     Done,
-    Failed { error: String },
+    /// Storyboarding was stopped on request; the job ends with the text produced so far
+    StoryboardReady { partial: bool },
+    Failed {
+        error: String,
+        /// What went wrong, for the UI to branch on; absent on jobs saved before it existed
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kind: Option<ErrorKind>,
+    },
+}
+
+impl ComicStage {
+    pub fn failed(error: impl Into<ToonanaError>) -> Self {
+        let error = error.into();
+        ComicStage::Failed { kind: Some(error.kind()), error: error.message().to_string() }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, ComicStage::Done | ComicStage::StoryboardReady { .. } | ComicStage::Failed { .. })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,6 +66,109 @@ pub struct ComicJobStatus {
     pub updated_at: String,
     pub result_image_path: Option<String>,
     pub storyboard_text: Option<String>,
+    /// Extra details about how the result was produced (e.g. skipped options)
+    #[serde(default)]
+    pub meta: Option<serde_json::Value>,
+    /// One image per panel, in panel order, when rendered in `RenderMode::PerPanel`
+    #[serde(default)]
+    pub panel_image_paths: Vec<String>,
+}
+
+/// Payload of the `comic://done` event, emitted once when a job finishes successfully.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComicDoneEvent {
+    pub job_id: String,
+    pub entry_id: String,
+    pub image_path: String,
+    pub thumbnail_path: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+/// Optional knobs for a comic job; every field has a sensible default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ComicJobOptions {
+    /// Ask the LLM for a materially different storyboard than a first take.
+    pub reroll: bool,
+    /// Reference image whose art style the comic should mimic (overrides the setting).
+    pub style_reference_image_path: Option<String>,
+    /// Run immediately even during quiet hours.
+    pub urgent: bool,
+    /// Only storyboard this part of the entry body.
+    pub selection: Option<TextSelection>,
+    pub render_mode: RenderMode,
+    /// Render this storyboard instead of generating one (used by `retry_comic_job`).
+    pub storyboard_text: Option<String>,
+    /// Job this one retries; recorded in the result's meta.
+    pub retry_of: Option<String>,
+    /// Style preset whose prompt fragment (and layout hint) is merged into image prompts.
+    pub style_preset_id: Option<String>,
+    /// Panels to ask for, clamped to `MIN_PANEL_COUNT..=MAX_PANEL_COUNT`; unset keeps 3-4.
+    pub panel_count: Option<u32>,
+}
+
+// Bounds for `ComicJobOptions::panel_count`
+pub const MIN_PANEL_COUNT: u32 = 1;
+pub const MAX_PANEL_COUNT: u32 = 8;
+
+impl ComicJobOptions {
+    /// The requested panel count, clamped to the supported range.
+    pub fn panel_count(&self) -> Option<usize> {
+        self.panel_count.map(|n| n.clamp(MIN_PANEL_COUNT, MAX_PANEL_COUNT) as usize)
+    }
+}
+
+// "3-4" when no count was requested, as the prompts have always said
+fn panel_count_phrase(panel_count: Option<usize>) -> String {
+    panel_count.map_or_else(|| "3-4".to_string(), |n| n.to_string())
+}
+
+/// How the storyboard is turned into images.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderMode {
+    /// One image holding every panel (Nano-Banana when configured, else Gemini)
+    #[default]
+    Combined,
+    /// One Gemini image per panel, rendered `panel_render_concurrency` at a time
+    PerPanel,
+}
+
+/// A `[start, end)` range of character (not byte) offsets into an entry body.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TextSelection {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl TextSelection {
+    /// The selected text, or an error when the range is empty, blank or out of bounds.
+    pub fn slice(&self, text: &str) -> Result<String, String> {
+        let len = text.chars().count();
+        if self.start >= self.end {
+            return Err(format!("selection start ({}) must be before end ({})", self.start, self.end));
+        }
+        if self.end > len {
+            return Err(format!("selection end ({}) is past the end of the entry ({} characters)", self.end, len));
+        }
+        let selected: String = text.chars().skip(self.start).take(self.end - self.start).collect();
+        if selected.trim().is_empty() {
+            return Err("selection contains no text".to_string());
+        }
+        Ok(selected)
+    }
+}
+
+/// How long until quiet hours end, if we're inside them right now. `local_offset` is the
+/// one captured at startup; `time` can't look it up once the runtime's threads exist.
+pub fn quiet_hours_remaining(settings: &Settings, local_offset: time::UtcOffset) -> Option<std::time::Duration> {
+    let window = settings.quiet_hours.as_ref()?;
+    let now = time::OffsetDateTime::now_utc().to_offset(local_offset);
+    let minute_of_day = u32::from(now.hour()) * 60 + u32::from(now.minute());
+    window
+        .remaining_minutes(minute_of_day)
+        .map(|m| std::time::Duration::from_secs(u64::from(m) * 60 - u64::from(now.second())))
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -46,6 +178,34 @@ pub struct ExportPanel {
     pub dialogue_cipher: Option<Vec<u8>>,
 }
 
+/// Newest comic image (png/jpg/webp) in an entry's image folder, by modification time.
+/// Thumbnails are never returned, and single panels only when the entry has no full comic
+/// (per-panel jobs, where the first panel stands in for the comic).
+pub fn newest_entry_image(entry_img_dir: &Path) -> Option<PathBuf> {
+    // (is a full comic, modified): higher wins
+    let mut best: Option<(PathBuf, (bool, std::time::SystemTime))> = None;
+    for ent in std::fs::read_dir(entry_img_dir).ok()?.flatten() {
+        let path = ent.path();
+        if !path.is_file() { continue; }
+        let ext_ok = path.extension().and_then(|s| s.to_str()).map(|ext| {
+            matches!(ext.to_ascii_lowercase().as_str(), "png" | "jpg" | "jpeg" | "webp")
+        }).unwrap_or(false);
+        if !ext_ok { continue; }
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        if name.contains(".thumb.") { continue; }
+        let full_comic = !name.contains("-panel-");
+        let modified = ent.metadata().ok()
+            .and_then(|m| m.modified().ok())
+            .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
+        let rank = (full_comic, modified);
+        match &best {
+            Some((_, r)) if rank <= *r => {}
+            _ => { best = Some((path, rank)); }
+        }
+    }
+    best.map(|(p, _)| p)
+}
+
 pub fn decode_base64_png(s: &str) -> Result<Vec<u8>> {
     let data = if let Some(idx) = s.find(",") {
         &s[(idx + 1)..]
@@ -74,180 +234,1445 @@ pub fn guess_image_extension(bytes: &[u8]) -> &'static str {
     "png"
 }
 
-fn build_gemini_image_prompt(storyboard_text: &str, style: &str) -> String {
+/// Read width/height from the image header without decoding the pixels.
+pub fn image_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    image::ImageReader::new(std::io::Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
+
+/// Canonical extension for an `output_image_format` setting value, if it's supported.
+pub fn parse_output_format(value: &str) -> Option<&'static str> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "png" => Some("png"),
+        "jpg" | "jpeg" => Some("jpg"),
+        "webp" => Some("webp"),
+        _ => None,
+    }
+}
+
+/// Re-encode `bytes` as `target` ("png", "jpg" or "webp").
+pub fn convert_image_format(bytes: &[u8], target: &str) -> Result<Vec<u8>> {
+    let img = image::load_from_memory(bytes).map_err(|e| anyhow!("decode image: {e}"))?;
+    let mut out = std::io::Cursor::new(Vec::new());
+    match target {
+        // JPEG has no alpha channel
+        "jpg" => image::DynamicImage::ImageRgb8(img.to_rgb8())
+            .write_to(&mut out, image::ImageFormat::Jpeg),
+        "webp" => image::DynamicImage::ImageRgba8(img.to_rgba8())
+            .write_to(&mut out, image::ImageFormat::WebP),
+        _ => img.write_to(&mut out, image::ImageFormat::Png),
+    }
+    .map_err(|e| anyhow!("encode {target}: {e}"))?;
+    Ok(out.into_inner())
+}
+
+// Long edge of result thumbnails, in pixels
+const THUMBNAIL_MAX_EDGE: u32 = 400;
+
+/// Downscale to fit `THUMBNAIL_MAX_EDGE` on the long edge (never upscaled), encoded as PNG.
+pub fn make_thumbnail(bytes: &[u8]) -> Result<Vec<u8>> {
+    let img = image::load_from_memory(bytes).map_err(|e| anyhow!("decode image: {e}"))?;
+    let thumb = if img.width().max(img.height()) > THUMBNAIL_MAX_EDGE {
+        img.thumbnail(THUMBNAIL_MAX_EDGE, THUMBNAIL_MAX_EDGE)
+    } else {
+        img
+    };
+    let mut out = std::io::Cursor::new(Vec::new());
+    thumb
+        .write_to(&mut out, image::ImageFormat::Png)
+        .map_err(|e| anyhow!("encode thumbnail: {e}"))?;
+    Ok(out.into_inner())
+}
+
+/// Write a thumbnail next to `image_path` (`<name>.thumb.png`) and record it as a
+/// `thumbnail` asset. Best-effort: failures are logged and yield `None`, since the
+/// full-size image is already saved.
+async fn save_result_thumbnail(
+    pool: &Pool<Sqlite>,
+    image_path: &Path,
+    bytes: Vec<u8>,
+    job_id: &str,
+    entry_id: &str,
+) -> Option<String> {
+    let thumb = match tokio::task::spawn_blocking(move || make_thumbnail(&bytes)).await {
+        Ok(Ok(t)) => t,
+        Ok(Err(e)) => {
+            warn!(error = %e, "thumbnail generation failed");
+            return None;
+        }
+        Err(e) => {
+            warn!(error = %e, "thumbnail task failed");
+            return None;
+        }
+    };
+    let thumb_path = image_path.with_extension("thumb.png");
+    if let Err(e) = tokio::fs::write(&thumb_path, &thumb).await {
+        warn!(error = %e, path = %thumb_path.display(), "failed to write thumbnail");
+        return None;
+    }
+    record_image_asset(pool, "thumbnail", &thumb_path, job_id, entry_id, Some(image_path)).await;
+    Some(thumb_path.display().to_string())
+}
+
+/// Register a saved image in `assets` (`comic`, `panel` or `thumbnail`). Best-effort like
+/// the thumbnail itself: the file is already on disk, so a failure is only logged.
+async fn record_image_asset(
+    pool: &Pool<Sqlite>,
+    kind: &str,
+    path: &Path,
+    job_id: &str,
+    entry_id: &str,
+    source: Option<&Path>,
+) {
+    let mut meta = serde_json::json!({
+        "entry_id": entry_id,
+        "job_id": job_id,
+        "created_at": now_iso(),
+    });
+    if let Some(source) = source {
+        meta["source"] = serde_json::json!(source.display().to_string());
+    }
+    if let Err(e) = register_asset(pool, kind, &path.display().to_string(), &meta).await {
+        warn!(error = %e, kind, path = %path.display(), "failed to record image asset");
+    }
+}
+
+pub fn guess_image_mime(bytes: &[u8]) -> &'static str {
+    match guess_image_extension(bytes) {
+        "jpg" => "image/jpeg",
+        "webp" => "image/webp",
+        _ => "image/png",
+    }
+}
+
+// Temperature bump applied on reroll, on top of the configured (or Ollama default) temperature
+const REROLL_TEMPERATURE_BOOST: f32 = 0.4;
+
+fn storyboard_options(options: &ComicJobOptions, settings: &Settings) -> Option<OllamaOptions> {
+    if !options.reroll {
+        return None;
+    }
+    let base = settings.ollama_temperature.unwrap_or(0.8);
+    Some(OllamaOptions {
+        temperature: Some((base + REROLL_TEMPERATURE_BOOST).min(2.0)),
+        seed: Some(rand::random::<u32>() as i64),
+        num_predict: None,
+        top_p: None,
+        num_ctx: None,
+    })
+}
+
+/// Name of the backend `generate_streaming` uses, as recorded in job metrics.
+pub(crate) fn storyboard_provider(settings: &Settings) -> &'static str {
+    if crate::openai::is_selected(settings) {
+        crate::openai::PROVIDER_OPENAI_COMPATIBLE
+    } else {
+        "ollama"
+    }
+}
+
+/// Stream text from the backend selected by `llm_provider` (Ollama by default).
+pub(crate) async fn generate_streaming(
+    model: Option<String>,
+    prompt: String,
+    settings: &Settings,
+    options: Option<OllamaOptions>,
+    stop: Option<&AtomicBool>,
+    on_chunk: impl FnMut(&str),
+) -> Result<StreamSummary, String> {
+    if crate::openai::is_selected(settings) {
+        crate::openai::generate_streaming(model, prompt, settings, options, stop, on_chunk).await
+    } else {
+        crate::ollama::generate_streaming(model, prompt, settings, options, stop, on_chunk).await
+    }
+}
+
+// Used when neither the request nor settings specify a style
+const FALLBACK_COMIC_STYLE: &str = "clean cartoon, soft colors";
+
+/// Pick the style for a comic: explicit request first, then the configured default.
+pub fn resolve_comic_style(style: Option<&str>, settings: &Settings) -> String {
+    style
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .or_else(|| {
+            settings
+                .default_comic_style
+                .as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+        })
+        .unwrap_or(FALLBACK_COMIC_STYLE)
+        .to_string()
+}
+
+/// The style line for image prompts: the style, followed by the preset's fragment if any.
+fn style_with_preset(style: &str, preset: Option<&StylePreset>) -> String {
+    match preset {
+        Some(p) if !style.contains(p.prompt_fragment.as_str()) => format!("{}; {}", style, p.prompt_fragment),
+        _ => style.to_string(),
+    }
+}
+
+// More than this many panels don't fit legibly in one row, so they're laid out in two
+const MAX_SINGLE_ROW_PANELS: usize = 4;
+
+fn default_layout_hint(panel_count: Option<usize>) -> String {
+    let n = panel_count_phrase(panel_count);
+    match panel_count {
+        Some(count) if count > MAX_SINGLE_ROW_PANELS => {
+            format!("{n} panels in two rows, read left-to-right then top-to-bottom, equal size, small gutters.")
+        }
+        _ => format!("{n} panels, left-to-right in one horizontal row, equal width, small gutters."),
+    }
+}
+
+fn build_gemini_image_prompt(storyboard_text: &str, style: &str, preset: Option<&StylePreset>, panel_count: Option<usize>) -> String {
     // A structured, style-aware prompt for image models
-    // Render exactly 3 panels in a single row, guided by the storyboard
-    format!(r#"Task: Render a single-row comic with 3-4 panels from the storyboard.
+    // Render the requested number of panels (3-4 by default), guided by the storyboard
+    let layout = preset
+        .and_then(|p| p.layout_hint.clone())
+        .unwrap_or_else(|| default_layout_hint(panel_count));
+    let n = panel_count_phrase(panel_count);
+    let rows = if panel_count.is_some_and(|c| c > MAX_SINGLE_ROW_PANELS) { "two-row" } else { "single-row" };
+    format!(r#"Task: Render a {rows} comic with {n} panels from the storyboard.
 
 Style: {}
 Layout Guidelines:
-- Layout: 3-4 panels, left-to-right in one horizontal row, equal width, small gutters.
+- Layout: {}
 - Keep characters consistent across panels (appearance, clothing, hair).
 - Include speech bubbles and captions exactly as written in the storyboard.
 - Avoid extra text, UI, or watermarks beyond bubbles/captions.
 - Maintain clear line art, readable bubbles, cohesive backgrounds.
 - Tone: light, charming, hopeful.
 
-Output: One coherent 3-4 panel comic image (single row).
+Output: One coherent {n} panel comic image ({rows}).
 
 Storyboard:
 {}"#,
-        style,
+        style_with_preset(style, preset),
+        layout,
         storyboard_text
     )
 }
 
-#[instrument(skip(status_map, db_pool, data_root), fields(job_id = %job_id, entry_id = %entry_id, style = %style))]
+// Openers models put before the storyboard itself ("Sure, here's your storyboard:")
+const PREAMBLE_OPENERS: &[&str] = &[
+    "sure", "here's", "here is", "certainly", "okay", "ok,", "of course", "absolutely",
+];
+
+// A first line counts as a preamble only when it's short, opens with a known phrase,
+// and doesn't look like panel content itself
+fn is_preamble_line(line: &str) -> bool {
+    let lower = line.trim().to_ascii_lowercase();
+    lower.chars().count() <= 120
+        && PREAMBLE_OPENERS.iter().any(|p| lower.starts_with(p))
+        && !lower.contains("panel ")
+        && !lower.starts_with("caption")
+}
+
+/// Tidy model output before it's stored or parsed: CRLF -> LF, trailing whitespace
+/// trimmed, runs of blank lines collapsed, and a leading chatty preamble line dropped.
+pub fn normalize_storyboard_text(text: &str) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.lines().map(str::trim_end) {
+        let blank = line.is_empty();
+        // Skip leading blanks and any blank following another blank
+        if blank && lines.last().map(|l| l.is_empty()).unwrap_or(true) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().map(|l| l.is_empty()).unwrap_or(false) {
+        lines.pop();
+    }
+    // Only drop the preamble when real content follows it
+    if lines.len() > 1 && is_preamble_line(lines[0]) {
+        lines.remove(0);
+        while lines.first().map(|l| l.is_empty()).unwrap_or(false) {
+            lines.remove(0);
+        }
+    }
+    lines.join("\n")
+}
+
+// "Panel 1", "**Panel 1:**", "## Panel 1 -" but not "Panel 10"
+fn is_first_panel_heading(line: &str) -> bool {
+    let lower = line
+        .trim_start_matches(|c: char| c.is_whitespace() || matches!(c, '#' | '*' | '-' | '_'))
+        .to_ascii_lowercase();
+    lower
+        .strip_prefix("panel 1")
+        .map(|rest| !rest.starts_with(|c: char| c.is_ascii_digit()))
+        .unwrap_or(false)
+}
+
+/// Remove wrappers models put around the storyboard: a surrounding ``` fence (with or
+/// without a language tag) and anything before the first "Panel 1" heading. Text
+/// without a "Panel 1" heading keeps its content and only loses the fences.
+pub fn strip_storyboard_wrappers(text: &str) -> String {
+    let mut lines: Vec<&str> = text.lines().collect();
+
+    // Fenced block: keep the inside of the first fence pair that holds panels
+    let fences: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| l.trim_start().starts_with("```"))
+        .map(|(i, _)| i)
+        .collect();
+    if let [open, close, ..] = fences[..] {
+        let inner = &lines[open + 1..close];
+        let wraps_everything = lines[..open].iter().all(|l| l.trim().is_empty());
+        if wraps_everything || inner.iter().any(|l| l.to_ascii_lowercase().contains("panel")) {
+            lines = inner.to_vec();
+        }
+    } else if let [only] = fences[..] {
+        // Unclosed fence (often truncated output): drop just the fence line
+        lines.remove(only);
+    }
+
+    if let Some(start) = lines.iter().position(|l| is_first_panel_heading(l)) {
+        lines.drain(..start);
+    }
+    lines.join("\n").trim().to_string()
+}
+
+pub const DEFAULT_MAX_CAPTION_WORDS: usize = 12;
+
+/// Shorten every caption and dialogue line ("Caption:", "Character 1:", ...) to at most
+/// `max_words` words, ending cut lines with an ellipsis. Descriptions are left alone.
+pub fn cap_caption_words(text: &str, max_words: usize) -> String {
+    if max_words == 0 {
+        return text.to_string();
+    }
+    text.lines()
+        .map(|line| {
+            let Some((key, value)) = line.split_once(':') else { return line.to_string() };
+            let k = key.trim_start_matches(|c: char| c.is_whitespace() || matches!(c, '*' | '-')).to_ascii_lowercase();
+            if !(k.starts_with("caption") || k.starts_with("character")) {
+                return line.to_string();
+            }
+            let words: Vec<&str> = value.split_whitespace().collect();
+            if words.len() <= max_words {
+                return line.to_string();
+            }
+            let kept = words[..max_words].join(" ");
+            let kept = kept.trim_end_matches([',', ';', ':', '.', '-']);
+            // Keep a closing quote when the dialogue was quoted
+            let quote = if value.trim_start().starts_with('"') { "\"" } else { "" };
+            format!("{}: {}…{}", key, kept, quote)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Everything applied to raw model output before a storyboard is stored or rendered:
+/// wrapper stripping, optional normalization, and the caption word cap.
+pub fn clean_storyboard_text(raw: &str, settings: &Settings) -> String {
+    let text = strip_storyboard_wrappers(raw);
+    let text = if settings.normalize_storyboard.unwrap_or(true) {
+        normalize_storyboard_text(&text)
+    } else {
+        text
+    };
+    cap_caption_words(&text, settings.max_caption_words.unwrap_or(DEFAULT_MAX_CAPTION_WORDS))
+}
+
+// "Panel 2", "**Panel 2:**", "## Panel 2 -"
+fn is_panel_heading(line: &str) -> bool {
+    let lower = line
+        .trim_start_matches(|c: char| c.is_whitespace() || matches!(c, '#' | '*' | '-' | '_'))
+        .to_ascii_lowercase();
+    lower
+        .strip_prefix("panel ")
+        .map(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+        .unwrap_or(false)
+}
+
+/// Split a storyboard into one text block per "Panel N" heading (heading included).
+/// Text before the first heading is ignored; a storyboard without headings is one panel.
+pub fn split_storyboard_panels(storyboard_text: &str) -> Vec<String> {
+    let mut panels: Vec<String> = Vec::new();
+    for line in storyboard_text.lines() {
+        if is_panel_heading(line) {
+            panels.push(line.trim().to_string());
+        } else if let Some(current) = panels.last_mut() {
+            if !line.trim().is_empty() {
+                current.push('\n');
+                current.push_str(line.trim_end());
+            }
+        }
+    }
+    if panels.is_empty() && !storyboard_text.trim().is_empty() {
+        panels.push(storyboard_text.trim().to_string());
+    }
+    panels
+}
+
+/// Which characters appear in one panel, as their storyboard numbers ("Character 2" -> 2),
+/// sorted and deduplicated.
+///
+/// Heuristic: a character is present when it has a "Character N:" line in the panel or
+/// is named as "character N" anywhere in the panel's text (e.g. the description).
+/// Character 1 is the journal's author, i.e. the avatar. Panels that name nobody
+/// return an empty list, so they get no character references.
+pub fn panel_characters(panel_text: &str) -> Vec<u32> {
+    let lower = panel_text.to_ascii_lowercase();
+    let mut found: Vec<u32> = lower
+        .match_indices("character")
+        .filter_map(|(i, m)| {
+            let rest = lower[i + m.len()..].trim_start();
+            let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+            digits.parse().ok()
+        })
+        .collect();
+    found.sort_unstable();
+    found.dedup();
+    found
+}
+
+/// One line of dialogue or inner thought: "Character 2: <text>".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PanelLine {
+    pub character: u32,
+    pub text: String,
+}
+
+/// A storyboard panel broken into the fields the storyboard prompt asks for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParsedPanel {
+    /// 0-based position in the storyboard
+    pub idx: u32,
+    pub description: String,
+    pub caption: Option<String>,
+    pub lines: Vec<PanelLine>,
+    /// See `panel_characters`
+    pub characters: Vec<u32>,
+}
+
+impl ParsedPanel {
+    /// Caption and dialogue as display text, one per line ("Character N: ..." kept).
+    pub fn dialogue_text(&self) -> Option<String> {
+        let mut out: Vec<String> = self.caption.iter().cloned().collect();
+        out.extend(self.lines.iter().map(|l| format!("Character {}: {}", l.character, l.text)));
+        (!out.is_empty()).then(|| out.join("\n"))
+    }
+}
+
+// "Character 2" -> 2
+fn character_number(key: &str) -> Option<u32> {
+    key.strip_prefix("character")?.trim().parse().ok()
+}
+
+/// Parse a storyboard into panels with their Description / Caption / Character N lines.
+/// Keys are matched case-insensitively and markdown emphasis around them is ignored;
+/// unknown lines are folded into the description so nothing visual is dropped.
+pub fn parse_storyboard(text: &str) -> Vec<ParsedPanel> {
+    split_storyboard_panels(text)
+        .iter()
+        .enumerate()
+        .map(|(idx, block)| {
+            let mut panel = ParsedPanel {
+                idx: idx as u32,
+                description: String::new(),
+                caption: None,
+                lines: Vec::new(),
+                characters: panel_characters(block),
+            };
+            let mut extra: Vec<&str> = Vec::new();
+            for line in block.lines() {
+                let line = line.trim();
+                if line.is_empty() || is_panel_heading(line) {
+                    continue;
+                }
+                let Some((key, value)) = line.split_once(':') else {
+                    extra.push(line);
+                    continue;
+                };
+                let key = key.trim_matches(|c: char| c.is_whitespace() || matches!(c, '*' | '_' | '-')).to_ascii_lowercase();
+                let value = value.trim().trim_matches('*').trim();
+                if value.is_empty() {
+                    continue;
+                }
+                if key == "description" {
+                    panel.description = value.to_string();
+                } else if key == "caption" {
+                    panel.caption = Some(value.to_string());
+                } else if let Some(character) = character_number(&key) {
+                    panel.lines.push(PanelLine { character, text: value.to_string() });
+                } else {
+                    extra.push(line);
+                }
+            }
+            if !extra.is_empty() {
+                if !panel.description.is_empty() {
+                    extra.insert(0, &panel.description);
+                }
+                panel.description = extra.join(" ");
+            }
+            panel
+        })
+        .collect()
+}
+
+/// Fewest panels a storyboard may have when no count was requested; the prompt asks for 3-4.
+pub const MIN_STORYBOARD_PANELS: usize = 3;
+
+fn build_missing_panels_prompt(storyboard_text: &str, have: usize, want: usize) -> String {
+    format!(r#"This comic storyboard stops after {have} panels but needs {want}. Write ONLY the missing panels, Panel {next} to Panel {want}, continuing the same story and characters.
+
+Use exactly the same structure (Panel N, Description:, optional Caption: and Character N: lines). No commentary, and do not repeat the existing panels.
+
+Existing storyboard:
+{storyboard_text}
+"#,
+        next = have + 1,
+    )
+}
+
+/// With `strict_panel_count` on, ask the model once for the panels missing from a short
+/// storyboard and append them. Errors when it's still below the requested `panel_count`
+/// (or `MIN_STORYBOARD_PANELS`); with the setting off the storyboard is returned untouched.
+pub async fn fill_missing_panels(storyboard_text: String, settings: &Settings, panel_count: Option<usize>) -> Result<String, String> {
+    if !settings.strict_panel_count.unwrap_or(false) {
+        return Ok(storyboard_text);
+    }
+    let want = panel_count.unwrap_or(MIN_STORYBOARD_PANELS);
+    let have = split_storyboard_panels(&storyboard_text).len();
+    if have >= want {
+        return Ok(storyboard_text);
+    }
+    info!(have, want, "storyboard is short; asking for the missing panels");
+    let prompt = build_missing_panels_prompt(&storyboard_text, have, want);
+    let mut extra = String::new();
+    generate_streaming(None, prompt, settings, None, None, |chunk| extra.push_str(chunk))
+        .await
+        .map_err(|e| format!("re-prompt for missing panels failed: {}", e))?;
+    let merged = format!("{}\n{}", storyboard_text, clean_storyboard_text(&extra, settings));
+    let merged = clean_storyboard_text(&merged, settings);
+    let got = split_storyboard_panels(&merged).len();
+    if got < want {
+        return Err(format!(
+            "storyboard has only {} panel(s) after re-prompting; at least {} are needed",
+            got, want
+        ));
+    }
+    Ok(merged)
+}
+
+/// Alt text assembled from the storyboard's descriptions and captions, used when no
+/// vision model is available to describe the rendered image.
+pub fn storyboard_alt_text(storyboard_text: &str) -> Option<String> {
+    let mut panels: Vec<String> = Vec::new();
+    for line in storyboard_text.lines() {
+        let line = line.trim();
+        if line.to_ascii_lowercase().starts_with("panel ") {
+            panels.push(String::new());
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else { continue };
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+        let key = key.trim().to_ascii_lowercase();
+        if key == "description" || key == "caption" {
+            if panels.is_empty() {
+                panels.push(String::new());
+            }
+            let current = panels.last_mut().expect("panel pushed above");
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(value);
+            if !value.ends_with(['.', '!', '?']) {
+                current.push('.');
+            }
+        }
+    }
+    let parts: Vec<String> = panels
+        .into_iter()
+        .filter(|p| !p.is_empty())
+        .enumerate()
+        .map(|(i, p)| format!("Panel {}: {}", i + 1, p))
+        .collect();
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(" "))
+    }
+}
+
+pub fn build_storyboard_prompt(entry_text: &str, reroll: bool, panel_count: Option<usize>) -> String {
+    let reroll_note = if reroll {
+        "\nVariation: Provide a fresh, distinct interpretation of the entry — choose different moments, framing, and dialogue than an obvious first take.\n"
+    } else {
+        ""
+    };
+    let n = panel_count_phrase(panel_count);
+    format!(r#"You are a helpful assistant that writes a short {n}‑panel comic storyboard from a journal entry.
+
+Guidelines:
+- Keep tone light, hopeful, and not too dark; find a positive spin.
+- Avoid heavy or sensitive content; keep it PG and uplifting.
+- Privacy: do not reveal personal or identifying information from the journal entry; do not quote it verbatim. Replace names, places, dates, or unique details with neutral terms (e.g., 'a friend', 'a cafe', 'today').
+- Only include characters or speakers that are clearly present in the journal entry.
+- Do NOT invent specific locations, props, or events beyond what the journal clearly implies. If details are unspecified, use a neutral everyday setting.
+- Maintain continuity across panels.
+
+Output strictly in this structure for exactly {n} panels (no extra commentary, no blank lines between panels):
+Panel 1
+Description: <one concise sentence describing what the viewer sees>
+Caption: <optional; short; ≤ 12 words>
+Character 1: <optional; dialogue or inner thought; ≤ 12 words>
+Character 2: <optional; dialogue; ≤ 12 words>
+Panel 2
+Description: <visual description>
+Caption: <optional>
+Character 1: <optional>
+Panel 3
+Description: <visual description>
+Caption: <optional>
+Character 1: <optional>
+
+Rules:
+- If a field is not needed for a panel, omit that line entirely (do not write "none").
+- Prefer everyday, grounded scenes that could plausibly match the journal entry.
+- Use generic references (e.g., "a friend") instead of names. Do not quote the journal directly.
+{reroll_note}
+Journal Entry:
+{entry_text}
+"#
+    )
+}
+
+/// Job status sink that updates the in-memory map and writes through to `comic_jobs`.
+/// Writes go through one task per job that always stores the latest status, so a burst of
+/// progress updates collapses into a few writes and rows never go back to an older stage.
+struct JobStatusWriter {
+    map: Arc<DashMap<String, ComicJobStatus>>,
+    tx: tokio::sync::watch::Sender<Option<ComicJobStatus>>,
+}
+
+impl JobStatusWriter {
+    fn new(map: Arc<DashMap<String, ComicJobStatus>>, pool: Pool<Sqlite>) -> Self {
+        let (tx, mut rx) = tokio::sync::watch::channel::<Option<ComicJobStatus>>(None);
+        // Ends once the job drops its writer, after persisting the final status
+        tokio::spawn(async move {
+            while rx.changed().await.is_ok() {
+                let status = rx.borrow_and_update().clone();
+                if let Some(status) = status {
+                    if let Err(e) = upsert_comic_job(&pool, &status).await {
+                        warn!(job_id = %status.job_id, error = %e, "failed to persist comic job status");
+                    }
+                }
+            }
+        });
+        Self { map, tx }
+    }
+
+    fn insert(&self, job_id: String, status: ComicJobStatus) {
+        self.map.insert(job_id, status.clone());
+        self.tx.send_replace(Some(status));
+    }
+
+    /// Update the in-memory status only; the row keeps the last persisted one.
+    fn insert_in_memory(&self, job_id: String, status: ComicJobStatus) {
+        self.map.insert(job_id, status);
+    }
+}
+
+fn build_panel_image_prompt(panel: &ParsedPanel, total: usize, style: &str) -> String {
+    panel_image_prompt(panel.idx, total, style, &panel.description, panel.dialogue_text().as_deref())
+}
+
+fn panel_image_prompt(idx: u32, total: usize, style: &str, description: &str, dialogue: Option<&str>) -> String {
+    let mut details = format!("Description: {}", description);
+    if let Some(dialogue) = dialogue {
+        details.push('\n');
+        details.push_str(dialogue);
+    }
+    format!(r#"Task: Render panel {n} of a {total}-panel comic as a single image.
+
+Style: {style}
+Guidelines:
+- Exactly one panel: no borders splitting the image, no other panels.
+- Include speech bubbles and captions exactly as written below.
+- Avoid extra text, UI, or watermarks beyond bubbles/captions.
+- Keep characters consistent with the rest of the comic (appearance, clothing, hair).
+- Tone: light, charming, hopeful.
+
+Panel:
+{details}"#,
+        n = idx + 1,
+    )
+}
+
+/// Message of the error for a provider that can't render single prompts (per-panel, regenerate).
+pub const UNSUPPORTED_PROVIDER_MARKER: &str = "does not support per-panel rendering";
+
+/// The backend `render_prompt_image` sends prompts to first. Nano-banana takes whole
+/// storyboards, so with it selected prompts go to Gemini only when `fallback_to_gemini` is on.
+pub(crate) fn prompt_image_provider(settings: &Settings) -> Result<ImageProvider, String> {
+    match settings.image_provider() {
+        ImageProvider::Gemini => Ok(ImageProvider::Gemini),
+        ImageProvider::A1111 => Ok(ImageProvider::A1111),
+        ImageProvider::NanoBanana if settings.fallback_to_gemini() => Ok(ImageProvider::Gemini),
+        ImageProvider::NanoBanana => Err(format!(
+            "image provider {} {}; choose gemini or a1111, or turn on fallback_to_gemini",
+            ImageProvider::NanoBanana.as_str(),
+            UNSUPPORTED_PROVIDER_MARKER
+        )),
+    }
+}
+
+/// Render an image prompt with the backend picked by `prompt_image_provider`.
+/// An Automatic1111 failure is retried with Gemini only when `fallback_to_gemini` is on.
+/// Returns the image and the provider that produced it.
+pub(crate) async fn render_prompt_image(
+    prompt: &str,
+    settings: &Settings,
+    cancel: &CancellationToken,
+    mut on_progress: impl FnMut(u32, u32),
+    on_rate_limited: impl FnMut(u64, u32) + Send,
+) -> Result<(String, ImageProvider), String> {
+    match prompt_image_provider(settings)? {
+        ImageProvider::A1111 => {}
+        ImageProvider::Gemini | ImageProvider::NanoBanana => {
+            return generate_image_with_progress(prompt, settings, cancel, on_progress, on_rate_limited)
+                .await
+                .map(|b64| (b64, ImageProvider::Gemini));
+        }
+    }
+    let a1111_error = match a1111_generate_image(prompt, settings, cancel, &mut on_progress).await {
+        Ok(b64) => return Ok((b64, ImageProvider::A1111)),
+        Err(e) if cancel.is_cancelled() => return Err(e),
+        Err(e) if !settings.fallback_to_gemini() => return Err(format!("{} (fallback to Gemini is off)", e)),
+        Err(e) => e,
+    };
+    warn!(error = %a1111_error, "a1111 failed, falling back to gemini");
+    generate_image_with_progress(prompt, settings, cancel, on_progress, on_rate_limited)
+        .await
+        .map(|b64| (b64, ImageProvider::Gemini))
+        .map_err(|e| format!("a1111 and gemini fallback both failed; a1111: {}; gemini: {}", a1111_error, e))
+}
+
+// Panels rendered at once when `panel_render_concurrency` is unset
+const DEFAULT_PANEL_RENDER_CONCURRENCY: usize = 2;
+
+/// Panels `render_panels` renders at once: the `panel_render_concurrency` setting, at least 1.
+pub fn panel_render_concurrency(settings: &Settings) -> usize {
+    settings.panel_render_concurrency.unwrap_or(DEFAULT_PANEL_RENDER_CONCURRENCY).max(1)
+}
+
+/// Render each panel as its own image, at most `panel_render_concurrency` at a time.
+/// A failed panel doesn't stop the others; results come back in panel order and
+/// `on_completed` gets the running count of finished panels.
+#[allow(clippy::too_many_arguments)]
+async fn render_panels(
+    panels: &[ParsedPanel],
+    style: &str,
+    settings: &Settings,
+    images_dir: &Path,
+    job_id: &str,
+    min_free_mb: u64,
+    cancel: &CancellationToken,
+    mut on_completed: impl FnMut(u32),
+) -> Vec<Result<PathBuf, String>> {
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(panel_render_concurrency(settings)));
+    let mut set = tokio::task::JoinSet::new();
+    for panel in panels {
+        let prompt = build_panel_image_prompt(panel, panels.len(), style);
+        let mut settings = settings.clone();
+        // Only panels the author (Character 1) appears in are conditioned on the avatar
+        if !panel.characters.contains(&1) {
+            settings.avatar_image_path = None;
+            settings.avatar_description = None;
+        }
+        let (semaphore, images_dir, cancel) = (semaphore.clone(), images_dir.to_path_buf(), cancel.clone());
+        let (idx, stem) = (panel.idx as usize, format!("{}-panel-{}", job_id, panel.idx));
+        set.spawn(async move {
+            let result = async {
+                let _permit = semaphore.acquire_owned().await.map_err(|e| e.to_string())?;
+                let (b64, _) = render_prompt_image(&prompt, &settings, &cancel, |_, _| {}, |_, _| {}).await?;
+                let bytes = decode_base64_png(&b64).map_err(|e| format!("image decode failed: {}", e))?;
+                ensure_free_space(&images_dir, bytes.len() as u64, min_free_mb)?;
+                write_image_file(&images_dir, &stem, guess_image_extension(&bytes), &bytes, &cancel)
+                    .await
+                    .map_err(|e| format!("write image failed: {}", e))
+            }
+            .await;
+            (idx, result)
+        });
+    }
+
+    let mut results: Vec<Result<PathBuf, String>> = panels.iter().map(|_| Err("not rendered".to_string())).collect();
+    let mut completed = 0u32;
+    while let Some(joined) = set.join_next().await {
+        match joined {
+            Ok((idx, result)) => {
+                if let Err(e) = &result {
+                    warn!(idx, error = %e, "panel render failed");
+                }
+                if let Some(slot) = results.get_mut(idx) {
+                    *slot = result;
+                }
+            }
+            Err(e) => error!(error = %e, "panel render task failed"),
+        }
+        completed += 1;
+        on_completed(completed);
+    }
+    results
+}
+
+/// Re-render one panel of the entry's latest storyboard from its stored description and
+/// dialogue and replace that panel's image. The LLM isn't called and other panels are left
+/// alone. Progress is reported in `status_map` under `sub_job_id`; `cancel` stops the render.
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(style, status_map, db_pool, data_root, cancel), fields(sub_job_id = %sub_job_id, entry_id = %entry_id))]
+pub async fn regenerate_panel(
+    sub_job_id: String,
+    entry_id: String,
+    panel_idx: u32,
+    style: Option<String>,
+    status_map: Arc<DashMap<String, ComicJobStatus>>,
+    db_pool: Pool<Sqlite>,
+    data_root: PathBuf,
+    cancel: CancellationToken,
+) -> Result<String, String> {
+    let settings = load_settings_from_dir(&data_root);
+    let storyboard = latest_storyboard(&db_pool, &entry_id)
+        .await?
+        .ok_or_else(|| format!("entry {} has no storyboard", entry_id))?;
+    let total = storyboard.panels.len();
+    let panel = storyboard
+        .panels
+        .iter()
+        .find(|p| p.idx == panel_idx as i64)
+        .ok_or_else(|| format!("storyboard has no panel {} ({} panels)", panel_idx, total))?;
+    let description = panel
+        .prompt
+        .clone()
+        .filter(|p| !p.trim().is_empty())
+        .ok_or_else(|| format!("panel {} has no stored description", panel_idx))?;
+    let style = resolve_comic_style(style.or_else(|| panel.style.clone()).as_deref(), &settings);
+    let prompt = panel_image_prompt(panel_idx, total, &style, &description, panel.dialogue.as_deref());
+
+    let mut render_settings = settings.clone();
+    // Same avatar rule as `render_panels`; panels saved without a character list keep it
+    let characters: Option<Vec<u32>> = panel
+        .meta
+        .as_ref()
+        .and_then(|m| m.get("characters"))
+        .and_then(|c| serde_json::from_value(c.clone()).ok());
+    if characters.is_some_and(|c| !c.contains(&1)) {
+        render_settings.avatar_image_path = None;
+        render_settings.avatar_description = None;
+    }
+
+    let status_map = JobStatusWriter::new(status_map, db_pool.clone());
+    let meta = serde_json::json!({ "regenerate_panel": panel_idx, "panel_id": panel.id });
+    let set_stage = |stage: ComicStage, result_image_path: Option<String>| {
+        status_map.insert(sub_job_id.clone(), ComicJobStatus {
+            job_id: sub_job_id.clone(),
+            entry_id: entry_id.clone(),
+            style: style.clone(),
+            stage,
+            updated_at: now_iso(),
+            result_image_path,
+            storyboard_text: None,
+            meta: Some(meta.clone()),
+            panel_image_paths: Vec::new(),
+        });
+    };
+
+    set_stage(ComicStage::Rendering { completed: 0, total: 100 }, None);
+    let result: Result<PathBuf, String> = async {
+        let (b64, _) = render_prompt_image(
+            &prompt,
+            &render_settings,
+            &cancel,
+            |completed, total| set_stage(ComicStage::Rendering { completed, total }, None),
+            |retry_in_secs, attempt| set_stage(ComicStage::RateLimited { retry_in_secs, attempt }, None),
+        )
+        .await?;
+        if cancel.is_cancelled() {
+            return Err("cancelled".to_string());
+        }
+        let bytes = decode_base64_png(&b64).map_err(|e| format!("image decode failed: {}", e))?;
+        set_stage(ComicStage::Saving, None);
+        let images_dir = data_root.join("images").join(&entry_id);
+        tokio::fs::create_dir_all(&images_dir)
+            .await
+            .map_err(|e| format!("create image dir failed: {}", e))?;
+        let min_free_mb = settings.min_free_disk_mb.unwrap_or(DEFAULT_MIN_FREE_DISK_MB);
+        ensure_free_space(&images_dir, bytes.len() as u64, min_free_mb)?;
+
+        let ext = guess_image_extension(&bytes);
+        let old = panel.image_path.as_deref().map(PathBuf::from).filter(|p| p.is_file());
+        let path = match old {
+            // Same format: replace the file in place, via a temp file so a failed write
+            // leaves the old image intact
+            Some(old) if old.extension().and_then(|e| e.to_str()) == Some(ext) => {
+                let tmp = old.with_extension(format!("{}.part", ext));
+                tokio::fs::write(&tmp, &bytes)
+                    .await
+                    .map_err(|e| format!("write image failed: {}", e))?;
+                tokio::fs::rename(&tmp, &old)
+                    .await
+                    .map_err(|e| format!("replace image failed: {}", e))?;
+                old
+            }
+            old => {
+                let stem = format!("{}-panel-{}", sub_job_id, panel_idx);
+                let path = write_unique_file(&images_dir, &stem, ext, &bytes)
+                    .await
+                    .map_err(|e| format!("write image failed: {}", e))?;
+                if let Some(old) = old {
+                    if let Err(e) = tokio::fs::remove_file(&old).await {
+                        warn!(path = %old.display(), error = %e, "failed to remove replaced panel image");
+                    }
+                }
+                path
+            }
+        };
+        record_image_asset(&db_pool, "panel", &path, &sub_job_id, &entry_id, None).await;
+        set_panel_image(&db_pool, &panel.id, &path.display().to_string()).await?;
+        Ok(path)
+    }
+    .await;
+
+    match result {
+        Ok(path) => {
+            let path = path.display().to_string();
+            info!(panel_idx, path = %path, "panel regenerated");
+            set_stage(ComicStage::Done, Some(path.clone()));
+            Ok(path)
+        }
+        Err(e) => {
+            error!(panel_idx, error = %e, "panel regeneration failed");
+            set_stage(ComicStage::failed(e.clone()), None);
+            Err(e)
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(options, app, queue_paused, storyboard_stop, cancel, job_slots, status_map, db_pool, data_root, local_offset), fields(job_id = %job_id, entry_id = %entry_id, style = %style))]
 pub async fn create_comic_job(
     job_id: String,
     entry_id: String,
     style: String,
+    options: ComicJobOptions,
+    app: AppHandle,
+    mut queue_paused: tokio::sync::watch::Receiver<bool>,
+    storyboard_stop: Arc<AtomicBool>,
+    cancel: CancellationToken,
+    job_slots: Arc<tokio::sync::Semaphore>,
     status_map: Arc<DashMap<String, ComicJobStatus>>,
     db_pool: Pool<Sqlite>,
     data_root: PathBuf,
+    local_offset: time::UtcOffset,
 ) -> JoinHandle<()> {
     let jid = job_id.clone();
     let eid = entry_id.clone();
     let st = style.clone();
     
     tokio::spawn(async move {
-        // Step 1: Parse entry
-        info!("comic job queued -> parsing");
-        status_map.insert(jid.clone(), ComicJobStatus {
-            job_id: jid.clone(),
-            entry_id: eid.clone(),
-            style: st.clone(),
-            stage: ComicStage::Parsing,
-            updated_at: now_iso(),
-            result_image_path: None,
-            storyboard_text: None,
-        });
-        tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+        let status_map = JobStatusWriter::new(status_map, db_pool.clone());
+        // Stay queued until the queue is unpaused, quiet hours are over and a job slot is
+        // free, all at once: a pause or quiet hours can start while waiting for the slot, so
+        // the permit goes back and the job waits again. It is held until the job ends.
+        let _slot = loop {
+            if *queue_paused.borrow() {
+                info!("comic queue paused; job waiting");
+            }
+            if queue_paused.wait_for(|paused| !*paused).await.is_err() {
+                // Sender dropped: the app is shutting down
+                return;
+            }
 
-        // Step 2: Storyboard
-        debug!("comic job -> storyboarding");
-        status_map.insert(jid.clone(), ComicJobStatus {
-            job_id: jid.clone(),
-            entry_id: eid.clone(),
-            style: st.clone(),
-            stage: ComicStage::Storyboarding,
-            updated_at: now_iso(),
-            result_image_path: None,
-            storyboard_text: None,
-        });
+            // Hold non-urgent jobs until quiet hours end. Settings are re-read each round so
+            // changing or clearing the window takes effect without restarting the job.
+            if !options.urgent {
+                while let Some(remaining) = quiet_hours_remaining(&load_settings_from_dir(&data_root), local_offset) {
+                    info!(wait_secs = remaining.as_secs(), "quiet hours; job waiting");
+                    status_map.insert(jid.clone(), ComicJobStatus {
+                        job_id: jid.clone(),
+                        entry_id: eid.clone(),
+                        style: st.clone(),
+                        stage: ComicStage::Queued,
+                        updated_at: now_iso(),
+                        result_image_path: None,
+                        storyboard_text: None,
+                        meta: Some(serde_json::json!({ "quiet_hours": true, "wait_secs": remaining.as_secs() })),
+                        panel_image_paths: Vec::new(),
+                    });
+                    tokio::time::sleep(remaining.min(std::time::Duration::from_secs(60))).await;
+                }
+            }
+
+            let permit = match job_slots.clone().try_acquire_owned() {
+                Ok(permit) => permit,
+                Err(_) => {
+                    info!("all job slots busy; job waiting");
+                    status_map.insert(jid.clone(), ComicJobStatus {
+                        job_id: jid.clone(),
+                        entry_id: eid.clone(),
+                        style: st.clone(),
+                        stage: ComicStage::Queued,
+                        updated_at: now_iso(),
+                        result_image_path: None,
+                        storyboard_text: None,
+                        meta: Some(serde_json::json!({ "waiting_for_slot": true })),
+                        panel_image_paths: Vec::new(),
+                    });
+                    match job_slots.clone().acquire_owned().await {
+                        Ok(permit) => permit,
+                        // Semaphore closed: the app is shutting down
+                        Err(_) => return,
+                    }
+                }
+            };
+            let quiet = !options.urgent && quiet_hours_remaining(&load_settings_from_dir(&data_root), local_offset).is_some();
+            if !*queue_paused.borrow() && !quiet {
+                break permit;
+            }
+            drop(permit);
+        };
+
+        let mut settings = load_settings_from_dir(&data_root);
+        if let Some(p) = options.style_reference_image_path.clone() {
+            settings.style_reference_image_path = Some(p);
+        }
+
+        let (storyboard_text, stream_summary, parsed_panels, panel_ids) = if let Some(text) = options.storyboard_text.clone() {
+            // A storyboard from an earlier run: skip parsing and the LLM, go straight to rendering
+            info!(chars = text.len(), "reusing existing storyboard; skipping to rendering");
+            let parsed_panels = parse_storyboard(&text);
+            // Its panel rows belong to the original run, so per-panel paths aren't recorded there
+            (text, StreamSummary::default(), parsed_panels, Vec::new())
+        } else {
+            // Step 1: Parse entry
+            info!("comic job queued -> parsing");
+            status_map.insert(jid.clone(), ComicJobStatus {
+                job_id: jid.clone(),
+                entry_id: eid.clone(),
+                style: st.clone(),
+                stage: ComicStage::Parsing,
+                updated_at: now_iso(),
+                result_image_path: None,
+                storyboard_text: None,
+                meta: None,
+                panel_image_paths: Vec::new(),
+            });
+            tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+
+            // Step 2: Storyboard
+            debug!("comic job -> storyboarding");
+            status_map.insert(jid.clone(), ComicJobStatus {
+                job_id: jid.clone(),
+                entry_id: eid.clone(),
+                style: st.clone(),
+                stage: ComicStage::Storyboarding,
+                updated_at: now_iso(),
+                result_image_path: None,
+                storyboard_text: None,
+                meta: None,
+                panel_image_paths: Vec::new(),
+            });
         
-        // Load entry body for prompting
-        let entry_body = get_entry_body(&db_pool, &eid).await;
-        if let Err(e) = entry_body {
-            error!(error = %e, "failed to load entry body");
+            // Load entry body for prompting
+            let entry_body = get_entry_body(&db_pool, &eid).await;
+            if let Err(e) = entry_body {
+                error!(error = %e, "failed to load entry body");
+                status_map.insert(jid.clone(), ComicJobStatus {
+                    job_id: jid.clone(),
+                    entry_id: eid.clone(),
+                    style: st.clone(),
+                    stage: ComicStage::failed(ToonanaError::new(ErrorKind::Db, format!("load entry failed: {}", e))),
+                    updated_at: now_iso(),
+                    result_image_path: None,
+                    storyboard_text: None,
+                    meta: None,
+                    panel_image_paths: Vec::new(),
+                });
+                return;
+            }
+            let entry_text = entry_body.unwrap_or_default();
+            let entry_text = match options.selection.map(|sel| sel.slice(&entry_text)) {
+                None => entry_text,
+                Some(Ok(selected)) => selected,
+                Some(Err(e)) => {
+                    error!(error = %e, "invalid selection");
+                    status_map.insert(jid.clone(), ComicJobStatus {
+                        job_id: jid.clone(),
+                        entry_id: eid.clone(),
+                        style: st.clone(),
+                        stage: ComicStage::failed(e),
+                        updated_at: now_iso(),
+                        result_image_path: None,
+                        storyboard_text: None,
+                        meta: None,
+                        panel_image_paths: Vec::new(),
+                    });
+                    return;
+                }
+            };
+
+            // Step 3: Prompting
+            debug!("comic job -> prompting");
+            let prompting_started = std::time::Instant::now();
             status_map.insert(jid.clone(), ComicJobStatus {
                 job_id: jid.clone(),
                 entry_id: eid.clone(),
                 style: st.clone(),
-                stage: ComicStage::Failed { error: format!("load entry failed: {}", e) },
+                stage: ComicStage::Prompting,
                 updated_at: now_iso(),
                 result_image_path: None,
                 storyboard_text: None,
+                meta: None,
+                panel_image_paths: Vec::new(),
             });
-            return;
-        }
-        let entry_text = entry_body.unwrap_or_default();
+        
+            let ollama_prompt = build_storyboard_prompt(&entry_text, options.reroll, options.panel_count());
+
+            let mut storyboard_text = String::new();
+        
+            let ollama_options = storyboard_options(&options, &settings);
+            let model_name = resolve_model(None, &settings);
+            // Partials normally live only in the status map. When enabled, a writer task keeps
+            // one storyboard row current, coalescing chunks that arrive while a write is running.
+            let storyboard_id = uuid::Uuid::new_v4().to_string();
+            let persist_partials = settings.persist_partial_storyboard.unwrap_or(false);
+            let partial_writer = persist_partials.then(|| {
+                let (tx, mut rx) = tokio::sync::watch::channel(String::new());
+                let (pool, sid, entry, model) = (db_pool.clone(), storyboard_id.clone(), eid.clone(), model_name.clone());
+                let task = tokio::spawn(async move {
+                    while rx.changed().await.is_ok() {
+                        let text = rx.borrow_and_update().clone();
+                        let data = serde_json::json!({ "text": text, "partial": true });
+                        if let Err(e) = upsert_storyboard(&pool, &sid, &entry, &data, &model).await {
+                            warn!(error = %e, "failed to persist partial storyboard");
+                        }
+                    }
+                });
+                (tx, task)
+            });
+            let stream_res = generate_streaming(None, ollama_prompt, &settings, ollama_options, Some(&storyboard_stop), |chunk| {
+                storyboard_text.push_str(chunk);
+                if let Some((tx, _)) = &partial_writer {
+                    let _ = tx.send(storyboard_text.clone());
+                }
+                // Update status with partial text; the job row only follows when partials are persisted
+                let status = ComicJobStatus {
+                    job_id: jid.clone(),
+                    entry_id: eid.clone(),
+                    style: st.clone(),
+                    stage: ComicStage::Prompting,
+                    updated_at: now_iso(),
+                    result_image_path: None,
+                    storyboard_text: Some(storyboard_text.clone()),
+                    meta: None,
+                    panel_image_paths: Vec::new(),
+                };
+                if persist_partials {
+                    status_map.insert(jid.clone(), status);
+                } else {
+                    status_map.insert_in_memory(jid.clone(), status);
+                }
+            }).await;
+        
+            // Let the writer flush its last partial so it can't land after the final text
+            let persisted_partial = match partial_writer {
+                Some((tx, task)) => {
+                    drop(tx);
+                    let _ = task.await;
+                    true
+                }
+                None => false,
+            };
+
+            let stream_summary = match stream_res {
+                Ok(summary) => summary,
+                Err(e) => {
+                    error!(error = %e, "ollama prompting failed");
+                    status_map.insert(jid.clone(), ComicJobStatus {
+                        job_id: jid.clone(),
+                        entry_id: eid.clone(),
+                        style: st.clone(),
+                        stage: ComicStage::failed(format!("ollama prompting failed: {}", e)),
+                        updated_at: now_iso(),
+                        result_image_path: None,
+                        storyboard_text: None,
+                        meta: None,
+                        panel_image_paths: Vec::new(),
+                    });
+                    return;
+                }
+            };
+            let storyboard_text = clean_storyboard_text(&storyboard_text, &settings);
+            let storyboard_text = if stream_summary.stopped {
+                storyboard_text
+            } else {
+                match fill_missing_panels(storyboard_text.clone(), &settings, options.panel_count()).await {
+                    Ok(text) => text,
+                    Err(e) => {
+                        error!(error = %e, "storyboard is missing panels");
+                        status_map.insert(jid.clone(), ComicJobStatus {
+                            job_id: jid.clone(),
+                            entry_id: eid.clone(),
+                            style: st.clone(),
+                            stage: ComicStage::failed(e),
+                            updated_at: now_iso(),
+                            result_image_path: None,
+                            storyboard_text: Some(storyboard_text),
+                            meta: None,
+                            panel_image_paths: Vec::new(),
+                        });
+                        return;
+                    }
+                }
+            };
+
+            // Keep every storyboard (including rerolls) so they can be compared later
+            let parsed_panels = parse_storyboard(&storyboard_text);
+            let storyboard_data = serde_json::json!({
+                "text": storyboard_text,
+                "panels": parsed_panels,
+                "reroll": options.reroll,
+                "continuations": stream_summary.continuations,
+                "truncated": stream_summary.truncated,
+                "stopped": stream_summary.stopped,
+            });
+            let saved = if persisted_partial {
+                upsert_storyboard(&db_pool, &storyboard_id, &eid, &storyboard_data, &model_name)
+                    .await
+                    .map(|_| storyboard_id.clone())
+            } else {
+                insert_storyboard(&db_pool, &eid, &storyboard_data, &model_name).await
+            };
+            let mut panel_ids: Vec<String> = Vec::new();
+            match saved {
+                Ok(sid) => match insert_panels(&db_pool, &sid, &eid, &jid, &st, &parsed_panels).await {
+                    Ok(ids) => panel_ids = ids,
+                    Err(e) => warn!(error = %e, "failed to persist storyboard panels"),
+                },
+                Err(e) => warn!(error = %e, "failed to persist storyboard"),
+            }
+            let prompting_ms = prompting_started.elapsed().as_millis() as i64;
+            if let Err(e) = record_job_metric(&db_pool, &jid, &eid, "prompting", Some(storyboard_provider(&settings)), Some(&model_name), &st, prompting_ms).await {
+                warn!(error = %e, "failed to record prompting duration");
+            }
+
+            // Stopped by the user: keep the partial storyboard for editing and skip rendering
+            if stream_summary.stopped {
+                info!(chars = storyboard_text.len(), "storyboard stopped early; job ends without rendering");
+                status_map.insert(jid.clone(), ComicJobStatus {
+                    job_id: jid.clone(),
+                    entry_id: eid.clone(),
+                    style: st.clone(),
+                    stage: ComicStage::StoryboardReady { partial: true },
+                    updated_at: now_iso(),
+                    result_image_path: None,
+                    storyboard_text: Some(storyboard_text.clone()),
+                    meta: None,
+                    panel_image_paths: Vec::new(),
+                });
+                return;
+            }
 
-        // Step 3: Prompting
-        debug!("comic job -> prompting");
+            (storyboard_text, stream_summary, parsed_panels, panel_ids)
+        };
+
+        // Step 4: Rendering. Per-panel jobs count finished panels; a single image reports percent.
+        debug!("comic job -> rendering");
+        // A storyboard that came back longer than requested is cut to the requested count
+        let to_render = &parsed_panels[..options.panel_count().map_or(parsed_panels.len(), |n| n.min(parsed_panels.len()))];
+        let total = match options.render_mode {
+            RenderMode::PerPanel => to_render.len() as u32,
+            RenderMode::Combined => 100,
+        };
         status_map.insert(jid.clone(), ComicJobStatus {
             job_id: jid.clone(),
             entry_id: eid.clone(),
             style: st.clone(),
-            stage: ComicStage::Prompting,
+            stage: ComicStage::Rendering { completed: 0, total },
             updated_at: now_iso(),
             result_image_path: None,
-            storyboard_text: None,
+            storyboard_text: Some(storyboard_text.clone()),
+            meta: None,
+            panel_image_paths: Vec::new(),
         });
-        
-        let ollama_prompt = format!(r#"You are a helpful assistant that writes a short 3‑panel comic storyboard from a journal entry.
-
-Guidelines:
-- Keep tone light, hopeful, and not too dark; find a positive spin.
-- Avoid heavy or sensitive content; keep it PG and uplifting.
-- Privacy: do not reveal personal or identifying information from the journal entry; do not quote it verbatim. Replace names, places, dates, or unique details with neutral terms (e.g., 'a friend', 'a cafe', 'today').
-- Only include characters or speakers that are clearly present in the journal entry.
-- Do NOT invent specific locations, props, or events beyond what the journal clearly implies. If details are unspecified, use a neutral everyday setting.
-- Maintain continuity across panels.
-
-Output strictly in this structure for exactly 3-4 panels (no extra commentary, no blank lines between panels):
-Panel 1
-Description: <one concise sentence describing what the viewer sees>
-Caption: <optional; short; ≤ 12 words>
-Character 1: <optional; dialogue or inner thought; ≤ 12 words>
-Character 2: <optional; dialogue; ≤ 12 words>
-Panel 2
-Description: <visual description>
-Caption: <optional>
-Character 1: <optional>
-Panel 3
-Description: <visual description>
-Caption: <optional>
-Character 1: <optional>
 
-Rules:
-- If a field is not needed for a panel, omit that line entirely (do not write "none").
-- Prefer everyday, grounded scenes that could plausibly match the journal entry.
-- Use generic references (e.g., "a friend") instead of names. Do not quote the journal directly.
+        // `cancel_job` records the cancelled status itself; the job just stops
+        if cancel.is_cancelled() {
+            info!("comic job cancelled before rendering");
+            return;
+        }
 
-Journal Entry:
-{}
-"#,
-            entry_text
-        );
+        // A preset deleted after the job was queued just renders with the plain style
+        let preset = match options.style_preset_id.as_deref() {
+            Some(id) => match get_style_preset(&db_pool, id).await {
+                Ok(p) => Some(p),
+                Err(e) => {
+                    warn!(error = %e, preset_id = id, "style preset unavailable; using plain style");
+                    None
+                }
+            },
+            None => None,
+        };
 
-        let mut storyboard_text = String::new();
-        let settings = load_settings_from_dir(&data_root);
-        
-        let stream_res = generate_streaming(None, ollama_prompt, &settings, |chunk| {
-            storyboard_text.push_str(chunk);
-            // Update status with partial text
+        let images_dir = data_root.join("images").join(&eid);
+        let _ = tokio::fs::create_dir_all(&images_dir).await;
+        let min_free_mb = settings.min_free_disk_mb.unwrap_or(DEFAULT_MIN_FREE_DISK_MB);
+        // Check before spending a render on an image we couldn't save
+        if let Err(e) = ensure_free_space(&data_root, 0, min_free_mb) {
+            error!(error = %e, "not enough disk space to render");
             status_map.insert(jid.clone(), ComicJobStatus {
                 job_id: jid.clone(),
                 entry_id: eid.clone(),
                 style: st.clone(),
-                stage: ComicStage::Prompting,
+                stage: ComicStage::failed(ToonanaError::new(ErrorKind::Io, e)),
                 updated_at: now_iso(),
                 result_image_path: None,
                 storyboard_text: Some(storyboard_text.clone()),
+                meta: None,
+                panel_image_paths: Vec::new(),
             });
-        }).await;
-        
-        if let Err(e) = stream_res {
-            error!(error = %e, "ollama prompting failed");
+            return;
+        }
+
+        if options.render_mode == RenderMode::PerPanel {
+            // Fail once up front rather than once per panel when the provider can't do it
+            let panel_provider = match prompt_image_provider(&settings) {
+                Ok(p) => p,
+                Err(e) => {
+                    error!(error = %e, "per-panel rendering unavailable");
+                    status_map.insert(jid.clone(), ComicJobStatus {
+                        job_id: jid.clone(),
+                        entry_id: eid.clone(),
+                        style: st.clone(),
+                        stage: ComicStage::failed(e),
+                        updated_at: now_iso(),
+                        result_image_path: None,
+                        storyboard_text: Some(storyboard_text.clone()),
+                        meta: None,
+                        panel_image_paths: Vec::new(),
+                    });
+                    return;
+                }
+            };
+            let render_started = std::time::Instant::now();
+            let results = render_panels(to_render, &style_with_preset(&st, preset.as_ref()), &settings, &images_dir, &jid, min_free_mb, &cancel, |completed| {
+                status_map.insert(jid.clone(), ComicJobStatus {
+                    job_id: jid.clone(),
+                    entry_id: eid.clone(),
+                    style: st.clone(),
+                    stage: ComicStage::Rendering { completed, total },
+                    updated_at: now_iso(),
+                    result_image_path: None,
+                    storyboard_text: Some(storyboard_text.clone()),
+                    meta: None,
+                    panel_image_paths: Vec::new(),
+                });
+            }).await;
+            if cancel.is_cancelled() {
+                info!("comic job cancelled during rendering");
+                return;
+            }
+
+            let mut panel_image_paths = Vec::new();
+            let mut panel_errors = Vec::new();
+            for (idx, result) in results.into_iter().enumerate() {
+                match result {
+                    Ok(path) => {
+                        record_image_asset(&db_pool, "panel", &path, &jid, &eid, None).await;
+                        let path = path.display().to_string();
+                        if let Some(panel_id) = panel_ids.get(idx) {
+                            if let Err(e) = set_panel_image(&db_pool, panel_id, &path).await {
+                                warn!(error = %e, idx, "failed to store panel image path");
+                            }
+                        }
+                        panel_image_paths.push(path);
+                    }
+                    Err(e) => panel_errors.push(serde_json::json!({ "idx": idx, "error": e })),
+                }
+            }
+            let mut meta = serde_json::json!({
+                "render_mode": "per_panel",
+                "panel_count": total,
+                "panel_render_concurrency": panel_render_concurrency(&settings),
+            });
+            if let Some(r) = &options.retry_of {
+                meta["retry_of"] = serde_json::json!(r);
+            }
+            if !panel_errors.is_empty() {
+                meta["panel_errors"] = serde_json::json!(panel_errors);
+            }
+            if panel_image_paths.is_empty() {
+                error!(panels = total, "every panel failed to render");
+                status_map.insert(jid.clone(), ComicJobStatus {
+                    job_id: jid.clone(),
+                    entry_id: eid.clone(),
+                    style: st.clone(),
+                    stage: ComicStage::failed("image generation failed for every panel"),
+                    updated_at: now_iso(),
+                    result_image_path: None,
+                    storyboard_text: Some(storyboard_text.clone()),
+                    meta: Some(meta),
+                    panel_image_paths: Vec::new(),
+                });
+                return;
+            }
+            let render_ms = render_started.elapsed().as_millis() as i64;
+            info!(rendered = panel_image_paths.len(), failed = panel_errors.len(), duration_ms = render_ms, "panel rendering finished");
+            if let Err(e) = record_job_metric(&db_pool, &jid, &eid, "rendering", Some(panel_provider.as_str()), None, &st, render_ms).await {
+                warn!(error = %e, "failed to record rendering duration");
+            }
+            // The first panel stands in for the comic wherever a single image is expected
+            let first = panel_image_paths[0].clone();
             status_map.insert(jid.clone(), ComicJobStatus {
                 job_id: jid.clone(),
                 entry_id: eid.clone(),
                 style: st.clone(),
-                stage: ComicStage::Failed { error: format!("ollama prompting failed: {}", e) },
+                stage: ComicStage::Done,
                 updated_at: now_iso(),
-                result_image_path: None,
-                storyboard_text: None,
+                result_image_path: Some(first.clone()),
+                storyboard_text: Some(storyboard_text.clone()),
+                meta: Some(meta),
+                panel_image_paths,
             });
+            let first_bytes = tokio::fs::read(&first).await.ok();
+            let dims = first_bytes.as_deref().and_then(image_dimensions);
+            let thumbnail_path = match first_bytes {
+                Some(b) => save_result_thumbnail(&db_pool, Path::new(&first), b, &jid, &eid).await,
+                None => None,
+            };
+            let done = ComicDoneEvent {
+                job_id: jid.clone(),
+                entry_id: eid.clone(),
+                image_path: first,
+                thumbnail_path,
+                width: dims.map(|d| d.0),
+                height: dims.map(|d| d.1),
+            };
+            if let Err(e) = app.emit("comic://done", done) {
+                warn!(error = %e, "failed to emit comic://done");
+            }
             return;
         }
 
-        // Step 4: Rendering
-        debug!("comic job -> rendering");
-        status_map.insert(jid.clone(), ComicJobStatus {
-            job_id: jid.clone(),
-            entry_id: eid.clone(),
-            style: st.clone(),
-            stage: ComicStage::Rendering { completed: 1, total: 1 },
-            updated_at: now_iso(),
-            result_image_path: None,
-            storyboard_text: Some(storyboard_text.clone()),
-        });
-
-        let images_dir = data_root.join("images").join(&eid);
-        let _ = tokio::fs::create_dir_all(&images_dir).await;
-
-        let nb_res = if settings.nano_banana_base_url.is_some() {
+        let render_started = std::time::Instant::now();
+        let mut render_provider = settings.image_provider().as_str();
+        // Kept apart from the Gemini outcome so both can be reported separately
+        let mut nano_banana_error: Option<NanoBananaError> = None;
+        let nb_res = if settings.image_provider() == ImageProvider::NanoBanana {
             // While waiting for Nano-Banana, periodically bump progress so the UI stays alive
             let mut tick_completed: u32 = 0;
             info!("sending storyboard to nano-banana");
@@ -270,23 +1695,34 @@ Journal Entry:
                                 updated_at: now_iso(),
                                 result_image_path: None,
                                 storyboard_text: Some(storyboard_text.clone()),
+                                meta: None,
+                                panel_image_paths: Vec::new(),
                             });
                         }
                     }
                 }
             };
 
-            // Fallback to direct Gemini if Nano-Banana failed
+            // Fall back to direct Gemini only when allowed; otherwise the job fails with nano-banana's error
             match res {
                 Ok(s) => {
                     info!("nano-banana image received");
+                    render_provider = "nano_banana";
                     Ok(s)
                 },
+                Err(e) if !settings.fallback_to_gemini() => {
+                    warn!(error = %e, status = ?e.status, body = ?e.body, "nano-banana failed; fallback to gemini is off");
+                    let message = format!("{} (fallback to Gemini is off)", e);
+                    nano_banana_error = Some(e);
+                    Err(message)
+                }
                 Err(e) => {
-                    warn!(error = %e, "nano-banana failed, falling back to gemini");
-                    let prompt = build_gemini_image_prompt(&storyboard_text, &st);
+                    warn!(error = %e, status = ?e.status, body = ?e.body, "nano-banana failed, falling back to gemini");
+                    render_provider = "gemini";
+                    nano_banana_error = Some(e);
+                    let prompt = build_gemini_image_prompt(&storyboard_text, &st, preset.as_ref(), options.panel_count());
                     let mut last_tick = tick_completed;
-                    generate_image_with_progress(&prompt, &settings, |completed, total| {
+                    generate_image_with_progress(&prompt, &settings, &cancel, |completed, total| {
                         if completed > last_tick && completed % 5 == 0 {
                             last_tick = completed;
                             debug!(progress = completed, total = total, "gemini rendering progress");
@@ -298,18 +1734,32 @@ Journal Entry:
                                 updated_at: now_iso(),
                                 result_image_path: None,
                                 storyboard_text: Some(storyboard_text.clone()),
+                                meta: None,
+                                panel_image_paths: Vec::new(),
                             });
                         }
-                    }).await.map_err(|ge| format!("nano-banana failed: {e}; gemini fallback failed: {ge}"))
+                    }, |retry_in_secs, attempt| {
+                        status_map.insert(jid.clone(), ComicJobStatus {
+                            job_id: jid.clone(),
+                            entry_id: eid.clone(),
+                            style: st.clone(),
+                            stage: ComicStage::RateLimited { retry_in_secs, attempt },
+                            updated_at: now_iso(),
+                            result_image_path: None,
+                            storyboard_text: Some(storyboard_text.clone()),
+                            meta: None,
+                            panel_image_paths: Vec::new(),
+                        });
+                    }).await
                 }
             }
         } else {
-            let prompt = build_gemini_image_prompt(&storyboard_text, &st);
+            let prompt = build_gemini_image_prompt(&storyboard_text, &st, preset.as_ref(), options.panel_count());
             let mut last_tick = 0u32;
-            generate_image_with_progress(&prompt, &settings, |completed, total| {
+            let rendered = render_prompt_image(&prompt, &settings, &cancel, |completed, total| {
                 if completed > last_tick && completed % 5 == 0 {
                     last_tick = completed;
-                    debug!(progress = completed, total = total, "gemini rendering progress");
+                    debug!(progress = completed, total = total, provider = render_provider, "rendering progress");
                     status_map.insert(jid.clone(), ComicJobStatus {
                         job_id: jid.clone(),
                         entry_id: eid.clone(),
@@ -318,19 +1768,163 @@ Journal Entry:
                         updated_at: now_iso(),
                         result_image_path: None,
                         storyboard_text: Some(storyboard_text.clone()),
+                        meta: None,
+                        panel_image_paths: Vec::new(),
                     });
                 }
-            }).await
+            }, |retry_in_secs, attempt| {
+                status_map.insert(jid.clone(), ComicJobStatus {
+                    job_id: jid.clone(),
+                    entry_id: eid.clone(),
+                    style: st.clone(),
+                    stage: ComicStage::RateLimited { retry_in_secs, attempt },
+                    updated_at: now_iso(),
+                    result_image_path: None,
+                    storyboard_text: Some(storyboard_text.clone()),
+                    meta: None,
+                    panel_image_paths: Vec::new(),
+                });
+            }).await;
+            rendered.map(|(b64, provider)| {
+                render_provider = provider.as_str();
+                b64
+            })
         };
+
+        let mut meta = serde_json::Map::new();
+        if nb_res.is_ok() {
+            // Which backend produced the image, and the configured one when a fallback stepped in
+            meta.insert("image_provider".into(), serde_json::json!(render_provider));
+            let configured = settings.image_provider().as_str();
+            if render_provider != configured {
+                meta.insert("fallback_from".into(), serde_json::json!(configured));
+            }
+        }
+        // Real panel count and who appears in each panel; per-panel rendering uses both
+        let panels = split_storyboard_panels(&storyboard_text);
+        let characters: Vec<Vec<u32>> = panels.iter().map(|p| panel_characters(p)).collect();
+        meta.insert("panel_count".into(), serde_json::json!(panels.len()));
+        meta.insert("panel_characters".into(), serde_json::json!(characters));
+        if let Some(sel) = &options.selection {
+            meta.insert("selection".into(), serde_json::json!(sel));
+        }
+        if let Some(r) = &options.retry_of {
+            meta.insert("retry_of".into(), serde_json::json!(r));
+        }
+        if let Some(p) = settings.style_reference_image_path.as_ref() {
+            let applied = render_provider == "gemini";
+            meta.insert("style_reference_image_path".into(), serde_json::json!(p));
+            meta.insert("style_reference_applied".into(), serde_json::json!(applied));
+            if !applied {
+                meta.insert("note".into(), serde_json::json!(format!("{} does not support style reference images; skipped", render_provider)));
+            }
+        }
+        if let Some(e) = &nano_banana_error {
+            meta.insert("nano_banana_error".into(), serde_json::json!(e));
+        }
+        if stream_summary.continuations > 0 || stream_summary.truncated {
+            meta.insert("storyboard_continuations".into(), serde_json::json!(stream_summary.continuations));
+            meta.insert("storyboard_truncated".into(), serde_json::json!(stream_summary.truncated));
+        }
+        let render_meta = (!meta.is_empty()).then_some(serde_json::Value::Object(meta));
+
+        if cancel.is_cancelled() {
+            info!("comic job cancelled during rendering");
+            return;
+        }
+        if nb_res.is_ok() {
+            let render_ms = render_started.elapsed().as_millis() as i64;
+            info!(provider = render_provider, duration_ms = render_ms, "rendering finished");
+            if let Err(e) = record_job_metric(&db_pool, &jid, &eid, "rendering", Some(render_provider), None, &st, render_ms).await {
+                warn!(error = %e, "failed to record rendering duration");
+            }
+        }
         
         match nb_res {
             Ok(b64_img) => {
                 match decode_base64_png(&b64_img) {
                     Ok(bytes) => {
-                        let ext = guess_image_extension(&bytes);
-                        let img_path = images_dir.join(format!("{}-result.{}", &jid, ext));
-                        let _ = tokio::fs::write(&img_path, bytes).await;
+                        // Gemini picks the response image format itself, so normalize after decoding
+                        let source_ext = guess_image_extension(&bytes);
+                        let mut render_meta = render_meta.clone();
+                        let target = settings.output_image_format.as_deref().and_then(parse_output_format);
+                        let (bytes, ext) = match target {
+                            Some(target) if target != source_ext => {
+                                let converted = tokio::task::spawn_blocking(move || {
+                                    let res = convert_image_format(&bytes, target);
+                                    (bytes, res)
+                                })
+                                .await;
+                                match converted {
+                                    Ok((_, Ok(out))) => {
+                                        info!(from = source_ext, to = target, "converted result image");
+                                        (out, target)
+                                    }
+                                    Ok((original, Err(e))) => {
+                                        warn!(error = %e, "image format conversion failed; keeping original");
+                                        (original, source_ext)
+                                    }
+                                    Err(e) => {
+                                        error!(error = %e, "image conversion task failed");
+                                        status_map.insert(jid.clone(), ComicJobStatus {
+                                            job_id: jid.clone(),
+                                            entry_id: eid.clone(),
+                                            style: st.clone(),
+                                            stage: ComicStage::failed(ToonanaError::new(ErrorKind::Other, format!("image conversion failed: {}", e))),
+                                            updated_at: now_iso(),
+                                            result_image_path: None,
+                                            storyboard_text: Some(storyboard_text.clone()),
+                                            meta: render_meta.clone(),
+                                            panel_image_paths: Vec::new(),
+                                        });
+                                        return;
+                                    }
+                                }
+                            }
+                            _ => (bytes, source_ext),
+                        };
+                        let m = render_meta.get_or_insert_with(|| serde_json::json!({}));
+                        m["output_format"] = serde_json::json!(ext);
+                        if ext != source_ext {
+                            m["converted_from"] = serde_json::json!(source_ext);
+                        }
+                        if let Err(e) = ensure_free_space(&images_dir, bytes.len() as u64, min_free_mb) {
+                            error!(error = %e, "not enough disk space to save image");
+                            status_map.insert(jid.clone(), ComicJobStatus {
+                                job_id: jid.clone(),
+                                entry_id: eid.clone(),
+                                style: st.clone(),
+                                stage: ComicStage::failed(ToonanaError::new(ErrorKind::Io, e)),
+                                updated_at: now_iso(),
+                                result_image_path: None,
+                                storyboard_text: Some(storyboard_text.clone()),
+                                meta: render_meta.clone(),
+                                panel_image_paths: Vec::new(),
+                            });
+                            return;
+                        }
+                        let dims = image_dimensions(&bytes);
+                        let img_path = match write_image_file(&images_dir, &format!("{}-result", &jid), ext, &bytes, &cancel).await {
+                            Ok(p) => p,
+                            Err(e) => {
+                                error!(error = %e, "failed to write result image");
+                                status_map.insert(jid.clone(), ComicJobStatus {
+                                    job_id: jid.clone(),
+                                    entry_id: eid.clone(),
+                                    style: st.clone(),
+                                    stage: ComicStage::failed(ToonanaError::new(ErrorKind::Io, format!("write image failed: {}", e))),
+                                    updated_at: now_iso(),
+                                    result_image_path: None,
+                                    storyboard_text: Some(storyboard_text.clone()),
+                                    meta: render_meta.clone(),
+                                    panel_image_paths: Vec::new(),
+                                });
+                                return;
+                            }
+                        };
                         info!(path = %img_path.display(), "saved generated image");
+                        record_image_asset(&db_pool, "comic", &img_path, &jid, &eid, None).await;
+                        let thumbnail_path = save_result_thumbnail(&db_pool, &img_path, bytes, &jid, &eid).await;
                         
                         status_map.insert(jid.clone(), ComicJobStatus {
                             job_id: jid.clone(),
@@ -340,6 +1934,8 @@ Journal Entry:
                             updated_at: now_iso(),
                             result_image_path: Some(img_path.display().to_string()),
                             storyboard_text: Some(storyboard_text.clone()),
+                            meta: render_meta.clone(),
+                            panel_image_paths: Vec::new(),
                         });
                         
                         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -352,7 +1948,21 @@ Journal Entry:
                             updated_at: now_iso(),
                             result_image_path: Some(img_path.display().to_string()),
                             storyboard_text: Some(storyboard_text.clone()),
+                            meta: render_meta.clone(),
+                            panel_image_paths: Vec::new(),
                         });
+
+                        let done = ComicDoneEvent {
+                            job_id: jid.clone(),
+                            entry_id: eid.clone(),
+                            image_path: img_path.display().to_string(),
+                            thumbnail_path,
+                            width: dims.map(|d| d.0),
+                            height: dims.map(|d| d.1),
+                        };
+                        if let Err(e) = app.emit("comic://done", done) {
+                            warn!(error = %e, "failed to emit comic://done");
+                        }
                     }
                     Err(e) => {
                         error!(error = %e, "image decode failed");
@@ -360,30 +1970,237 @@ Journal Entry:
                             job_id: jid.clone(),
                             entry_id: eid.clone(),
                             style: st.clone(),
-                            stage: ComicStage::Failed { error: format!("image decode failed: {}", e) },
+                            stage: ComicStage::failed(ToonanaError::new(ErrorKind::Decode, format!("image decode failed: {}", e))),
                             updated_at: now_iso(),
                             result_image_path: None,
                             storyboard_text: Some(storyboard_text.clone()),
+                            meta: render_meta.clone(),
+                            panel_image_paths: Vec::new(),
                         });
                     }
                 }
             }
             Err(e) => {
                 error!(error = %e, "image generation failed");
+                // With a nano-banana failure on record and Gemini tried, `e` is the fallback's error
+                let fell_back = nano_banana_error.is_some() && render_provider == "gemini";
+                let error = if fell_back {
+                    format!("image generation failed: nano-banana and gemini fallback both failed; gemini: {}", e)
+                } else {
+                    format!("image generation failed: {}", e)
+                };
+                let mut render_meta = render_meta.clone();
+                if fell_back {
+                    let m = render_meta.get_or_insert_with(|| serde_json::json!({}));
+                    m["gemini_error"] = serde_json::json!(e);
+                }
                 status_map.insert(jid.clone(), ComicJobStatus {
                     job_id: jid.clone(),
                     entry_id: eid.clone(),
                     style: st.clone(),
-                    stage: ComicStage::Failed { error: format!("image generation failed: {}", e) },
+                    stage: ComicStage::failed(error),
                     updated_at: now_iso(),
                     result_image_path: None,
                     storyboard_text: Some(storyboard_text.clone()),
+                    meta: render_meta.clone(),
+                    panel_image_paths: Vec::new(),
                 });
             }
         }
     })
 }
 
+/// Payload of the `comic://storyboard-draft` events emitted while a draft storyboard
+/// streams: a stage change, or a chunk of generated text (stage `prompting`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoryboardDraftProgress {
+    pub draft_id: String,
+    pub entry_id: String,
+    #[serde(flatten)]
+    pub stage: ComicStage,
+    pub chunk: Option<String>,
+}
+
+/// A storyboard generated without rendering, parsed into panels.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoryboardDraft {
+    pub draft_id: String,
+    pub entry_id: String,
+    pub style: String,
+    pub storyboard_text: String,
+    pub panels: Vec<ParsedPanel>,
+    pub truncated: bool,
+}
+
+/// Run only the parsing and prompting steps of a comic job: same prompt, model options
+/// and clean-up, but nothing is rendered or saved. Tokens go out as
+/// `comic://storyboard-draft` events tagged with `draft_id`.
+pub async fn generate_storyboard_draft(
+    app: &AppHandle,
+    db_pool: &Pool<Sqlite>,
+    draft_id: String,
+    entry_id: String,
+    style: String,
+    settings: &Settings,
+) -> Result<StoryboardDraft, String> {
+    let emit = |stage: ComicStage, chunk: Option<String>| {
+        let event = StoryboardDraftProgress { draft_id: draft_id.clone(), entry_id: entry_id.clone(), stage, chunk };
+        if let Err(e) = app.emit("comic://storyboard-draft", event) {
+            warn!(error = %e, "failed to emit comic://storyboard-draft");
+        }
+    };
+    let fail = |error: String| {
+        emit(ComicStage::failed(error.clone()), None);
+        error
+    };
+
+    emit(ComicStage::Parsing, None);
+    let entry_text = get_entry_body(db_pool, &entry_id)
+        .await
+        .map_err(|e| fail(format!("load entry failed: {}", e)))?;
+
+    emit(ComicStage::Prompting, None);
+    let options = ComicJobOptions::default();
+    let prompt = build_storyboard_prompt(&entry_text, options.reroll, None);
+    let mut storyboard_text = String::new();
+    let ollama_options = storyboard_options(&options, settings);
+    let summary = generate_streaming(None, prompt, settings, ollama_options, None, |chunk| {
+        storyboard_text.push_str(chunk);
+        emit(ComicStage::Prompting, Some(chunk.to_string()));
+    })
+    .await
+    .map_err(|e| fail(format!("ollama prompting failed: {}", e)))?;
+    let storyboard_text = clean_storyboard_text(&storyboard_text, settings);
+    let storyboard_text = fill_missing_panels(storyboard_text, settings, None).await.map_err(fail)?;
+    let panels = parse_storyboard(&storyboard_text);
+
+    info!(chars = storyboard_text.len(), panels = panels.len(), "storyboard draft ready");
+    emit(ComicStage::StoryboardReady { partial: false }, None);
+    Ok(StoryboardDraft {
+        draft_id: draft_id.clone(),
+        entry_id: entry_id.clone(),
+        style,
+        storyboard_text,
+        panels,
+        truncated: summary.truncated,
+    })
+}
+
+/// Payload of the `comic://preview-progress` event emitted while a preview renders.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComicPreviewProgress {
+    pub preview_id: String,
+    pub entry_id: String,
+    #[serde(flatten)]
+    pub stage: ComicStage,
+}
+
+/// Result of a preview run; the image is only returned, never written to disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComicPreview {
+    pub preview_id: String,
+    pub entry_id: String,
+    pub style: String,
+    pub image_base64: String,
+    pub mime: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub storyboard_text: String,
+    pub provider: String,
+}
+
+/// Run the storyboard + render pipeline for an entry without persisting anything:
+/// no image files, no storyboard rows, no metrics. Progress goes out as
+/// `comic://preview-progress` events tagged with `preview_id`; `cancel` stops the render.
+pub async fn preview_comic(
+    app: &AppHandle,
+    db_pool: &Pool<Sqlite>,
+    preview_id: String,
+    entry_id: String,
+    style: String,
+    settings: &Settings,
+    cancel: &CancellationToken,
+) -> Result<ComicPreview, String> {
+    let emit = |stage: ComicStage| {
+        let event = ComicPreviewProgress { preview_id: preview_id.clone(), entry_id: entry_id.clone(), stage };
+        if let Err(e) = app.emit("comic://preview-progress", event) {
+            warn!(error = %e, "failed to emit comic://preview-progress");
+        }
+    };
+    let fail = |error: String| {
+        emit(ComicStage::failed(error.clone()));
+        error
+    };
+
+    emit(ComicStage::Parsing);
+    let entry_text = get_entry_body(db_pool, &entry_id)
+        .await
+        .map_err(|e| fail(format!("load entry failed: {}", e)))?;
+
+    emit(ComicStage::Prompting);
+    let prompt = build_storyboard_prompt(&entry_text, false, None);
+    let mut storyboard_text = String::new();
+    let ollama_options = storyboard_options(&ComicJobOptions::default(), settings);
+    generate_streaming(None, prompt, settings, ollama_options, None, |chunk| {
+        storyboard_text.push_str(chunk);
+    })
+    .await
+    .map_err(|e| fail(format!("ollama prompting failed: {}", e)))?;
+    let storyboard_text = clean_storyboard_text(&storyboard_text, settings);
+    let storyboard_text = fill_missing_panels(storyboard_text, settings, None).await.map_err(fail)?;
+
+    emit(ComicStage::Rendering { completed: 0, total: 100 });
+    let mut provider = settings.image_provider().as_str();
+    let nano_banana = if settings.image_provider() == ImageProvider::NanoBanana {
+        match nano_banana_generate_image(&storyboard_text, settings).await {
+            Ok(s) => Some(s),
+            Err(e) if !settings.fallback_to_gemini() => {
+                return Err(fail(format!("rendering failed: {} (fallback to Gemini is off)", e)));
+            }
+            Err(e) => {
+                warn!(error = %e, "preview: nano-banana failed, falling back to gemini");
+                provider = "gemini";
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let b64_img = match nano_banana {
+        Some(s) => s,
+        None => {
+            let prompt = build_gemini_image_prompt(&storyboard_text, &style, None, None);
+            let mut last_tick = 0u32;
+            let (b64, used) = render_prompt_image(&prompt, settings, cancel, |completed, total| {
+                if completed > last_tick && completed % 5 == 0 {
+                    last_tick = completed;
+                    emit(ComicStage::Rendering { completed, total });
+                }
+            }, |retry_in_secs, attempt| emit(ComicStage::RateLimited { retry_in_secs, attempt }))
+            .await
+            .map_err(|e| fail(format!("rendering failed: {}", e)))?;
+            provider = used.as_str();
+            b64
+        }
+    };
+
+    let bytes = decode_base64_png(&b64_img).map_err(|e| fail(format!("decode image failed: {}", e)))?;
+    let dims = image_dimensions(&bytes);
+    emit(ComicStage::Done);
+    info!(provider, "comic preview rendered");
+    Ok(ComicPreview {
+        preview_id: preview_id.clone(),
+        entry_id: entry_id.clone(),
+        style,
+        mime: guess_image_mime(&bytes).to_string(),
+        image_base64: B64.encode(&bytes),
+        width: dims.map(|d| d.0),
+        height: dims.map(|d| d.1),
+        storyboard_text,
+        provider: provider.to_string(),
+    })
+}
+
 pub async fn save_image_to_disk(
     data_dir: PathBuf,
     base64_png: String,
@@ -395,9 +2212,146 @@ pub async fn save_image_to_disk(
     tokio::fs::create_dir_all(&img_dir)
         .await
         .map_err(|e| e.to_string())?;
-    let file_path = img_dir.join(format!("{panel_id}.png"));
-    tokio::fs::write(&file_path, bytes)
+    let file_path = write_unique_file(&img_dir, &panel_id, "png", &bytes)
         .await
         .map_err(|e| e.to_string())?;
     Ok(file_path.display().to_string())
-}
\ No newline at end of file
+}
+
+/// `write_unique_file` run as its own task, so aborting the job mid-write can't leave a
+/// truncated image behind. Nothing is written once `cancel` has fired.
+async fn write_image_file(dir: &Path, stem: &str, ext: &str, bytes: &[u8], cancel: &CancellationToken) -> std::io::Result<PathBuf> {
+    if cancel.is_cancelled() {
+        return Err(std::io::Error::new(std::io::ErrorKind::Interrupted, Cancelled));
+    }
+    let (dir, stem, ext, bytes) = (dir.to_path_buf(), stem.to_string(), ext.to_string(), bytes.to_vec());
+    tokio::spawn(async move { write_unique_file(&dir, &stem, &ext, &bytes).await })
+        .await
+        .map_err(std::io::Error::other)?
+}
+
+/// Write `bytes` to `dir/{stem}-{unix_ms}.{ext}`, adding `-1`, `-2`, ... if that name is taken.
+/// Files are opened with `create_new`, so two renders of the same entry never overwrite each other.
+pub async fn write_unique_file(dir: &Path, stem: &str, ext: &str, bytes: &[u8]) -> std::io::Result<PathBuf> {
+    use tokio::io::AsyncWriteExt;
+    let ts = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let mut index = 0u32;
+    loop {
+        let name = if index == 0 {
+            format!("{stem}-{ts}.{ext}")
+        } else {
+            format!("{stem}-{ts}-{index}.{ext}")
+        };
+        let path = dir.join(name);
+        match tokio::fs::OpenOptions::new().write(true).create_new(true).open(&path).await {
+            Ok(mut file) => {
+                let written = async {
+                    file.write_all(bytes).await?;
+                    file.flush().await
+                }
+                .await;
+                if let Err(e) = written {
+                    // Don't leave a truncated image behind
+                    drop(file);
+                    let _ = tokio::fs::remove_file(&path).await;
+                    return Err(e);
+                }
+                return Ok(path);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => index += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panel_concurrency_defaults_to_two_and_is_at_least_one() {
+        let mut settings = Settings::default();
+        assert_eq!(panel_render_concurrency(&settings), 2);
+        settings.panel_render_concurrency = Some(4);
+        assert_eq!(panel_render_concurrency(&settings), 4);
+        settings.panel_render_concurrency = Some(0);
+        assert_eq!(panel_render_concurrency(&settings), 1);
+    }
+
+    #[test]
+    fn nano_banana_needs_fallback_for_single_prompts() {
+        let mut settings = Settings { image_provider: Some("nano_banana".into()), ..Default::default() };
+        let err = prompt_image_provider(&settings).unwrap_err();
+        assert_eq!(ToonanaError::from(err).kind(), ErrorKind::UnsupportedProvider);
+
+        settings.fallback_to_gemini = Some(true);
+        assert_eq!(prompt_image_provider(&settings), Ok(ImageProvider::Gemini));
+        settings.image_provider = Some("a1111".into());
+        assert_eq!(prompt_image_provider(&settings), Ok(ImageProvider::A1111));
+    }
+
+    #[test]
+    fn strips_fenced_storyboard_with_language_tag() {
+        let raw = "```text\nPanel 1\nDescription: A cup of coffee on a desk.\nCaption: Monday again.\nPanel 2\nDescription: A laptop opens.\n```";
+        let cleaned = strip_storyboard_wrappers(raw);
+        assert!(cleaned.starts_with("Panel 1"));
+        assert!(!cleaned.contains("```"));
+        let panels = parse_storyboard(&cleaned);
+        assert_eq!(panels.len(), 2);
+        assert_eq!(panels[0].caption.as_deref(), Some("Monday again."));
+    }
+
+    #[test]
+    fn strips_preamble_and_trailing_chatter_around_a_fence() {
+        let raw = "Sure! Here is your storyboard:\n\n```markdown\n**Panel 1:**\nDescription: Rain on a window.\n**Panel 2:**\nDescription: An umbrella by the door.\nCharacter 1: Not today.\n```\n\nLet me know if you'd like changes!";
+        let cleaned = strip_storyboard_wrappers(raw);
+        assert!(cleaned.starts_with("**Panel 1:**"));
+        assert!(!cleaned.contains("Let me know"));
+        let panels = parse_storyboard(&cleaned);
+        assert_eq!(panels.len(), 2);
+        assert_eq!(panels[0].description, "Rain on a window.");
+        assert_eq!(panels[1].lines[0].text, "Not today.");
+    }
+
+    #[test]
+    fn strips_prefix_line_without_fences() {
+        let raw = "Here is your storyboard:\nPanel 1\nDescription: A park bench.\nPanel 2\nDescription: Pigeons gather.";
+        let cleaned = strip_storyboard_wrappers(raw);
+        assert_eq!(cleaned.lines().next(), Some("Panel 1"));
+        assert_eq!(parse_storyboard(&cleaned).len(), 2);
+    }
+
+    #[test]
+    fn keeps_panels_of_truncated_output_with_an_unclosed_fence() {
+        let raw = "```\nPanel 1\nDescription: A train platform.\nPanel 2\nDescription: The train pulls";
+        let cleaned = strip_storyboard_wrappers(raw);
+        assert!(!cleaned.contains("```"));
+        let panels = parse_storyboard(&cleaned);
+        assert_eq!(panels.len(), 2);
+        assert_eq!(panels[1].description, "The train pulls");
+    }
+
+    #[tokio::test]
+    async fn concurrent_renders_of_one_entry_get_separate_files() {
+        let dir = std::env::temp_dir().join(format!("toonana-unique-{}", uuid::Uuid::new_v4().simple()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        // Same stem, started together, so both usually land on the same millisecond
+        let (a, b) = tokio::join!(
+            write_unique_file(&dir, "entry-result", "png", b"first render"),
+            write_unique_file(&dir, "entry-result", "png", b"second render"),
+        );
+        let (a, b) = (a.unwrap(), b.unwrap());
+        assert_ne!(a, b);
+        assert_eq!(tokio::fs::read(&a).await.unwrap(), b"first render");
+        assert_eq!(tokio::fs::read(&b).await.unwrap(), b"second render");
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+
+    #[test]
+    fn panel_ten_is_not_mistaken_for_the_first_panel() {
+        let raw = "Panel 10 is where it ends.\nPanel 1\nDescription: Start.";
+        assert_eq!(strip_storyboard_wrappers(raw), "Panel 1\nDescription: Start.");
+    }
+}
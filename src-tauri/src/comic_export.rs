@@ -0,0 +1,117 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use std::path::Path;
+use tauri::{AppHandle, Emitter};
+use tracing::{info, warn};
+
+use crate::comic::{convert_image_format, guess_image_extension, newest_entry_image, parse_output_format};
+use crate::database::entry_ids_created_between;
+
+/// Payload of the `export://progress` event, sent after each entry is handled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportProgress {
+    pub done: usize,
+    pub total: usize,
+    pub current_entry: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportFailure {
+    pub entry_id: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportAllResult {
+    pub path: String,
+    pub exported: usize,
+    /// Already present in the target directory from an earlier run
+    pub skipped: usize,
+    pub failed: Vec<ExportFailure>,
+}
+
+/// Copy the newest comic of every entry into `dir` as `{date}-{entry_id}.{ext}`, converted
+/// to `format` ("png", "jpg", "webp", or "original" to keep each file as is).
+/// Files that already exist are skipped, so re-running after an interruption resumes
+/// where it left off. A failing entry is recorded and the export moves on.
+pub async fn export_all_comics(
+    app: &AppHandle,
+    pool: &Pool<Sqlite>,
+    data_dir: &Path,
+    dir: &Path,
+    format: &str,
+) -> Result<ExportAllResult> {
+    let target = match format.trim().to_ascii_lowercase().as_str() {
+        "original" | "" => None,
+        other => Some(parse_output_format(other).ok_or_else(|| anyhow!("unsupported export format '{}'", format))?),
+    };
+    tokio::fs::create_dir_all(dir).await.context("create export dir")?;
+
+    // "" .. "~" brackets every ISO timestamp, i.e. all entries oldest first
+    let entries = entry_ids_created_between(pool, "", "~")
+        .await
+        .map_err(|e| anyhow!(e))?;
+    let items: Vec<(String, String, std::path::PathBuf)> = entries
+        .into_iter()
+        .filter_map(|(id, created_at)| {
+            let image = newest_entry_image(&data_dir.join("images").join(&id))?;
+            let date = created_at.split('T').next().unwrap_or_default().to_string();
+            Some((id, date, image))
+        })
+        .collect();
+
+    let total = items.len();
+    let mut result = ExportAllResult {
+        path: dir.display().to_string(),
+        exported: 0,
+        skipped: 0,
+        failed: Vec::new(),
+    };
+
+    for (done, (entry_id, date, image)) in items.into_iter().enumerate() {
+        match export_one(&image, dir, &format!("{}-{}", date, entry_id), target).await {
+            Ok(true) => result.exported += 1,
+            Ok(false) => result.skipped += 1,
+            Err(e) => {
+                warn!(entry_id = %entry_id, error = %e, "export: entry failed");
+                result.failed.push(ExportFailure { entry_id: entry_id.clone(), error: e.to_string() });
+            }
+        }
+        let progress = ExportProgress { done: done + 1, total, current_entry: entry_id };
+        if let Err(e) = app.emit("export://progress", progress) {
+            warn!(error = %e, "failed to emit export://progress");
+        }
+    }
+
+    info!(
+        exported = result.exported,
+        skipped = result.skipped,
+        failed = result.failed.len(),
+        "comic export finished"
+    );
+    Ok(result)
+}
+
+// Returns false when the output already exists
+async fn export_one(image: &Path, dir: &Path, stem: &str, target: Option<&'static str>) -> Result<bool> {
+    let bytes = tokio::fs::read(image).await.context("read comic image")?;
+    let source_ext = guess_image_extension(&bytes);
+    let ext = target.unwrap_or(source_ext);
+    let out_path = dir.join(format!("{}.{}", stem, ext));
+    if tokio::fs::try_exists(&out_path).await.unwrap_or(false) {
+        return Ok(false);
+    }
+    let bytes = if ext != source_ext {
+        tokio::task::spawn_blocking(move || convert_image_format(&bytes, ext))
+            .await
+            .context("conversion task failed")??
+    } else {
+        bytes
+    };
+    // Write to a temp name first so an interrupted export never leaves a half file behind
+    let tmp = dir.join(format!(".{}.{}.part", stem, ext));
+    tokio::fs::write(&tmp, &bytes).await.context("write export file")?;
+    tokio::fs::rename(&tmp, &out_path).await.context("finalize export file")?;
+    Ok(true)
+}
@@ -0,0 +1,163 @@
+use ab_glyph::PxScale;
+use anyhow::{anyhow, Context, Result};
+use image::{imageops, Rgb, RgbImage};
+use imageproc::drawing::{draw_text_mut, text_size};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::caption::load_font;
+use crate::comic::newest_entry_image;
+use crate::database::entry_ids_created_between;
+use crate::settings::Settings;
+
+const CELL_SIZE: u32 = 400;
+const LABEL_HEIGHT: u32 = 44;
+const GUTTER: u32 = 16;
+const MAX_COLUMNS: u32 = 6;
+const BACKGROUND: Rgb<u8> = Rgb([250, 247, 240]);
+const LABEL_COLOR: Rgb<u8> = Rgb([60, 60, 60]);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ContactSheetResult {
+    /// None when there was nothing to export
+    pub path: Option<String>,
+    pub comic_count: usize,
+    pub message: String,
+}
+
+struct SheetItem {
+    date: String,
+    image_path: PathBuf,
+}
+
+/// `[from, to)` day bounds for a calendar month.
+fn month_bounds(year: i32, month: u32) -> Result<(String, String)> {
+    if !(1..=12).contains(&month) {
+        return Err(anyhow!("month must be between 1 and 12, got {}", month));
+    }
+    let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    Ok((
+        format!("{:04}-{:02}-01", year, month),
+        format!("{:04}-{:02}-01", next_year, next_month),
+    ))
+}
+
+/// Tile the newest comic of every entry created in the month into one image at `path`,
+/// labeling each tile with its date. The format follows the path's extension.
+pub async fn export_month_contact_sheet(
+    pool: &Pool<Sqlite>,
+    data_dir: &Path,
+    settings: &Settings,
+    year: i32,
+    month: u32,
+    path: &Path,
+) -> Result<ContactSheetResult> {
+    let (from, to) = month_bounds(year, month)?;
+    let entries = entry_ids_created_between(pool, &from, &to)
+        .await
+        .map_err(|e| anyhow!(e))?;
+
+    // Decoding, compositing and encoding are CPU-bound and the file I/O is blocking
+    let (data_dir, settings, path) = (data_dir.to_path_buf(), settings.clone(), path.to_path_buf());
+    tokio::task::spawn_blocking(move || render_contact_sheet(entries, &data_dir, &settings, year, month, &path))
+        .await
+        .context("contact sheet task failed")?
+}
+
+fn render_contact_sheet(
+    entries: Vec<(String, String)>,
+    data_dir: &Path,
+    settings: &Settings,
+    year: i32,
+    month: u32,
+    path: &Path,
+) -> Result<ContactSheetResult> {
+    let items: Vec<SheetItem> = entries
+        .into_iter()
+        .filter_map(|(id, created_at)| {
+            let image_path = newest_entry_image(&data_dir.join("images").join(&id))?;
+            let date = created_at.split('T').next().unwrap_or_default().to_string();
+            Some(SheetItem { date, image_path })
+        })
+        .collect();
+
+    if items.is_empty() {
+        return Ok(ContactSheetResult {
+            path: None,
+            comic_count: 0,
+            message: format!("nothing to export: no comics in {:04}-{:02}", year, month),
+        });
+    }
+
+    let font = match load_font(settings) {
+        Ok(f) => Some(f),
+        Err(e) => {
+            warn!(error = %e, "contact sheet: no font, tiles will be unlabeled");
+            None
+        }
+    };
+
+    let count = items.len() as u32;
+    let columns = ((count as f64).sqrt().ceil() as u32).clamp(1, MAX_COLUMNS);
+    let rows = count.div_ceil(columns);
+    let tile_height = CELL_SIZE + LABEL_HEIGHT;
+    let width = columns * CELL_SIZE + (columns + 1) * GUTTER;
+    let height = rows * tile_height + (rows + 1) * GUTTER;
+    let mut sheet = RgbImage::from_pixel(width, height, BACKGROUND);
+    let scale = PxScale::from(26.0);
+
+    let mut placed = 0usize;
+    for (i, item) in items.iter().enumerate() {
+        let col = i as u32 % columns;
+        let row = i as u32 / columns;
+        let x0 = GUTTER + col * (CELL_SIZE + GUTTER);
+        let y0 = GUTTER + row * (tile_height + GUTTER);
+
+        let img = match image::open(&item.image_path) {
+            Ok(img) => img,
+            Err(e) => {
+                warn!(path = %item.image_path.display(), error = %e, "contact sheet: skipping unreadable image");
+                continue;
+            }
+        };
+        // Fit inside the cell, centered, keeping the aspect ratio
+        let thumb = img.thumbnail(CELL_SIZE, CELL_SIZE).to_rgb8();
+        let dx = (CELL_SIZE - thumb.width()) / 2;
+        let dy = (CELL_SIZE - thumb.height()) / 2;
+        imageops::overlay(&mut sheet, &thumb, (x0 + dx) as i64, (y0 + dy) as i64);
+        placed += 1;
+
+        if let Some(font) = &font {
+            let (w, h) = text_size(scale, font, &item.date);
+            let lx = x0 + CELL_SIZE.saturating_sub(w) / 2;
+            let ly = y0 + CELL_SIZE + LABEL_HEIGHT.saturating_sub(h) / 2;
+            draw_text_mut(&mut sheet, LABEL_COLOR, lx as i32, ly as i32, scale, font, &item.date);
+        }
+    }
+
+    if placed == 0 {
+        return Ok(ContactSheetResult {
+            path: None,
+            comic_count: 0,
+            message: format!("nothing to export: comic images for {:04}-{:02} could not be read", year, month),
+        });
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).context("create export dir")?;
+    }
+    // JPEG/WebP by extension; anything else is written as PNG
+    let format = image::ImageFormat::from_path(path).unwrap_or(image::ImageFormat::Png);
+    sheet
+        .save_with_format(path, format)
+        .with_context(|| format!("write contact sheet to {}", path.display()))?;
+    info!(path = %path.display(), comics = placed, "contact sheet exported");
+
+    Ok(ContactSheetResult {
+        path: Some(path.display().to_string()),
+        comic_count: placed,
+        message: format!("exported {} comics", placed),
+    })
+}
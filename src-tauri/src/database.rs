@@ -1,16 +1,24 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use sqlx::{Pool, Sqlite, Row, sqlite::SqlitePoolOptions, sqlite::SqliteConnectOptions};
+use sqlx::{Pool, Sqlite, Row, sqlite::SqlitePoolOptions, sqlite::SqliteConnectOptions, sqlite::SqliteRow, SqliteConnection};
 use std::path::Path;
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 use time::OffsetDateTime;
 
+use crate::comic::{ComicJobOptions, ComicJobStatus, ComicStage, ParsedPanel};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EntryUpsert {
     pub id: Option<String>,
     pub body_cipher: Vec<u8>,
+    /// `None` keeps an existing entry's mood; `set_entry_mood` clears it
     pub mood: Option<String>,
+    /// `None` keeps an existing entry's tags
     pub tags: Option<serde_json::Value>,
+    /// RFC3339 creation time for a new entry (e.g. when importing); an existing entry keeps its own
+    #[serde(default)]
+    pub created_at: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -22,6 +30,14 @@ pub struct Entry {
     pub mood: Option<String>,
     pub tags: Option<serde_json::Value>,
     pub embedding: Option<Vec<u8>>,
+    pub pinned: bool,
+    pub body_hash: Option<String>,
+    /// Comic style used for this entry when a job doesn't specify one
+    pub preferred_style: Option<String>,
+    /// Short generated title shown in lists instead of the preview
+    pub summary: Option<String>,
+    /// Hidden from the main list but kept as a normal entry
+    pub archived: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -32,12 +48,122 @@ pub struct EntryListItem {
     pub body_preview: Option<String>,
     pub mood: Option<String>,
     pub tags: Option<serde_json::Value>,
+    pub pinned: bool,
+    /// Generated title; the UI shows it when present and `body_preview` otherwise
+    pub summary: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct ListParams {
     pub limit: Option<i64>,
     pub offset: Option<i64>,
+    /// Only entries with this mood (case-insensitive)
+    pub mood: Option<String>,
+    /// Only entries carrying at least one of these tags (case-insensitive)
+    pub tags_any: Option<Vec<String>>,
+    /// RFC3339 lower bound on `created_at`, inclusive
+    pub date_from: Option<String>,
+    /// RFC3339 upper bound on `created_at`, exclusive
+    pub date_to: Option<String>,
+}
+
+/// One page of a filtered entry list plus how many entries match overall.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EntryPage {
+    pub items: Vec<EntryListItem>,
+    pub total: i64,
+}
+
+/// Every table/column the app expects, with the column type used when healing via ALTER TABLE.
+/// Keep in sync with `init_db`.
+const EXPECTED_SCHEMA: &[(&str, &[(&str, &str)])] = &[
+    ("entries", &[
+        ("id", "TEXT"),
+        ("created_at", "TEXT"),
+        ("updated_at", "TEXT"),
+        ("body_cipher", "BLOB"),
+        ("mood", "TEXT"),
+        ("tags", "TEXT"),
+        ("embedding", "BLOB"),
+        ("pinned", "INTEGER NOT NULL DEFAULT 0"),
+        ("body_hash", "TEXT"),
+        ("preferred_style", "TEXT"),
+        ("summary", "TEXT"),
+        ("archived", "INTEGER NOT NULL DEFAULT 0"),
+    ]),
+    ("storyboards", &[
+        ("id", "TEXT"),
+        ("entry_id", "TEXT"),
+        ("json_cipher", "BLOB"),
+        ("model", "TEXT"),
+        ("created_at", "TEXT"),
+    ]),
+    ("panels", &[
+        ("id", "TEXT"),
+        ("entry_id", "TEXT"),
+        ("idx", "INTEGER"),
+        ("prompt_cipher", "BLOB"),
+        ("dialogue_cipher", "BLOB"),
+        ("seed", "INTEGER"),
+        ("cfg", "REAL"),
+        ("style", "TEXT"),
+        ("image_path", "TEXT"),
+        ("meta", "TEXT"),
+    ]),
+    ("style_presets", &[
+        ("id", "TEXT"),
+        ("name", "TEXT"),
+        ("prompt_fragment", "TEXT"),
+        ("layout_hint", "TEXT"),
+        ("builtin", "INTEGER NOT NULL DEFAULT 0"),
+        ("created_at", "TEXT"),
+    ]),
+    ("assets", &[
+        ("id", "TEXT"),
+        ("kind", "TEXT"),
+        ("path", "TEXT"),
+        ("meta", "TEXT"),
+    ]),
+    ("job_metrics", &[
+        ("id", "INTEGER"),
+        ("job_id", "TEXT"),
+        ("entry_id", "TEXT"),
+        ("stage", "TEXT"),
+        ("provider", "TEXT"),
+        ("style", "TEXT"),
+        ("duration_ms", "INTEGER"),
+        ("created_at", "TEXT"),
+        ("model", "TEXT"),
+    ]),
+    ("comic_jobs", &[
+        ("job_id", "TEXT"),
+        ("entry_id", "TEXT"),
+        ("style", "TEXT"),
+        ("stage", "TEXT"),
+        ("updated_at", "TEXT"),
+        ("result_image_path", "TEXT"),
+        ("storyboard_text", "TEXT"),
+        ("panel_image_paths", "TEXT"),
+        ("meta", "TEXT"),
+        ("options", "TEXT"),
+    ]),
+    ("schema_meta", &[
+        ("version", "INTEGER"),
+    ]),
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SchemaIssue {
+    pub table: String,
+    pub column: Option<String>,
+    pub problem: String,
+    pub healed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SchemaReport {
+    pub ok: bool,
+    pub issues: Vec<SchemaIssue>,
 }
 
 pub fn now_iso() -> String {
@@ -46,73 +172,206 @@ pub fn now_iso() -> String {
         .unwrap_or_default()
 }
 
-pub async fn init_db(pool: &Pool<Sqlite>) -> Result<()> {
-    // First, check if we need to migrate from the old schema with title
-    let table_info = sqlx::query("PRAGMA table_info(entries)")
-        .fetch_all(pool)
+/// A numbered schema change. Steps run in order, each in its own transaction, and
+/// `schema_meta.version` records the last one applied.
+struct Migration {
+    version: i64,
+    name: &'static str,
+    description: &'static str,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "drop_entries_title",
+        description: "rebuild the entries table without the old title column (titles are discarded)",
+    },
+    Migration {
+        version: 2,
+        name: "add_comic_jobs_meta",
+        description: "add comic_jobs.meta for job details such as quiet-hours waits",
+    },
+    Migration {
+        version: 3,
+        name: "add_entries_pinned",
+        description: "add entries.pinned",
+    },
+    Migration {
+        version: 4,
+        name: "add_entries_body_hash",
+        description: "add entries.body_hash for duplicate detection",
+    },
+    Migration {
+        version: 5,
+        name: "add_entries_preferred_style",
+        description: "add entries.preferred_style",
+    },
+    Migration {
+        version: 6,
+        name: "add_entries_summary",
+        description: "add entries.summary for generated titles",
+    },
+    Migration {
+        version: 7,
+        name: "add_entries_archived",
+        description: "add entries.archived",
+    },
+    Migration {
+        version: 8,
+        name: "add_comic_jobs_panel_image_paths",
+        description: "add comic_jobs.panel_image_paths for per-panel renders",
+    },
+    Migration {
+        version: 9,
+        name: "add_job_metrics_model",
+        description: "add job_metrics.model",
+    },
+    Migration {
+        version: 10,
+        name: "add_comic_jobs_options",
+        description: "add comic_jobs.options so queued jobs can resume after a restart",
+    },
+];
+
+/// The schema version recorded in the database; None before the first migration run.
+pub async fn schema_version(pool: &Pool<Sqlite>) -> Result<Option<i64>, String> {
+    if table_columns(pool, "schema_meta").await?.is_empty() {
+        return Ok(None);
+    }
+    sqlx::query_scalar("SELECT MAX(version) FROM schema_meta")
+        .fetch_one(pool)
         .await
-        .unwrap_or_default();
-    
-    let has_title_column = table_info.iter().any(|row| {
-        row.try_get::<String, _>("name")
-            .map(|n| n == "title")
-            .unwrap_or(false)
-    });
-    
-    if has_title_column {
-        // Need to migrate: create new table without title column
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS entries_new (
-                id TEXT PRIMARY KEY,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL,
-                body_cipher BLOB NOT NULL,
-                mood TEXT,
-                tags TEXT,
-                embedding BLOB
-            );
-            "#,
-        )
-        .execute(pool)
-        .await?;
-        
-        // Copy data from old table (excluding title)
-        sqlx::query(
-            r#"
-            INSERT INTO entries_new (id, created_at, updated_at, body_cipher, mood, tags, embedding)
-            SELECT id, created_at, updated_at, body_cipher, mood, tags, embedding FROM entries
-            "#,
-        )
-        .execute(pool)
+        .map_err(|e| e.to_string())
+}
+
+async fn conn_has_column(conn: &mut SqliteConnection, table: &str, column: &str) -> Result<bool> {
+    let rows = sqlx::query(&format!("PRAGMA table_info({})", table))
+        .fetch_all(&mut *conn)
         .await?;
-        
-        // Drop old table and rename new one
-        sqlx::query("DROP TABLE entries")
-            .execute(pool)
+    Ok(rows.iter().any(|row| row.try_get::<String, _>("name").map(|n| n == column).unwrap_or(false)))
+}
+
+// Additive step: tables created later by `init_db` already have the column
+async fn add_column_step(conn: &mut SqliteConnection, table: &str, column: &str, decl: &str) -> Result<()> {
+    let table_exists = !sqlx::query(&format!("PRAGMA table_info({})", table))
+        .fetch_all(&mut *conn)
+        .await?
+        .is_empty();
+    if table_exists && !conn_has_column(conn, table, column).await? {
+        sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl))
+            .execute(&mut *conn)
             .await?;
-        
-        sqlx::query("ALTER TABLE entries_new RENAME TO entries")
-            .execute(pool)
+    }
+    Ok(())
+}
+
+// Whether running the step would rewrite existing data on this database
+async fn migration_is_destructive(pool: &Pool<Sqlite>, migration: &Migration) -> Result<bool, String> {
+    match migration.version {
+        1 => Ok(table_columns(pool, "entries").await?.iter().any(|c| c == "title")),
+        _ => Ok(false),
+    }
+}
+
+async fn apply_migration_step(conn: &mut SqliteConnection, version: i64) -> Result<()> {
+    match version {
+        1 => {
+            // Databases created before the title column existed (or fresh ones) have nothing to do
+            if !conn_has_column(conn, "entries", "title").await? {
+                return Ok(());
+            }
+            sqlx::query(
+                r#"
+                CREATE TABLE entries_new (
+                    id TEXT PRIMARY KEY,
+                    created_at TEXT NOT NULL,
+                    updated_at TEXT NOT NULL,
+                    body_cipher BLOB NOT NULL,
+                    mood TEXT,
+                    tags TEXT,
+                    embedding BLOB
+                );
+                "#,
+            )
+            .execute(&mut *conn)
             .await?;
-    } else {
-        // Create table with new schema (no title)
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS entries (
-                id TEXT PRIMARY KEY,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL,
-                body_cipher BLOB NOT NULL,
-                mood TEXT,
-                tags TEXT,
-                embedding BLOB
-            );
-            "#,
-        )
+            sqlx::query(
+                r#"
+                INSERT INTO entries_new (id, created_at, updated_at, body_cipher, mood, tags, embedding)
+                SELECT id, created_at, updated_at, body_cipher, mood, tags, embedding FROM entries
+                "#,
+            )
+            .execute(&mut *conn)
+            .await?;
+            sqlx::query("DROP TABLE entries").execute(&mut *conn).await?;
+            sqlx::query("ALTER TABLE entries_new RENAME TO entries").execute(&mut *conn).await?;
+            Ok(())
+        }
+        2 => add_column_step(conn, "comic_jobs", "meta", "TEXT").await,
+        3 => add_column_step(conn, "entries", "pinned", "INTEGER NOT NULL DEFAULT 0").await,
+        4 => add_column_step(conn, "entries", "body_hash", "TEXT").await,
+        5 => add_column_step(conn, "entries", "preferred_style", "TEXT").await,
+        6 => add_column_step(conn, "entries", "summary", "TEXT").await,
+        7 => add_column_step(conn, "entries", "archived", "INTEGER NOT NULL DEFAULT 0").await,
+        8 => add_column_step(conn, "comic_jobs", "panel_image_paths", "TEXT").await,
+        9 => add_column_step(conn, "job_metrics", "model", "TEXT").await,
+        10 => add_column_step(conn, "comic_jobs", "options", "TEXT").await,
+        other => Err(anyhow::anyhow!("unknown schema migration {}", other)),
+    }
+}
+
+/// Apply every migration newer than the recorded version. Returns how many ran.
+async fn run_migrations(pool: &Pool<Sqlite>) -> Result<usize> {
+    sqlx::query("CREATE TABLE IF NOT EXISTS schema_meta (version INTEGER NOT NULL)")
         .execute(pool)
         .await?;
+    let current = schema_version(pool).await.map_err(|e| anyhow::anyhow!(e))?.unwrap_or(0);
+    let mut applied = 0;
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let mut tx = pool.begin().await?;
+        apply_migration_step(&mut tx, migration.version)
+            .await
+            .with_context(|| format!("migration {} ({})", migration.version, migration.name))?;
+        sqlx::query("DELETE FROM schema_meta").execute(&mut *tx).await?;
+        sqlx::query("INSERT INTO schema_meta (version) VALUES (?1)")
+            .bind(migration.version)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        tracing::info!(version = migration.version, name = migration.name, "applied schema migration");
+        applied += 1;
     }
+    Ok(applied)
+}
+
+pub async fn init_db(pool: &Pool<Sqlite>) -> Result<()> {
+    run_migrations(pool).await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS entries (
+            id TEXT PRIMARY KEY,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            body_cipher BLOB NOT NULL,
+            mood TEXT,
+            tags TEXT,
+            embedding BLOB,
+            pinned INTEGER NOT NULL DEFAULT 0,
+            body_hash TEXT,
+            preferred_style TEXT,
+            summary TEXT,
+            archived INTEGER NOT NULL DEFAULT 0
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_entries_updated_at ON entries(updated_at)")
+        .execute(pool)
+        .await?;
+    backfill_body_hashes(pool).await?;
 
     sqlx::query(
         r#"
@@ -160,10 +419,153 @@ pub async fn init_db(pool: &Pool<Sqlite>) -> Result<()> {
     .execute(pool)
     .await?;
 
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS job_metrics (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            job_id TEXT NOT NULL,
+            entry_id TEXT NOT NULL,
+            stage TEXT NOT NULL,
+            provider TEXT,
+            style TEXT,
+            duration_ms INTEGER NOT NULL,
+            created_at TEXT NOT NULL,
+            model TEXT
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS comic_jobs (
+            job_id TEXT PRIMARY KEY,
+            entry_id TEXT NOT NULL,
+            style TEXT NOT NULL,
+            stage TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            result_image_path TEXT,
+            storyboard_text TEXT,
+            panel_image_paths TEXT,
+            meta TEXT,
+            options TEXT,
+            FOREIGN KEY(entry_id) REFERENCES entries(id)
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_comic_jobs_entry ON comic_jobs(entry_id)")
+        .execute(pool)
+        .await?;
+
+    // Built-ins are only added when the table is first created, so deleting one sticks
+    let seed_presets = table_columns(pool, "style_presets")
+        .await
+        .map_err(|e| anyhow::anyhow!(e))?
+        .is_empty();
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS style_presets (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            prompt_fragment TEXT NOT NULL,
+            layout_hint TEXT,
+            builtin INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    if seed_presets {
+        seed_style_presets(pool).await?;
+    }
+
+    Ok(())
+}
+
+// (id, name, prompt_fragment, layout_hint)
+const BUILTIN_STYLE_PRESETS: &[(&str, &str, &str, Option<&str>)] = &[
+    (
+        "builtin-watercolor-diary",
+        "Watercolor diary",
+        "soft watercolor washes on textured paper, loose ink outlines, muted pastel palette, handwritten-style lettering",
+        None,
+    ),
+    (
+        "builtin-90s-manga",
+        "90s manga",
+        "1990s shoujo/shonen manga look, screentone shading, expressive eyes, speed lines for emphasis, mostly black and white with sparing color accents",
+        Some("3-4 panels with varied sizes, read left-to-right, dynamic angled gutters"),
+    ),
+    (
+        "builtin-newspaper-strip",
+        "Newspaper strip",
+        "classic Sunday newspaper comic strip, bold clean line art, flat bright colors, simple backgrounds",
+        Some("exactly 4 equal panels in one horizontal row with thin black borders"),
+    ),
+    (
+        "builtin-storybook",
+        "Storybook",
+        "children's picture book illustration, gouache textures, warm lighting, rounded friendly shapes",
+        None,
+    ),
+];
+
+async fn seed_style_presets(pool: &Pool<Sqlite>) -> Result<()> {
+    let now = now_iso();
+    for (id, name, fragment, layout) in BUILTIN_STYLE_PRESETS {
+        sqlx::query(
+            r#"INSERT OR IGNORE INTO style_presets (id, name, prompt_fragment, layout_hint, builtin, created_at)
+               VALUES (?1, ?2, ?3, ?4, 1, ?5)"#
+        )
+        .bind(id)
+        .bind(name)
+        .bind(fragment)
+        .bind(layout)
+        .bind(&now)
+        .execute(pool)
+        .await?;
+    }
     Ok(())
 }
 
-pub async fn create_pool(db_path: &Path) -> Result<Pool<Sqlite>> {
+/// SHA-256 (hex) of the plaintext entry body.
+pub fn body_hash(plaintext: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(plaintext))
+}
+
+/// Fill `body_hash` for rows written before the column existed. Returns rows updated.
+pub async fn backfill_body_hashes(pool: &Pool<Sqlite>) -> Result<u64> {
+    let rows = sqlx::query(r#"SELECT id, body_cipher FROM entries WHERE body_hash IS NULL"#)
+        .fetch_all(pool)
+        .await?;
+    let mut updated = 0u64;
+    for row in rows {
+        let id: String = row.try_get("id")?;
+        let body: Vec<u8> = row.try_get("body_cipher")?;
+        let plaintext = match crate::vault::decrypt(&body) {
+            Ok(p) => p,
+            Err(e) => {
+                tracing::warn!(entry_id = %id, error = %e, "skipping body hash: cannot decrypt");
+                continue;
+            }
+        };
+        sqlx::query(r#"UPDATE entries SET body_hash = ?1 WHERE id = ?2"#)
+            .bind(body_hash(&plaintext))
+            .bind(&id)
+            .execute(pool)
+            .await?;
+        updated += 1;
+    }
+    Ok(updated)
+}
+
+/// Open the database and run migrations. With `defer_destructive`, nothing is migrated
+/// when a destructive migration is pending; the UI asks first and calls `apply_migrations`.
+pub async fn create_pool(db_path: &Path, defer_destructive: bool) -> Result<Pool<Sqlite>> {
     let opts = SqliteConnectOptions::new()
         .filename(db_path)
         .create_if_missing(true);
@@ -173,32 +575,147 @@ pub async fn create_pool(db_path: &Path) -> Result<Pool<Sqlite>> {
         .connect_with(opts)
         .await?;
     
+    if defer_destructive {
+        let pending = pending_migrations(&pool).await.map_err(|e| anyhow::anyhow!(e))?;
+        if let Some(m) = pending.iter().find(|m| m.destructive) {
+            tracing::warn!(migration = %m.name, "destructive migration pending; waiting for confirmation");
+            return Ok(pool);
+        }
+    }
     init_db(&pool).await?;
     Ok(pool)
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PendingMigration {
+    pub name: String,
+    pub description: String,
+    /// Rewrites or removes existing data rather than only adding to the schema
+    pub destructive: bool,
+}
+
+/// What `init_db` would change right now, without changing anything.
+pub async fn pending_migrations(pool: &Pool<Sqlite>) -> Result<Vec<PendingMigration>, String> {
+    let mut pending = Vec::new();
+    let current = schema_version(pool).await?.unwrap_or(0);
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        pending.push(PendingMigration {
+            name: migration.name.to_string(),
+            description: migration.description.to_string(),
+            destructive: migration_is_destructive(pool, migration).await?,
+        });
+    }
+    for (table, columns) in EXPECTED_SCHEMA {
+        let existing = table_columns(pool, table).await?;
+        if existing.is_empty() {
+            pending.push(PendingMigration {
+                name: format!("create_{}", table),
+                description: format!("create table {}", table),
+                destructive: false,
+            });
+            continue;
+        }
+        for (column, decl) in columns.iter() {
+            let name = format!("add_{}_{}", table, column);
+            // Columns added by a pending numbered migration are already listed
+            if !existing.iter().any(|c| c == column) && !pending.iter().any(|p| p.name == name) {
+                pending.push(PendingMigration {
+                    name,
+                    description: format!("add column {}.{} {}", table, column, decl),
+                    destructive: false,
+                });
+            }
+        }
+    }
+    let has_index: Option<i64> = sqlx::query_scalar(
+        "SELECT 1 FROM sqlite_master WHERE type = 'index' AND name = 'idx_entries_updated_at'",
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    if has_index.is_none() {
+        pending.push(PendingMigration {
+            name: "index_entries_updated_at".to_string(),
+            description: "index entries by updated_at".to_string(),
+            destructive: false,
+        });
+    }
+    Ok(pending)
+}
+
+/// Run all pending migrations and report anything still pending afterwards.
+pub async fn apply_migrations(pool: &Pool<Sqlite>) -> Result<Vec<PendingMigration>, String> {
+    init_db(pool).await.map_err(|e| format!("migration failed: {}", e))?;
+    pending_migrations(pool).await
+}
+
+// Limits on entry tags; every list query parses them, so they must stay small
+const MAX_TAGS: usize = 32;
+const MAX_TAG_CHARS: usize = 64;
+
+/// Tags must be an array of short, non-empty strings. Whitespace is trimmed and
+/// duplicates are dropped; anything else is rejected with a descriptive error.
+fn validate_tags(tags: Option<serde_json::Value>) -> Result<Option<serde_json::Value>, String> {
+    let items = match tags {
+        None | Some(serde_json::Value::Null) => return Ok(None),
+        Some(serde_json::Value::Array(items)) => items,
+        Some(other) => return Err(format!("tags must be an array of strings, got: {}", other)),
+    };
+    let mut cleaned: Vec<String> = Vec::new();
+    for item in items {
+        let serde_json::Value::String(tag) = item else {
+            return Err(format!("tags must be strings, got: {}", item));
+        };
+        let tag = tag.trim();
+        if tag.is_empty() {
+            continue;
+        }
+        if tag.chars().count() > MAX_TAG_CHARS {
+            return Err(format!("tag '{}…' is longer than {} characters", tag.chars().take(20).collect::<String>(), MAX_TAG_CHARS));
+        }
+        if !cleaned.iter().any(|t| t == tag) {
+            cleaned.push(tag.to_string());
+        }
+    }
+    if cleaned.len() > MAX_TAGS {
+        return Err(format!("too many tags ({}), at most {} are allowed", cleaned.len(), MAX_TAGS));
+    }
+    Ok(Some(serde_json::Value::from(cleaned)))
+}
+
 pub async fn upsert_entry(pool: &Pool<Sqlite>, entry: EntryUpsert) -> Result<Entry, String> {
     let id = entry.id.unwrap_or_else(|| Uuid::new_v4().to_string());
     let now = now_iso();
-    let tags_json = entry.tags.map(|t| t.to_string());
+    let tags_json = validate_tags(entry.tags)?.map(|t| t.to_string());
+    // Hash the plaintext so identical bodies match however they were encrypted
+    let hash = body_hash(&crate::vault::decrypt(&entry.body_cipher)?);
+    let created_at = match entry.created_at.as_deref() {
+        Some(c) => normalize_rfc3339("created_at", c)?,
+        None => now.clone(),
+    };
 
     let _ = sqlx::query(
         r#"
-        INSERT INTO entries (id, created_at, updated_at, body_cipher, mood, tags, embedding)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, NULL)
+        INSERT INTO entries (id, created_at, updated_at, body_cipher, mood, tags, embedding, body_hash)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, NULL, ?7)
         ON CONFLICT(id) DO UPDATE SET
           updated_at=excluded.updated_at,
           body_cipher=excluded.body_cipher,
-          mood=excluded.mood,
-          tags=excluded.tags
+          -- a save without a mood or tags keeps the stored ones (e.g. an inferred mood)
+          mood=COALESCE(excluded.mood, entries.mood),
+          tags=COALESCE(excluded.tags, entries.tags),
+          -- an edited body makes the old embedding stale
+          embedding=CASE WHEN entries.body_hash IS excluded.body_hash THEN entries.embedding ELSE NULL END,
+          body_hash=excluded.body_hash
         "#,
     )
     .bind(&id)
-    .bind(&now)
+    .bind(&created_at)
     .bind(&now)
     .bind(&entry.body_cipher)
     .bind(&entry.mood)
     .bind(&tags_json)
+    .bind(&hash)
     .execute(pool)
     .await
     .map_err(|e| e.to_string())?;
@@ -206,15 +723,28 @@ pub async fn upsert_entry(pool: &Pool<Sqlite>, entry: EntryUpsert) -> Result<Ent
     get_entry(pool, id).await
 }
 
+/// Id of an entry whose plaintext hashes to `hash` (see `body_hash`), if any.
+pub async fn find_entry_by_body_hash(pool: &Pool<Sqlite>, hash: &str) -> Result<Option<String>, String> {
+    sqlx::query_scalar(r#"SELECT id FROM entries WHERE body_hash = ?1 LIMIT 1"#)
+        .bind(hash)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Columns read into `Entry` by `entry_from_row`
+const ENTRY_COLUMNS: &str = "id, created_at, updated_at, body_cipher, mood, tags, embedding, pinned, body_hash, preferred_style, summary, archived";
+
 pub async fn get_entry(pool: &Pool<Sqlite>, id: String) -> Result<Entry, String> {
-    let row = sqlx::query(
-        r#"SELECT id, created_at, updated_at, body_cipher, mood, tags, embedding FROM entries WHERE id = ?1"#
-    )
-    .bind(&id)
-    .fetch_one(pool)
-    .await
-    .map_err(|e| e.to_string())?;
-    
+    let row = sqlx::query(&format!("SELECT {} FROM entries WHERE id = ?1", ENTRY_COLUMNS))
+        .bind(&id)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    entry_from_row(&row)
+}
+
+fn entry_from_row(row: &SqliteRow) -> Result<Entry, String> {
     let tags_str: Option<String> = row.try_get("tags").map_err(|e| e.to_string())?;
     let tags_val = tags_str
         .as_deref()
@@ -228,58 +758,171 @@ pub async fn get_entry(pool: &Pool<Sqlite>, id: String) -> Result<Entry, String>
         mood: row.try_get("mood").map_err(|e| e.to_string())?,
         tags: tags_val,
         embedding: row.try_get("embedding").ok(),
+        pinned: row.try_get::<i64, _>("pinned").unwrap_or(0) != 0,
+        body_hash: row.try_get("body_hash").ok().flatten(),
+        preferred_style: row.try_get("preferred_style").ok().flatten(),
+        summary: row.try_get("summary").ok().flatten(),
+        archived: row.try_get::<i64, _>("archived").unwrap_or(0) != 0,
     })
 }
 
-pub async fn list_entries(pool: &Pool<Sqlite>, params: Option<ListParams>) -> Result<Vec<EntryListItem>, String> {
+/// Entries for the main list; archived entries are left out.
+pub async fn list_entries(
+    pool: &Pool<Sqlite>,
+    params: Option<ListParams>,
+    preview_length: usize,
+) -> Result<EntryPage, String> {
+    list_entries_by_archived(pool, params, preview_length, false).await
+}
+
+/// Archived entries only, in the same order as `list_entries`.
+pub async fn list_archived_entries(
+    pool: &Pool<Sqlite>,
+    params: Option<ListParams>,
+    preview_length: usize,
+) -> Result<EntryPage, String> {
+    list_entries_by_archived(pool, params, preview_length, true).await
+}
+
+// RFC3339 in UTC, the form `created_at` is stored in
+fn normalize_rfc3339(name: &str, value: &str) -> Result<String, String> {
+    use time::format_description::well_known::Rfc3339;
+    OffsetDateTime::parse(value.trim(), &Rfc3339)
+        .map_err(|e| format!("invalid RFC3339 timestamp for {} '{}': {}", name, value, e))?
+        .to_offset(time::UtcOffset::UTC)
+        .format(&Rfc3339)
+        .map_err(|e| e.to_string())
+}
+
+// WHERE clause (with `?` placeholders) and its binds, in order, for the list filters
+fn entry_filter(params: Option<&ListParams>, archived: bool) -> Result<(String, Vec<String>), String> {
+    let mut conditions = vec![format!("archived = {}", archived as i64)];
+    let mut binds = Vec::new();
+    let Some(p) = params else {
+        return Ok((conditions.join(" AND "), binds));
+    };
+    if let Some(mood) = p.mood.as_deref().map(str::trim).filter(|m| !m.is_empty()) {
+        conditions.push("lower(mood) = ?".to_string());
+        binds.push(mood.to_lowercase());
+    }
+    let tags: Vec<String> = p
+        .tags_any
+        .iter()
+        .flatten()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    if !tags.is_empty() {
+        // json_each errors on malformed JSON, so rows with bad tags are treated as untagged
+        conditions.push(format!(
+            "EXISTS (SELECT 1 FROM json_each(CASE WHEN json_valid(entries.tags) THEN entries.tags ELSE '[]' END) AS t \
+             WHERE lower(t.value) IN ({}))",
+            vec!["?"; tags.len()].join(", ")
+        ));
+        binds.extend(tags);
+    }
+    // julianday() compares exactly however many fractional digits each timestamp has
+    if let Some(from) = p.date_from.as_deref().filter(|s| !s.trim().is_empty()) {
+        conditions.push("julianday(created_at) >= julianday(?)".to_string());
+        binds.push(normalize_rfc3339("date_from", from)?);
+    }
+    if let Some(to) = p.date_to.as_deref().filter(|s| !s.trim().is_empty()) {
+        conditions.push("julianday(created_at) < julianday(?)".to_string());
+        binds.push(normalize_rfc3339("date_to", to)?);
+    }
+    Ok((conditions.join(" AND "), binds))
+}
+
+async fn list_entries_by_archived(
+    pool: &Pool<Sqlite>,
+    params: Option<ListParams>,
+    preview_length: usize,
+    archived: bool,
+) -> Result<EntryPage, String> {
     let limit = params.as_ref().and_then(|p| p.limit).unwrap_or(100);
     let offset = params.as_ref().and_then(|p| p.offset).unwrap_or(0);
+    let (filter, binds) = entry_filter(params.as_ref(), archived)?;
+
+    let count_sql = format!("SELECT COUNT(*) FROM entries WHERE {}", filter);
+    let mut count_query = sqlx::query_scalar::<_, i64>(&count_sql);
+    for b in &binds {
+        count_query = count_query.bind(b);
+    }
+    let total = count_query.fetch_one(pool).await.map_err(|e| e.to_string())?;
+
+    let sql = format!(
+        r#"SELECT id, created_at, updated_at, body_cipher, mood, tags, pinned, summary FROM entries
+           WHERE {}
+           ORDER BY pinned DESC, created_at DESC, id ASC LIMIT ? OFFSET ?"#,
+        filter
+    );
+    let mut query = sqlx::query(&sql);
+    for b in &binds {
+        query = query.bind(b);
+    }
+    let rows = query
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(EntryPage {
+        items: rows.iter().map(|row| entry_list_item_from_row(row, preview_length)).collect(),
+        total,
+    })
+}
+
+// Used when `preview_length` isn't configured
+pub const DEFAULT_PREVIEW_LENGTH: usize = 50;
+
+/// First `max_chars` characters of `text`, cut back to the last word boundary, with an
+/// ellipsis only when something was actually cut. A single overlong word is hard-cut.
+pub fn preview_text(text: &str, max_chars: usize) -> String {
+    let text = text.trim();
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let head: String = text.chars().take(max_chars).collect();
+    // The cut lands on a boundary if the next char is whitespace
+    let next_is_space = text.chars().nth(max_chars).map(char::is_whitespace).unwrap_or(true);
+    let cut = if next_is_space {
+        head.as_str()
+    } else {
+        match head.rfind(char::is_whitespace) {
+            Some(idx) => &head[..idx],
+            None => head.as_str(),
+        }
+    };
+    format!("{}...", cut.trim_end())
+}
+
+// Expects the columns selected by `list_entries`
+fn entry_list_item_from_row(row: &SqliteRow, preview_length: usize) -> EntryListItem {
+    let tags_str: Option<String> = row.try_get("tags").ok();
+    let tags_val = tags_str
+        .as_deref()
+        .and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok());
     
-    let rows = sqlx::query(
-        r#"SELECT id, created_at, updated_at, body_cipher, mood, tags FROM entries ORDER BY created_at DESC LIMIT ?1 OFFSET ?2"#
-    )
-    .bind(limit)
-    .bind(offset)
-    .fetch_all(pool)
-    .await
-    .map_err(|e| e.to_string())?;
-    
-    let items = rows
-        .into_iter()
-        .map(|row| {
-            let tags_str: Option<String> = row.try_get("tags").ok();
-            let tags_val = tags_str
-                .as_deref()
-                .and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok());
-            
-            // Get body preview - first 50 chars of decrypted body
-            let body_preview = if let Ok(cipher) = row.try_get::<Vec<u8>, _>("body_cipher") {
-                String::from_utf8(cipher)
-                    .ok()
-                    .map(|text| {
-                        let preview = text.chars().take(50).collect::<String>();
-                        if text.len() > 50 {
-                            format!("{}...", preview.trim())
-                        } else {
-                            preview.trim().to_string()
-                        }
-                    })
-            } else {
-                None
-            };
-            
-            EntryListItem {
-                id: row.try_get("id").unwrap_or_default(),
-                created_at: row.try_get("created_at").unwrap_or_default(),
-                updated_at: row.try_get("updated_at").unwrap_or_default(),
-                body_preview,
-                mood: row.try_get("mood").ok(),
-                tags: tags_val,
-            }
-        })
-        .collect();
+    // Get body preview, cut on a word boundary
+    let body_preview = if let Ok(cipher) = row.try_get::<Vec<u8>, _>("body_cipher") {
+        crate::vault::decrypt_to_string(&cipher)
+            .ok()
+            .map(|text| preview_text(&text, preview_length))
+    } else {
+        None
+    };
     
-    Ok(items)
+    EntryListItem {
+        id: row.try_get("id").unwrap_or_default(),
+        created_at: row.try_get("created_at").unwrap_or_default(),
+        updated_at: row.try_get("updated_at").unwrap_or_default(),
+        body_preview,
+        mood: row.try_get("mood").ok(),
+        tags: tags_val,
+        pinned: row.try_get::<i64, _>("pinned").unwrap_or(0) != 0,
+        summary: row.try_get("summary").ok().flatten(),
+    }
 }
 
 pub async fn get_entry_body(pool: &Pool<Sqlite>, entry_id: &str) -> Result<String> {
@@ -294,31 +937,1599 @@ pub async fn get_entry_body(pool: &Pool<Sqlite>, entry_id: &str) -> Result<Strin
     let cipher: Vec<u8> = row.try_get("body_cipher")
         .map_err(|e| anyhow::anyhow!("row: {}", e))?;
     
-    let text = String::from_utf8(cipher)
-        .map_err(|e| anyhow::anyhow!("utf8: {}", e))?;
+    let text = crate::vault::decrypt_to_string(&cipher)
+        .map_err(|e| anyhow::anyhow!("{}", e))?;
     
     Ok(text)
 }
 
-pub async fn delete_entry(pool: &Pool<Sqlite>, id: &str) -> Result<(), String> {
-    // Remove dependent rows first to maintain integrity
-    let _ = sqlx::query(r#"DELETE FROM panels WHERE entry_id = ?1"#)
+pub async fn set_entry_mood(pool: &Pool<Sqlite>, id: &str, mood: Option<&str>) -> Result<(), String> {
+    sqlx::query(r#"UPDATE entries SET mood = ?1, updated_at = ?2 WHERE id = ?3"#)
+        .bind(mood)
+        .bind(now_iso())
         .bind(id)
         .execute(pool)
         .await
         .map_err(|e| e.to_string())?;
+    Ok(())
+}
 
-    let _ = sqlx::query(r#"DELETE FROM storyboards WHERE entry_id = ?1"#)
+pub async fn set_entry_summary(pool: &Pool<Sqlite>, id: &str, summary: Option<&str>) -> Result<(), String> {
+    sqlx::query(r#"UPDATE entries SET summary = ?1, updated_at = ?2 WHERE id = ?3"#)
+        .bind(summary)
+        .bind(now_iso())
         .bind(id)
         .execute(pool)
         .await
         .map_err(|e| e.to_string())?;
+    Ok(())
+}
 
-    let _ = sqlx::query(r#"DELETE FROM entries WHERE id = ?1"#)
+/// Flip the entry's pinned flag and return the new state.
+pub async fn toggle_pin(pool: &Pool<Sqlite>, id: &str) -> Result<bool, String> {
+    let row = sqlx::query(
+        r#"UPDATE entries SET pinned = CASE WHEN pinned = 0 THEN 1 ELSE 0 END, updated_at = ?1
+           WHERE id = ?2 RETURNING pinned"#
+    )
+    .bind(now_iso())
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| "entry not found".to_string())?;
+    Ok(row.try_get::<i64, _>("pinned").unwrap_or(0) != 0)
+}
+
+/// Move an entry in or out of the archive. Errors if the entry doesn't exist.
+pub async fn set_archived(pool: &Pool<Sqlite>, id: &str, archived: bool) -> Result<(), String> {
+    let res = sqlx::query(r#"UPDATE entries SET archived = ?1, updated_at = ?2 WHERE id = ?3"#)
+        .bind(archived as i64)
+        .bind(now_iso())
         .bind(id)
         .execute(pool)
         .await
         .map_err(|e| e.to_string())?;
+    if res.rows_affected() == 0 {
+        return Err("entry not found".to_string());
+    }
+    Ok(())
+}
 
+/// Set (or clear with `None`/blank) the entry's preferred comic style.
+pub async fn set_preferred_style(pool: &Pool<Sqlite>, id: &str, style: Option<&str>) -> Result<(), String> {
+    let style = style.map(str::trim).filter(|s| !s.is_empty());
+    let res = sqlx::query(r#"UPDATE entries SET preferred_style = ?1, updated_at = ?2 WHERE id = ?3"#)
+        .bind(style)
+        .bind(now_iso())
+        .bind(id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    if res.rows_affected() == 0 {
+        return Err("entry not found".to_string());
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+pub async fn get_preferred_style(pool: &Pool<Sqlite>, id: &str) -> Result<Option<String>, String> {
+    sqlx::query_scalar::<_, Option<String>>(r#"SELECT preferred_style FROM entries WHERE id = ?1"#)
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map(|v| v.flatten())
+        .map_err(|e| e.to_string())
+}
+
+pub async fn delete_entry(pool: &Pool<Sqlite>, id: &str) -> Result<(), String> {
+    // One transaction, so a job status written meanwhile either lands before the cleanup
+    // or finds the entry gone (`upsert_comic_job` skips rows for missing entries)
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+
+    // Remove dependent rows first to maintain integrity
+    let _ = sqlx::query(r#"DELETE FROM panels WHERE entry_id = ?1"#)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Assets record their entry in meta; older rows only by living under images/<entry_id>/
+    let _ = sqlx::query(
+        r#"DELETE FROM assets
+           WHERE json_valid(meta) AND json_extract(meta, '$.entry_id') = ?1
+              OR replace(path, '\', '/') LIKE '%/images/' || ?1 || '/%'"#
+    )
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let _ = sqlx::query(r#"DELETE FROM comic_jobs WHERE entry_id = ?1"#)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let _ = sqlx::query(r#"DELETE FROM storyboards WHERE entry_id = ?1"#)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let _ = sqlx::query(r#"DELETE FROM entries WHERE id = ?1"#)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoryboardRecord {
+    pub id: String,
+    pub entry_id: String,
+    pub model: String,
+    pub created_at: String,
+    pub data: Option<serde_json::Value>,
+}
+
+pub async fn insert_storyboard(
+    pool: &Pool<Sqlite>,
+    entry_id: &str,
+    data: &serde_json::Value,
+    model: &str,
+) -> Result<String, String> {
+    let id = Uuid::new_v4().to_string();
+    let json_bytes = serde_json::to_vec(data).map_err(|e| e.to_string())?;
+    sqlx::query(
+        r#"INSERT INTO storyboards (id, entry_id, json_cipher, model, created_at) VALUES (?1, ?2, ?3, ?4, ?5)"#
+    )
+    .bind(&id)
+    .bind(entry_id)
+    .bind(&json_bytes)
+    .bind(model)
+    .bind(now_iso())
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+/// Insert or overwrite the storyboard row `id`; lets a partial storyboard be saved while
+/// it streams and then replaced by the final text.
+pub async fn upsert_storyboard(
+    pool: &Pool<Sqlite>,
+    id: &str,
+    entry_id: &str,
+    data: &serde_json::Value,
+    model: &str,
+) -> Result<(), String> {
+    let json_bytes = serde_json::to_vec(data).map_err(|e| e.to_string())?;
+    sqlx::query(
+        r#"INSERT INTO storyboards (id, entry_id, json_cipher, model, created_at) VALUES (?1, ?2, ?3, ?4, ?5)
+           ON CONFLICT(id) DO UPDATE SET json_cipher = excluded.json_cipher, model = excluded.model"#
+    )
+    .bind(id)
+    .bind(entry_id)
+    .bind(&json_bytes)
+    .bind(model)
+    .bind(now_iso())
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Write one `panels` row per parsed panel, linked to its storyboard and job via `meta`;
+/// returns the new row ids in panel order.
+/// Prompt and dialogue are stored as UTF-8 text like the storyboard JSON.
+pub async fn insert_panels(
+    pool: &Pool<Sqlite>,
+    storyboard_id: &str,
+    entry_id: &str,
+    job_id: &str,
+    style: &str,
+    panels: &[ParsedPanel],
+) -> Result<Vec<String>, String> {
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    let mut ids = Vec::with_capacity(panels.len());
+    for panel in panels {
+        let id = Uuid::new_v4().to_string();
+        let meta = serde_json::json!({
+            "storyboard_id": storyboard_id,
+            "job_id": job_id,
+            "characters": panel.characters,
+        });
+        sqlx::query(
+            r#"INSERT INTO panels (id, entry_id, idx, prompt_cipher, dialogue_cipher, style, meta)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)"#
+        )
+        .bind(&id)
+        .bind(entry_id)
+        .bind(panel.idx as i64)
+        .bind(panel.description.as_bytes())
+        .bind(panel.dialogue_text().map(String::into_bytes))
+        .bind(style)
+        .bind(meta.to_string())
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        ids.push(id);
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(ids)
+}
+
+pub async fn set_panel_image(pool: &Pool<Sqlite>, panel_id: &str, image_path: &str) -> Result<(), String> {
+    sqlx::query("UPDATE panels SET image_path = ?1 WHERE id = ?2")
+        .bind(image_path)
+        .bind(panel_id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetRecord {
+    pub id: String,
+    pub kind: String,
+    pub path: String,
+    pub meta: Option<serde_json::Value>,
+}
+
+fn asset_from_row(row: &SqliteRow) -> AssetRecord {
+    AssetRecord {
+        id: row.try_get("id").unwrap_or_default(),
+        kind: row.try_get("kind").unwrap_or_default(),
+        path: row.try_get("path").unwrap_or_default(),
+        meta: row
+            .try_get::<Option<String>, _>("meta")
+            .ok()
+            .flatten()
+            .and_then(|m| serde_json::from_str(&m).ok()),
+    }
+}
+
+/// Record a file in `assets`. `meta.entry_id` ties it to an entry so it's removed with it.
+/// A path that's already registered keeps its id and gets the new kind and meta.
+pub async fn register_asset(pool: &Pool<Sqlite>, kind: &str, path: &str, meta: &serde_json::Value) -> Result<String, String> {
+    let existing: Option<String> = sqlx::query_scalar("SELECT id FROM assets WHERE path = ?1 LIMIT 1")
+        .bind(path)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(id) = existing {
+        sqlx::query("UPDATE assets SET kind = ?2, meta = ?3 WHERE id = ?1")
+            .bind(&id)
+            .bind(kind)
+            .bind(meta.to_string())
+            .execute(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        return Ok(id);
+    }
+    let id = Uuid::new_v4().to_string();
+    sqlx::query("INSERT INTO assets (id, kind, path, meta) VALUES (?1, ?2, ?3, ?4)")
+        .bind(&id)
+        .bind(kind)
+        .bind(path)
+        .bind(meta.to_string())
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+/// Every asset, or only those of `kind`, newest first.
+pub async fn list_assets(pool: &Pool<Sqlite>, kind: Option<&str>) -> Result<Vec<AssetRecord>, String> {
+    let rows = sqlx::query(
+        r#"SELECT id, kind, path, meta FROM assets
+           WHERE ?1 IS NULL OR kind = ?1
+           ORDER BY CASE WHEN json_valid(meta) THEN json_extract(meta, '$.created_at') END DESC, id ASC"#
+    )
+    .bind(kind)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(rows.iter().map(asset_from_row).collect())
+}
+
+/// The entry's assets of `kind`, newest first.
+pub async fn list_entry_assets(pool: &Pool<Sqlite>, entry_id: &str, kind: &str) -> Result<Vec<AssetRecord>, String> {
+    let rows = sqlx::query(
+        r#"SELECT id, kind, path, meta FROM assets
+           WHERE kind = ?2 AND json_valid(meta) AND json_extract(meta, '$.entry_id') = ?1
+           ORDER BY json_extract(meta, '$.created_at') DESC, id ASC"#
+    )
+    .bind(entry_id)
+    .bind(kind)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(rows.iter().map(asset_from_row).collect())
+}
+
+/// Remove the asset row (not the file) and return what it was.
+pub async fn delete_asset(pool: &Pool<Sqlite>, id: &str) -> Result<AssetRecord, String> {
+    let row = sqlx::query("DELETE FROM assets WHERE id = ?1 RETURNING id, kind, path, meta")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("asset not found: {}", id))?;
+    Ok(asset_from_row(&row))
+}
+
+/// Assets whose image no panel or comic job points at. A thumbnail counts as referenced
+/// while the image it was made from (`meta.source`) is.
+pub async fn unreferenced_assets(pool: &Pool<Sqlite>) -> Result<Vec<AssetRecord>, String> {
+    let rows = sqlx::query(
+        r#"WITH refs(path) AS (
+               SELECT image_path FROM panels WHERE image_path IS NOT NULL
+               UNION SELECT result_image_path FROM comic_jobs WHERE result_image_path IS NOT NULL
+               UNION SELECT e.value FROM comic_jobs j, json_each(j.panel_image_paths) e
+                     WHERE json_valid(j.panel_image_paths)
+           )
+           SELECT id, kind, path, meta FROM assets
+           WHERE (CASE
+                      WHEN kind = 'thumbnail' AND json_valid(meta) THEN json_extract(meta, '$.source')
+                      ELSE path
+                  END) NOT IN (SELECT path FROM refs)"#
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(rows.iter().map(asset_from_row).collect())
+}
+
+/// A named style with extra prompt text, usable in place of a free-form comic style.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StylePreset {
+    pub id: String,
+    pub name: String,
+    /// Appended to the style line of image prompts
+    pub prompt_fragment: String,
+    /// Replaces the default layout guideline for combined (single-image) comics
+    pub layout_hint: Option<String>,
+    /// Shipped with the app rather than created by the user
+    pub builtin: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StylePresetInput {
+    pub name: String,
+    pub prompt_fragment: String,
+    pub layout_hint: Option<String>,
+}
+
+impl StylePresetInput {
+    // Trimmed copy; name and fragment are required
+    fn validated(&self) -> Result<(String, String, Option<String>), String> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err("preset name must not be empty".to_string());
+        }
+        let fragment = self.prompt_fragment.trim();
+        if fragment.is_empty() {
+            return Err("preset prompt_fragment must not be empty".to_string());
+        }
+        let layout = self.layout_hint.as_deref().map(str::trim).filter(|l| !l.is_empty()).map(str::to_string);
+        Ok((name.to_string(), fragment.to_string(), layout))
+    }
+}
+
+fn style_preset_from_row(row: &SqliteRow) -> StylePreset {
+    StylePreset {
+        id: row.try_get("id").unwrap_or_default(),
+        name: row.try_get("name").unwrap_or_default(),
+        prompt_fragment: row.try_get("prompt_fragment").unwrap_or_default(),
+        layout_hint: row.try_get("layout_hint").ok().flatten(),
+        builtin: row.try_get::<i64, _>("builtin").unwrap_or(0) != 0,
+    }
+}
+
+// Unique-constraint failures read better as a duplicate name
+fn preset_write_error(e: sqlx::Error, name: &str) -> String {
+    match &e {
+        sqlx::Error::Database(db) if db.message().contains("UNIQUE") => format!("a style preset named '{}' already exists", name),
+        _ => e.to_string(),
+    }
+}
+
+pub async fn list_style_presets(pool: &Pool<Sqlite>) -> Result<Vec<StylePreset>, String> {
+    let rows = sqlx::query(
+        "SELECT id, name, prompt_fragment, layout_hint, builtin FROM style_presets ORDER BY builtin DESC, name COLLATE NOCASE"
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(rows.iter().map(style_preset_from_row).collect())
+}
+
+pub async fn get_style_preset(pool: &Pool<Sqlite>, id: &str) -> Result<StylePreset, String> {
+    sqlx::query("SELECT id, name, prompt_fragment, layout_hint, builtin FROM style_presets WHERE id = ?1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .map(|row| style_preset_from_row(&row))
+        .ok_or_else(|| format!("style preset not found: {}", id))
+}
+
+pub async fn create_style_preset(pool: &Pool<Sqlite>, input: &StylePresetInput) -> Result<StylePreset, String> {
+    let (name, fragment, layout) = input.validated()?;
+    let id = Uuid::new_v4().to_string();
+    sqlx::query(
+        r#"INSERT INTO style_presets (id, name, prompt_fragment, layout_hint, builtin, created_at)
+           VALUES (?1, ?2, ?3, ?4, 0, ?5)"#
+    )
+    .bind(&id)
+    .bind(&name)
+    .bind(&fragment)
+    .bind(&layout)
+    .bind(now_iso())
+    .execute(pool)
+    .await
+    .map_err(|e| preset_write_error(e, &name))?;
+    get_style_preset(pool, &id).await
+}
+
+pub async fn update_style_preset(pool: &Pool<Sqlite>, id: &str, input: &StylePresetInput) -> Result<StylePreset, String> {
+    let (name, fragment, layout) = input.validated()?;
+    let updated = sqlx::query(
+        "UPDATE style_presets SET name = ?2, prompt_fragment = ?3, layout_hint = ?4 WHERE id = ?1"
+    )
+    .bind(id)
+    .bind(&name)
+    .bind(&fragment)
+    .bind(&layout)
+    .execute(pool)
+    .await
+    .map_err(|e| preset_write_error(e, &name))?;
+    if updated.rows_affected() == 0 {
+        return Err(format!("style preset not found: {}", id));
+    }
+    get_style_preset(pool, id).await
+}
+
+pub async fn delete_style_preset(pool: &Pool<Sqlite>, id: &str) -> Result<(), String> {
+    let deleted = sqlx::query("DELETE FROM style_presets WHERE id = ?1")
+        .bind(id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    if deleted.rows_affected() == 0 {
+        return Err(format!("style preset not found: {}", id));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PanelRecord {
+    pub id: String,
+    pub idx: i64,
+    pub prompt: Option<String>,
+    pub dialogue: Option<String>,
+    pub style: Option<String>,
+    pub image_path: Option<String>,
+    pub meta: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoryboardDetail {
+    pub id: String,
+    pub entry_id: String,
+    pub model: String,
+    pub created_at: String,
+    pub text: Option<String>,
+    pub panels: Vec<PanelRecord>,
+}
+
+fn cipher_text(row: &SqliteRow, column: &str) -> Result<Option<String>, String> {
+    let bytes: Option<Vec<u8>> = row.try_get(column).map_err(|e| e.to_string())?;
+    bytes.map(|b| crate::vault::decrypt_to_string(&b)).transpose()
+}
+
+/// The entry's newest storyboard with its panels in order, or `None` if it has none.
+pub async fn latest_storyboard(pool: &Pool<Sqlite>, entry_id: &str) -> Result<Option<StoryboardDetail>, String> {
+    let Some(row) = sqlx::query(
+        r#"SELECT id, entry_id, json_cipher, model, created_at FROM storyboards
+           WHERE entry_id = ?1 ORDER BY created_at DESC LIMIT 1"#
+    )
+    .bind(entry_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())? else {
+        return Ok(None);
+    };
+    let id: String = row.try_get("id").map_err(|e| e.to_string())?;
+    let text = row
+        .try_get::<Vec<u8>, _>("json_cipher")
+        .ok()
+        .and_then(|b| serde_json::from_slice::<serde_json::Value>(&b).ok())
+        .and_then(|v| v.get("text").and_then(|t| t.as_str()).map(str::to_string));
+
+    let panel_rows = sqlx::query(
+        r#"SELECT id, idx, prompt_cipher, dialogue_cipher, style, image_path, meta FROM panels
+           WHERE entry_id = ?1 AND json_extract(meta, '$.storyboard_id') = ?2
+           ORDER BY idx"#
+    )
+    .bind(entry_id)
+    .bind(&id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    let panels = panel_rows
+        .iter()
+        .map(|r| {
+            Ok(PanelRecord {
+                id: r.try_get("id").map_err(|e| e.to_string())?,
+                idx: r.try_get("idx").map_err(|e| e.to_string())?,
+                prompt: cipher_text(r, "prompt_cipher")?,
+                dialogue: cipher_text(r, "dialogue_cipher")?,
+                style: r.try_get("style").map_err(|e| e.to_string())?,
+                image_path: r.try_get("image_path").map_err(|e| e.to_string())?,
+                meta: r
+                    .try_get::<Option<String>, _>("meta")
+                    .ok()
+                    .flatten()
+                    .and_then(|m| serde_json::from_str(&m).ok()),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(Some(StoryboardDetail {
+        id,
+        entry_id: row.try_get("entry_id").map_err(|e| e.to_string())?,
+        model: row.try_get("model").map_err(|e| e.to_string())?,
+        created_at: row.try_get("created_at").map_err(|e| e.to_string())?,
+        text,
+        panels,
+    }))
+}
+
+pub async fn list_storyboards(pool: &Pool<Sqlite>, entry_id: &str) -> Result<Vec<StoryboardRecord>, String> {
+    let rows = sqlx::query(
+        r#"SELECT id, entry_id, json_cipher, model, created_at FROM storyboards WHERE entry_id = ?1 ORDER BY created_at DESC"#
+    )
+    .bind(entry_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let items = rows
+        .into_iter()
+        .map(|row| {
+            let data = row
+                .try_get::<Vec<u8>, _>("json_cipher")
+                .ok()
+                .and_then(|b| serde_json::from_slice::<serde_json::Value>(&b).ok());
+            StoryboardRecord {
+                id: row.try_get("id").unwrap_or_default(),
+                entry_id: row.try_get("entry_id").unwrap_or_default(),
+                model: row.try_get("model").unwrap_or_default(),
+                created_at: row.try_get("created_at").unwrap_or_default(),
+                data,
+            }
+        })
+        .collect();
+
+    Ok(items)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StageTimingStats {
+    pub stage: String,
+    pub provider: Option<String>,
+    pub samples: i64,
+    pub average_ms: Option<f64>,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn record_job_metric(
+    pool: &Pool<Sqlite>,
+    job_id: &str,
+    entry_id: &str,
+    stage: &str,
+    provider: Option<&str>,
+    model: Option<&str>,
+    style: &str,
+    duration_ms: i64,
+) -> Result<(), String> {
+    sqlx::query(
+        r#"INSERT INTO job_metrics (job_id, entry_id, stage, provider, model, style, duration_ms, created_at)
+           VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"#
+    )
+    .bind(job_id)
+    .bind(entry_id)
+    .bind(stage)
+    .bind(provider)
+    .bind(model)
+    .bind(style)
+    .bind(duration_ms)
+    .bind(now_iso())
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// One item of the `recent_activity` feed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityItem {
+    /// entry_created | entry_updated | comic_started | comic_completed | comic_failed
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub entry_id: String,
+    pub job_id: Option<String>,
+    pub at: String,
+    pub detail: Option<String>,
+}
+
+/// Write the job's current status. Stage and `meta` are stored as JSON. Rows for entries that no longer exist are not (re)created, and a job
+/// that already ended keeps its final status.
+pub async fn upsert_comic_job(pool: &Pool<Sqlite>, status: &ComicJobStatus) -> Result<(), String> {
+    let stage = serde_json::to_string(&status.stage).map_err(|e| e.to_string())?;
+    let panel_image_paths = (!status.panel_image_paths.is_empty())
+        .then(|| serde_json::to_string(&status.panel_image_paths))
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    let meta = status.meta.as_ref().map(|m| m.to_string());
+    sqlx::query(
+        r#"INSERT INTO comic_jobs (job_id, entry_id, style, stage, updated_at, result_image_path, storyboard_text, panel_image_paths, meta)
+           SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9
+           WHERE EXISTS (SELECT 1 FROM entries WHERE id = ?2)
+           ON CONFLICT(job_id) DO UPDATE SET
+               stage = excluded.stage,
+               updated_at = excluded.updated_at,
+               result_image_path = excluded.result_image_path,
+               storyboard_text = excluded.storyboard_text,
+               panel_image_paths = excluded.panel_image_paths,
+               meta = excluded.meta
+           WHERE json_extract(comic_jobs.stage, '$.stage') NOT IN ('done', 'storyboard_ready', 'failed')"#
+    )
+    .bind(&status.job_id)
+    .bind(&status.entry_id)
+    .bind(&status.style)
+    .bind(stage)
+    .bind(&status.updated_at)
+    .bind(&status.result_image_path)
+    .bind(&status.storyboard_text)
+    .bind(panel_image_paths)
+    .bind(meta)
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+const COMIC_JOB_COLUMNS: &str = "job_id, entry_id, style, stage, updated_at, result_image_path, storyboard_text, panel_image_paths, meta";
+
+fn comic_job_from_row(row: &SqliteRow) -> Result<ComicJobStatus, String> {
+    let stage: String = row.try_get("stage").map_err(|e| e.to_string())?;
+    Ok(ComicJobStatus {
+        job_id: row.try_get("job_id").map_err(|e| e.to_string())?,
+        entry_id: row.try_get("entry_id").map_err(|e| e.to_string())?,
+        style: row.try_get("style").map_err(|e| e.to_string())?,
+        stage: serde_json::from_str(&stage).map_err(|e| format!("bad stage '{}': {}", stage, e))?,
+        updated_at: row.try_get("updated_at").map_err(|e| e.to_string())?,
+        result_image_path: row.try_get("result_image_path").map_err(|e| e.to_string())?,
+        storyboard_text: row.try_get("storyboard_text").map_err(|e| e.to_string())?,
+        meta: row
+            .try_get::<Option<String>, _>("meta")
+            .map_err(|e| e.to_string())?
+            .and_then(|m| serde_json::from_str(&m).ok()),
+        panel_image_paths: row
+            .try_get::<Option<String>, _>("panel_image_paths")
+            .map_err(|e| e.to_string())?
+            .and_then(|p| serde_json::from_str(&p).ok())
+            .unwrap_or_default(),
+    })
+}
+
+pub async fn get_comic_job(pool: &Pool<Sqlite>, job_id: &str) -> Result<Option<ComicJobStatus>, String> {
+    let row = sqlx::query(&format!("SELECT {} FROM comic_jobs WHERE job_id = ?1", COMIC_JOB_COLUMNS))
+        .bind(job_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    row.as_ref().map(comic_job_from_row).transpose()
+}
+
+/// Record the options a job was started with, so `queued_comic_jobs` can start it again.
+pub async fn set_comic_job_options(pool: &Pool<Sqlite>, job_id: &str, options: &ComicJobOptions) -> Result<(), String> {
+    let options = serde_json::to_string(options).map_err(|e| e.to_string())?;
+    sqlx::query("UPDATE comic_jobs SET options = ?1 WHERE job_id = ?2")
+        .bind(options)
+        .bind(job_id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Replace a job's `meta`, whatever its stage (finished jobs included, unlike `upsert_comic_job`).
+pub async fn set_comic_job_meta(pool: &Pool<Sqlite>, job_id: &str, meta: &serde_json::Value) -> Result<(), String> {
+    sqlx::query("UPDATE comic_jobs SET meta = ?1 WHERE job_id = ?2")
+        .bind(meta.to_string())
+        .bind(job_id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Job history, newest first, optionally for one entry.
+pub async fn list_comic_jobs(
+    pool: &Pool<Sqlite>,
+    entry_id: Option<&str>,
+    limit: i64,
+) -> Result<Vec<ComicJobStatus>, String> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM comic_jobs WHERE ?1 IS NULL OR entry_id = ?1 ORDER BY updated_at DESC LIMIT ?2",
+        COMIC_JOB_COLUMNS
+    ))
+    .bind(entry_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    rows.iter().map(comic_job_from_row).collect()
+}
+
+/// Finished comic images of one entry, oldest first.
+pub async fn entry_comic_image_paths(pool: &Pool<Sqlite>, entry_id: &str) -> Result<Vec<String>, String> {
+    sqlx::query_scalar(
+        r#"SELECT result_image_path FROM comic_jobs
+           WHERE entry_id = ?1 AND result_image_path IS NOT NULL
+           ORDER BY updated_at ASC"#,
+    )
+    .bind(entry_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+// False while `comic_jobs` is missing or outdated, i.e. `create_pool` deferred the
+// migrations that create it; no job can have been left behind in that case
+async fn comic_jobs_current(pool: &Pool<Sqlite>) -> Result<bool, String> {
+    let existing = table_columns(pool, "comic_jobs").await?;
+    Ok(EXPECTED_SCHEMA
+        .iter()
+        .filter(|(t, _)| *t == "comic_jobs")
+        .flat_map(|(_, cols)| cols.iter())
+        .all(|(c, _)| existing.iter().any(|e| e == c)))
+}
+
+/// Fail every job that was running when the app last stopped (nothing is running it
+/// anymore) and return them so they can be shown with a retry option. Jobs still queued
+/// never started, so they are left for `queued_comic_jobs`.
+/// Does nothing while the migrations that create `comic_jobs` are deferred.
+pub async fn recover_interrupted_comic_jobs(pool: &Pool<Sqlite>) -> Result<Vec<ComicJobStatus>, String> {
+    if !comic_jobs_current(pool).await? {
+        tracing::info!("comic_jobs schema not current (migrations deferred); skipping job recovery");
+        return Ok(Vec::new());
+    }
+    let stage = serde_json::to_string(&ComicStage::failed("interrupted by restart"))
+        .map_err(|e| e.to_string())?;
+    let rows = sqlx::query(&format!(
+        r#"UPDATE comic_jobs SET stage = ?1, updated_at = ?2
+           WHERE json_extract(stage, '$.stage') NOT IN ('queued', 'done', 'storyboard_ready', 'failed')
+           RETURNING {}"#,
+        COMIC_JOB_COLUMNS
+    ))
+    .bind(stage)
+    .bind(now_iso())
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    rows.iter().map(comic_job_from_row).collect()
+}
+
+/// Jobs still queued when the app last stopped, oldest first, with the options they were
+/// started with (defaults for jobs saved before options were recorded).
+pub async fn queued_comic_jobs(pool: &Pool<Sqlite>) -> Result<Vec<(ComicJobStatus, ComicJobOptions)>, String> {
+    if !comic_jobs_current(pool).await? {
+        return Ok(Vec::new());
+    }
+    let rows = sqlx::query(&format!(
+        r#"SELECT {}, options FROM comic_jobs
+           WHERE json_extract(stage, '$.stage') = 'queued'
+           ORDER BY updated_at"#,
+        COMIC_JOB_COLUMNS
+    ))
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    rows.iter()
+        .map(|row| {
+            let status = comic_job_from_row(row)?;
+            let options = row
+                .try_get::<Option<String>, _>("options")
+                .map_err(|e| e.to_string())?
+                .and_then(|o| serde_json::from_str(&o).ok())
+                .unwrap_or_default();
+            Ok((status, options))
+        })
+        .collect()
+}
+
+/// Entry creations/updates plus comic jobs, newest first. Completed renders come from
+/// `job_metrics`; started, failed and storyboard-only jobs from `comic_jobs`.
+pub async fn recent_activity(pool: &Pool<Sqlite>, limit: i64) -> Result<Vec<ActivityItem>, String> {
+    let rows = sqlx::query(
+        r#"SELECT kind, entry_id, job_id, at, detail FROM (
+               SELECT 'entry_created' AS kind, id AS entry_id, NULL AS job_id, created_at AS at, NULL AS detail
+               FROM entries
+               UNION ALL
+               SELECT 'entry_updated', id, NULL, updated_at, NULL
+               FROM entries WHERE updated_at > created_at
+               UNION ALL
+               SELECT 'comic_completed', entry_id, job_id, created_at, provider
+               FROM job_metrics WHERE stage = 'rendering'
+               UNION ALL
+               SELECT CASE json_extract(stage, '$.stage')
+                          WHEN 'failed' THEN 'comic_failed'
+                          WHEN 'storyboard_ready' THEN 'comic_completed'
+                          ELSE 'comic_started'
+                      END,
+                      entry_id, job_id, updated_at,
+                      CASE json_extract(stage, '$.stage')
+                          WHEN 'failed' THEN json_extract(stage, '$.error')
+                          WHEN 'storyboard_ready' THEN 'storyboard only'
+                      END
+               FROM comic_jobs WHERE json_extract(stage, '$.stage') != 'done'
+           )
+           ORDER BY at DESC
+           LIMIT ?1"#
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| ActivityItem {
+            kind: row.try_get("kind").unwrap_or_default(),
+            entry_id: row.try_get("entry_id").unwrap_or_default(),
+            job_id: row.try_get("job_id").ok().flatten(),
+            at: row.try_get("at").unwrap_or_default(),
+            detail: row.try_get("detail").ok().flatten(),
+        })
+        .collect())
+}
+
+/// Average duration of `stage`, optionally restricted to one provider.
+pub async fn average_stage_duration(
+    pool: &Pool<Sqlite>,
+    stage: &str,
+    provider: Option<&str>,
+) -> Result<StageTimingStats, String> {
+    let row = sqlx::query(
+        r#"SELECT COUNT(*) AS samples, AVG(duration_ms) AS average_ms FROM job_metrics
+           WHERE stage = ?1 AND (?2 IS NULL OR provider = ?2)"#
+    )
+    .bind(stage)
+    .bind(provider)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(StageTimingStats {
+        stage: stage.to_string(),
+        provider: provider.map(|p| p.to_string()),
+        samples: row.try_get("samples").unwrap_or(0),
+        average_ms: row.try_get("average_ms").ok(),
+    })
+}
+
+async fn table_columns(pool: &Pool<Sqlite>, table: &str) -> Result<Vec<String>, String> {
+    let rows = sqlx::query(&format!("PRAGMA table_info({})", table))
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(rows
+        .iter()
+        .filter_map(|row| row.try_get::<String, _>("name").ok())
+        .collect())
+}
+
+/// Copy the database to `dest` (via `VACUUM INTO`). With `decrypt_bodies`, entry bodies are
+/// stored as plaintext so the copy can be restored on a machine with a different vault key;
+/// otherwise they stay encrypted and only restore where this vault key is available.
+pub async fn snapshot_for_transfer(pool: &Pool<Sqlite>, dest: &Path, decrypt_bodies: bool) -> Result<(), String> {
+    let target = dest.display().to_string().replace('\'', "''");
+    sqlx::query(&format!("VACUUM INTO '{}'", target))
+        .execute(pool)
+        .await
+        .map_err(|e| format!("snapshot database: {}", e))?;
+    if !decrypt_bodies {
+        return Ok(());
+    }
+    let copy = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(SqliteConnectOptions::new().filename(dest))
+        .await
+        .map_err(|e| e.to_string())?;
+    let rows = sqlx::query("SELECT id, body_cipher FROM entries")
+        .fetch_all(&copy)
+        .await
+        .map_err(|e| e.to_string())?;
+    for row in rows {
+        let id: String = row.try_get("id").map_err(|e| e.to_string())?;
+        let body: Vec<u8> = row.try_get("body_cipher").map_err(|e| e.to_string())?;
+        let plaintext = crate::vault::decrypt(&body).map_err(|e| format!("entry {}: {}", id, e))?;
+        sqlx::query("UPDATE entries SET body_cipher = ?1 WHERE id = ?2")
+            .bind(plaintext)
+            .bind(&id)
+            .execute(&copy)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    copy.close().await;
+    Ok(())
+}
+
+/// Check that `path` is a toonana database this build can restore: it must have every
+/// table in `EXPECTED_SCHEMA` that holds user data, and a schema version no newer than
+/// the last of `MIGRATIONS`. Missing columns are fine (older versions); restore copies the
+/// columns both sides have.
+pub async fn validate_restore_source(path: &Path) -> Result<(), String> {
+    let src = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(SqliteConnectOptions::new().filename(path).read_only(true))
+        .await
+        .map_err(|e| format!("backup database cannot be opened: {}", e))?;
+    let result = async {
+        for table in ["entries", "storyboards", "panels"] {
+            if table_columns(&src, table).await?.is_empty() {
+                return Err(format!("backup database has no {} table", table));
+            }
+        }
+        let latest = MIGRATIONS.last().map(|m| m.version).unwrap_or(0);
+        if let Some(version) = schema_version(&src).await?.filter(|v| *v > latest) {
+            return Err(format!(
+                "backup database is at schema version {}, newer than this version of toonana supports ({}); update the app first",
+                version, latest
+            ));
+        }
+        Ok(())
+    }
+    .await;
+    src.close().await;
+    result
+}
+
+/// Replace every table's rows with those of the database at `src`, in one transaction.
+/// Plaintext bodies are encrypted with this machine's vault key, and stored paths that
+/// start with `old_data_dir` are moved under `new_data_dir`. Returns restored entries.
+pub async fn restore_from_file(
+    pool: &Pool<Sqlite>,
+    src: &Path,
+    old_data_dir: Option<&str>,
+    new_data_dir: &str,
+) -> Result<u64, String> {
+    let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+    sqlx::query("ATTACH DATABASE ?1 AS restore_src")
+        .bind(src.display().to_string())
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("attach backup: {}", e))?;
+
+    let result = async {
+        sqlx::query("BEGIN IMMEDIATE").execute(&mut *conn).await.map_err(|e| e.to_string())?;
+        // Tables are emptied and refilled in schema order; check references at commit instead
+        sqlx::query("PRAGMA defer_foreign_keys = ON").execute(&mut *conn).await.map_err(|e| e.to_string())?;
+        let copied = async {
+            // The schema version describes this database's tables, not the backup's
+            for (table, _) in EXPECTED_SCHEMA.iter().filter(|(t, _)| *t != "schema_meta") {
+                let main_cols: Vec<String> = sqlx::query(&format!("PRAGMA main.table_info({})", table))
+                    .fetch_all(&mut *conn)
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .iter()
+                    .filter_map(|r| r.try_get::<String, _>("name").ok())
+                    .collect();
+                let src_cols: Vec<String> = sqlx::query(&format!("PRAGMA restore_src.table_info({})", table))
+                    .fetch_all(&mut *conn)
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .iter()
+                    .filter_map(|r| r.try_get::<String, _>("name").ok())
+                    .collect();
+                sqlx::query(&format!("DELETE FROM main.{}", table))
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                // A table the backup predates simply ends up empty
+                let cols: Vec<&String> = main_cols.iter().filter(|c| src_cols.contains(c)).collect();
+                if cols.is_empty() {
+                    continue;
+                }
+                let list = cols.iter().map(|c| format!("\"{}\"", c)).collect::<Vec<_>>().join(", ");
+                sqlx::query(&format!(
+                    "INSERT INTO main.{t} ({list}) SELECT {list} FROM restore_src.{t}",
+                    t = table,
+                    list = list
+                ))
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| format!("restore {}: {}", table, e))?;
+            }
+
+            let rows = sqlx::query("SELECT id, body_cipher FROM main.entries")
+                .fetch_all(&mut *conn)
+                .await
+                .map_err(|e| e.to_string())?;
+            let entries = rows.len() as u64;
+            for row in rows {
+                let id: String = row.try_get("id").map_err(|e| e.to_string())?;
+                let body: Vec<u8> = row.try_get("body_cipher").map_err(|e| e.to_string())?;
+                if crate::vault::is_encrypted(&body) {
+                    // Only restorable if this machine holds the key it was encrypted with
+                    crate::vault::decrypt(&body).map_err(|e| format!("entry {}: {}", id, e))?;
+                    continue;
+                }
+                let sealed = crate::vault::encrypt(&body)?;
+                sqlx::query("UPDATE main.entries SET body_cipher = ?1 WHERE id = ?2")
+                    .bind(sealed)
+                    .bind(&id)
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+
+            if let Some(old) = old_data_dir.filter(|old| *old != new_data_dir) {
+                for (table, column) in [
+                    ("panels", "image_path"),
+                    ("assets", "path"),
+                    ("comic_jobs", "result_image_path"),
+                    ("comic_jobs", "panel_image_paths"),
+                ] {
+                    sqlx::query(&format!(
+                        "UPDATE main.{t} SET {c} = replace({c}, ?1, ?2) WHERE instr({c}, ?1) > 0",
+                        t = table,
+                        c = column
+                    ))
+                    .bind(old)
+                    .bind(new_data_dir)
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                }
+            }
+            Ok::<u64, String>(entries)
+        }
+        .await;
+        match copied {
+            Ok(entries) => {
+                sqlx::query("COMMIT").execute(&mut *conn).await.map_err(|e| e.to_string())?;
+                Ok(entries)
+            }
+            Err(e) => {
+                let _ = sqlx::query("ROLLBACK").execute(&mut *conn).await;
+                Err(e)
+            }
+        }
+    }
+    .await;
+    let _ = sqlx::query("DETACH DATABASE restore_src").execute(&mut *conn).await;
+    result
+}
+
+/// Compare the live schema with `EXPECTED_SCHEMA`. With `heal`, missing tables are recreated
+/// via `init_db` and missing columns are added with idempotent `ALTER TABLE ADD COLUMN`.
+pub async fn verify_schema(pool: &Pool<Sqlite>, heal: bool) -> Result<SchemaReport, String> {
+    let mut issues = Vec::new();
+
+    let mut missing_tables = Vec::new();
+    for (table, _) in EXPECTED_SCHEMA {
+        if table_columns(pool, table).await?.is_empty() {
+            missing_tables.push(*table);
+        }
+    }
+    let tables_healed = if heal && !missing_tables.is_empty() {
+        init_db(pool).await.map_err(|e| format!("recreate tables: {}", e))?;
+        true
+    } else {
+        false
+    };
+    for table in &missing_tables {
+        issues.push(SchemaIssue {
+            table: table.to_string(),
+            column: None,
+            problem: "table missing".to_string(),
+            healed: tables_healed,
+        });
+    }
+
+    for (table, columns) in EXPECTED_SCHEMA {
+        let existing = table_columns(pool, table).await?;
+        if existing.is_empty() {
+            continue;
+        }
+        for (column, col_type) in columns.iter() {
+            if existing.iter().any(|c| c == column) {
+                continue;
+            }
+            let healed = if heal {
+                sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, col_type))
+                    .execute(pool)
+                    .await
+                    .is_ok()
+            } else {
+                false
+            };
+            issues.push(SchemaIssue {
+                table: table.to_string(),
+                column: Some(column.to_string()),
+                problem: "column missing".to_string(),
+                healed,
+            });
+        }
+    }
+
+    Ok(SchemaReport {
+        ok: issues.iter().all(|i| i.healed),
+        issues,
+    })
+}
+
+/// Entries whose `updated_at` is strictly after `since` (RFC3339), oldest change first.
+pub async fn list_entries_changed_since(pool: &Pool<Sqlite>, since: &str) -> Result<Vec<Entry>, String> {
+    use time::format_description::well_known::Rfc3339;
+    let parsed = OffsetDateTime::parse(since.trim(), &Rfc3339)
+        .map_err(|e| format!("invalid RFC3339 timestamp '{}': {}", since, e))?
+        .to_offset(time::UtcOffset::UTC);
+    let normalized = parsed.format(&Rfc3339).map_err(|e| e.to_string())?;
+    // A string lower bound keeps the updated_at index usable; julianday() then compares
+    // exactly regardless of how many fractional digits each timestamp carries. The bound is
+    // a whole second earlier because "...:05.5Z" sorts before "...:05Z" ('.' < 'Z'), so a
+    // bound in the same second would drop rows with fractional seconds.
+    let lower_bound = (parsed.replace_nanosecond(0).map_err(|e| e.to_string())? - time::Duration::SECOND)
+        .format(&Rfc3339)
+        .map_err(|e| e.to_string())?;
+
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM entries WHERE updated_at >= ?1 AND julianday(updated_at) > julianday(?2) ORDER BY updated_at ASC",
+        ENTRY_COLUMNS
+    ))
+    .bind(&lower_bound)
+    .bind(&normalized)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    rows.iter().map(entry_from_row).collect()
+}
+
+/// Every entry id, archived ones included, oldest first.
+pub async fn all_entry_ids(pool: &Pool<Sqlite>) -> Result<Vec<String>, String> {
+    sqlx::query_scalar("SELECT id FROM entries ORDER BY created_at ASC, id ASC")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// `(id, created_at)` of entries created in `[from, to)`, oldest first. Bounds are
+/// compared as RFC3339 strings, e.g. "2024-05-01".
+pub async fn entry_ids_created_between(
+    pool: &Pool<Sqlite>,
+    from: &str,
+    to: &str,
+) -> Result<Vec<(String, String)>, String> {
+    sqlx::query_as::<_, (String, String)>(
+        "SELECT id, created_at FROM entries WHERE created_at >= ?1 AND created_at < ?2 ORDER BY created_at ASC",
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Entries from earlier years written on the given calendar day ("on this day").
+///
+/// `created_at` is stored in UTC; days are matched in the caller's local time given by
+/// `utc_offset_minutes` (0 = UTC). Asking for Feb 28 in a non-leap year also returns
+/// Feb 29 entries so leap-day memories still surface. Newest first.
+pub async fn entries_on_day(
+    pool: &Pool<Sqlite>,
+    month: u8,
+    day: u8,
+    utc_offset_minutes: i32,
+    preview_length: usize,
+) -> Result<Vec<EntryListItem>, String> {
+    let month_enum = time::Month::try_from(month).map_err(|_| format!("invalid month: {}", month))?;
+    // 2024 is a leap year, so Feb 29 validates
+    time::Date::from_calendar_date(2024, month_enum, day)
+        .map_err(|_| format!("invalid day {} for month {}", day, month))?;
+    let offset = time::UtcOffset::from_whole_seconds(utc_offset_minutes * 60)
+        .map_err(|_| format!("invalid UTC offset: {} minutes", utc_offset_minutes))?;
+    let today = OffsetDateTime::now_utc().to_offset(offset).date();
+
+    let mut days = vec![format!("{:02}-{:02}", month, day)];
+    if month == 2 && day == 28 && !time::util::is_leap_year(today.year()) {
+        days.push("02-29".to_string());
+    }
+    let modifier = format!("{:+} minutes", utc_offset_minutes);
+
+    let rows = sqlx::query(
+        r#"SELECT id, created_at, updated_at, body_cipher, mood, tags, pinned, summary FROM entries
+           WHERE strftime('%m-%d', created_at, ?1) IN (?2, ?3)
+             AND CAST(strftime('%Y', created_at, ?1) AS INTEGER) < ?4
+           ORDER BY created_at DESC"#,
+    )
+    .bind(&modifier)
+    .bind(&days[0])
+    .bind(days.last().unwrap_or(&days[0]))
+    .bind(today.year())
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(rows.iter().map(|row| entry_list_item_from_row(row, preview_length)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn memory_pool() -> Pool<Sqlite> {
+        // One connection: every connection to `sqlite::memory:` is a separate database
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("open in-memory database");
+        init_db(&pool).await.expect("init schema");
+        pool
+    }
+
+    // Bodies are stored as legacy plaintext so reading them never needs the keychain
+    #[tokio::test]
+    async fn upsert_without_mood_or_tags_keeps_the_stored_ones() {
+        let pool = memory_pool().await;
+        let id = "e1".to_string();
+        insert_tagged_entry(&pool, &id, "2024-01-01T00:00:00Z", Some("happy"), Some(r#"["work"]"#)).await;
+        let saved = upsert_entry(&pool, EntryUpsert {
+            id: Some(id.clone()),
+            body_cipher: b"edited body".to_vec(),
+            mood: None,
+            tags: None,
+            created_at: None,
+        })
+        .await
+        .expect("upsert");
+        assert_eq!(saved.mood.as_deref(), Some("happy"));
+        assert_eq!(saved.tags, Some(serde_json::json!(["work"])));
+
+        let saved = upsert_entry(&pool, EntryUpsert {
+            id: Some(id),
+            body_cipher: b"edited body".to_vec(),
+            mood: Some("calm".into()),
+            tags: Some(serde_json::json!(["home"])),
+            created_at: None,
+        })
+        .await
+        .expect("upsert");
+        assert_eq!(saved.mood.as_deref(), Some("calm"));
+        assert_eq!(saved.tags, Some(serde_json::json!(["home"])));
+    }
+
+    async fn insert_plain_entry(pool: &Pool<Sqlite>, id: &str, created_at: &str) {
+        insert_tagged_entry(pool, id, created_at, None, None).await;
+    }
+
+    async fn insert_tagged_entry(pool: &Pool<Sqlite>, id: &str, created_at: &str, mood: Option<&str>, tags: Option<&str>) {
+        sqlx::query("INSERT INTO entries (id, created_at, updated_at, body_cipher, mood, tags) VALUES (?1, ?2, ?2, ?3, ?4, ?5)")
+            .bind(id)
+            .bind(created_at)
+            .bind(b"plaintext body".to_vec())
+            .bind(mood)
+            .bind(tags)
+            .execute(pool)
+            .await
+            .expect("insert entry");
+    }
+
+    fn job(job_id: &str, entry_id: &str, stage: ComicStage) -> ComicJobStatus {
+        ComicJobStatus {
+            job_id: job_id.to_string(),
+            entry_id: entry_id.to_string(),
+            style: "ink".to_string(),
+            stage,
+            updated_at: now_iso(),
+            result_image_path: None,
+            storyboard_text: None,
+            meta: None,
+            panel_image_paths: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn startup_recovery_fails_only_running_jobs() {
+        let pool = memory_pool().await;
+        insert_plain_entry(&pool, "e1", "2024-05-01T10:00:00Z").await;
+        upsert_comic_job(&pool, &job("rendering", "e1", ComicStage::Rendering { completed: 1, total: 4 })).await.unwrap();
+        upsert_comic_job(&pool, &job("prompting", "e1", ComicStage::Prompting)).await.unwrap();
+        upsert_comic_job(&pool, &job("done", "e1", ComicStage::Done)).await.unwrap();
+        upsert_comic_job(&pool, &job("queued", "e1", ComicStage::Queued)).await.unwrap();
+        let options = ComicJobOptions { urgent: true, panel_count: Some(5), ..Default::default() };
+        set_comic_job_options(&pool, "queued", &options).await.unwrap();
+
+        let mut recovered: Vec<String> = recover_interrupted_comic_jobs(&pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|j| j.job_id)
+            .collect();
+        recovered.sort();
+        assert_eq!(recovered, ["prompting", "rendering"]);
+
+        let rendering = get_comic_job(&pool, "rendering").await.unwrap().unwrap();
+        assert!(matches!(rendering.stage, ComicStage::Failed { ref error, .. } if error == "interrupted by restart"));
+        let done = get_comic_job(&pool, "done").await.unwrap().unwrap();
+        assert!(matches!(done.stage, ComicStage::Done));
+        // Queued jobs never started; they keep their stage and options
+        let queued = queued_comic_jobs(&pool).await.unwrap();
+        assert_eq!(queued.len(), 1);
+        assert!(matches!(queued[0].0.stage, ComicStage::Queued));
+        assert!(queued[0].1.urgent);
+        assert_eq!(queued[0].1.panel_count, Some(5));
+        // A second start finds nothing left to recover
+        assert!(recover_interrupted_comic_jobs(&pool).await.unwrap().is_empty());
+    }
+
+    async fn filter_fixture() -> Pool<Sqlite> {
+        let pool = memory_pool().await;
+        insert_tagged_entry(&pool, "anxious-work", "2024-03-01T09:00:00Z", Some("Anxious"), Some(r#"["Work","deadline"]"#)).await;
+        insert_tagged_entry(&pool, "anxious-home", "2024-03-15T20:00:00.250Z", Some("anxious"), Some(r#"["home"]"#)).await;
+        insert_tagged_entry(&pool, "happy-work", "2024-04-02T12:00:00Z", Some("happy"), Some(r#"["work"]"#)).await;
+        insert_tagged_entry(&pool, "untagged", "2024-04-20T08:00:00Z", None, None).await;
+        insert_tagged_entry(&pool, "bad-tags", "2024-04-21T08:00:00Z", Some("happy"), Some("not json")).await;
+        insert_tagged_entry(&pool, "archived-work", "2024-03-02T09:00:00Z", Some("anxious"), Some(r#"["work"]"#)).await;
+        sqlx::query("UPDATE entries SET archived = 1 WHERE id = 'archived-work'").execute(&pool).await.unwrap();
+        pool
+    }
+
+    async fn listed(pool: &Pool<Sqlite>, params: ListParams) -> (Vec<String>, i64) {
+        let page = list_entries(pool, Some(params), DEFAULT_PREVIEW_LENGTH).await.unwrap();
+        let mut ids: Vec<String> = page.items.into_iter().map(|e| e.id).collect();
+        ids.sort();
+        (ids, page.total)
+    }
+
+    #[tokio::test]
+    async fn list_without_filters_returns_every_unarchived_entry() {
+        let pool = filter_fixture().await;
+        let (ids, total) = listed(&pool, ListParams::default()).await;
+        assert_eq!(ids, ["anxious-home", "anxious-work", "bad-tags", "happy-work", "untagged"]);
+        assert_eq!(total, 5);
+    }
+
+    #[tokio::test]
+    async fn list_filters_by_mood_case_insensitively() {
+        let pool = filter_fixture().await;
+        let (ids, total) = listed(&pool, ListParams { mood: Some(" ANXIOUS ".into()), ..Default::default() }).await;
+        assert_eq!(ids, ["anxious-home", "anxious-work"]);
+        assert_eq!(total, 2);
+    }
+
+    #[tokio::test]
+    async fn list_filters_by_any_tag_and_skips_malformed_tags() {
+        let pool = filter_fixture().await;
+        let params = ListParams { tags_any: Some(vec!["work".into(), "HOME".into()]), ..Default::default() };
+        let (ids, _) = listed(&pool, params).await;
+        assert_eq!(ids, ["anxious-home", "anxious-work", "happy-work"]);
+    }
+
+    #[tokio::test]
+    async fn list_filters_by_date_range_with_exclusive_end() {
+        let pool = filter_fixture().await;
+        let params = ListParams {
+            date_from: Some("2024-03-15T20:00:00.250Z".into()),
+            date_to: Some("2024-04-20T08:00:00Z".into()),
+            ..Default::default()
+        };
+        let (ids, _) = listed(&pool, params).await;
+        assert_eq!(ids, ["anxious-home", "happy-work"]);
+    }
+
+    #[tokio::test]
+    async fn list_date_bounds_accept_other_offsets() {
+        let pool = filter_fixture().await;
+        // 2024-04-02T12:00:00Z expressed in UTC+02:00
+        let params = ListParams { date_from: Some("2024-04-02T14:00:00+02:00".into()), ..Default::default() };
+        let (ids, _) = listed(&pool, params).await;
+        assert_eq!(ids, ["bad-tags", "happy-work", "untagged"]);
+    }
+
+    #[tokio::test]
+    async fn list_combines_mood_tags_and_dates() {
+        let pool = filter_fixture().await;
+        let params = ListParams {
+            mood: Some("anxious".into()),
+            tags_any: Some(vec!["work".into()]),
+            date_from: Some("2024-03-01T00:00:00Z".into()),
+            date_to: Some("2024-04-01T00:00:00Z".into()),
+            ..Default::default()
+        };
+        let (ids, total) = listed(&pool, params).await;
+        assert_eq!(ids, ["anxious-work"]);
+        assert_eq!(total, 1);
+    }
+
+    #[tokio::test]
+    async fn list_total_counts_every_match_beyond_the_page() {
+        let pool = filter_fixture().await;
+        let params = ListParams { limit: Some(1), offset: Some(1), mood: Some("happy".into()), ..Default::default() };
+        let (ids, total) = listed(&pool, params).await;
+        assert_eq!(ids.len(), 1);
+        assert_eq!(total, 2);
+    }
+
+    #[tokio::test]
+    async fn archived_list_uses_the_same_filters() {
+        let pool = filter_fixture().await;
+        let params = ListParams { tags_any: Some(vec!["work".into()]), ..Default::default() };
+        let page = list_archived_entries(&pool, Some(params), DEFAULT_PREVIEW_LENGTH).await.unwrap();
+        assert_eq!(page.items.iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), ["archived-work"]);
+        assert_eq!(page.total, 1);
+    }
+
+    #[tokio::test]
+    async fn list_rejects_a_malformed_date() {
+        let pool = filter_fixture().await;
+        let params = ListParams { date_from: Some("last tuesday".into()), ..Default::default() };
+        let err = list_entries(&pool, Some(params), DEFAULT_PREVIEW_LENGTH).await.unwrap_err();
+        assert!(err.contains("date_from"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn changed_since_keeps_fractional_seconds_in_the_same_second() {
+        let pool = memory_pool().await;
+        insert_plain_entry(&pool, "before", "2024-05-01T10:00:04.900Z").await;
+        insert_plain_entry(&pool, "at", "2024-05-01T10:00:05Z").await;
+        insert_plain_entry(&pool, "fraction", "2024-05-01T10:00:05.5Z").await;
+        insert_plain_entry(&pool, "later", "2024-05-01T10:00:06Z").await;
+
+        let ids = |entries: Vec<Entry>| entries.into_iter().map(|e| e.id).collect::<Vec<_>>();
+        let changed = list_entries_changed_since(&pool, "2024-05-01T10:00:05Z").await.unwrap();
+        assert_eq!(ids(changed), ["fraction", "later"]);
+        let changed = list_entries_changed_since(&pool, "2024-05-01T12:00:05.25+02:00").await.unwrap();
+        assert_eq!(ids(changed), ["fraction", "later"]);
+    }
+
+    #[tokio::test]
+    async fn old_database_with_title_migrates_once_to_the_current_version() {
+        let pool = SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+        // The shape of databases created before schema_meta existed
+        sqlx::query(
+            "CREATE TABLE entries (id TEXT PRIMARY KEY, title TEXT, created_at TEXT NOT NULL, updated_at TEXT NOT NULL, \
+             body_cipher BLOB NOT NULL, mood TEXT, tags TEXT, embedding BLOB)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO entries (id, title, created_at, updated_at, body_cipher, mood) VALUES ('old', 'A title', '2023-01-01T00:00:00Z', '2023-01-01T00:00:00Z', X'6869', 'calm')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(schema_version(&pool).await.unwrap(), None);
+        let pending = pending_migrations(&pool).await.unwrap();
+        assert!(pending.iter().any(|m| m.name == "drop_entries_title" && m.destructive));
+
+        init_db(&pool).await.unwrap();
+        let latest = MIGRATIONS.last().unwrap().version;
+        assert_eq!(schema_version(&pool).await.unwrap(), Some(latest));
+        let columns = table_columns(&pool, "entries").await.unwrap();
+        assert!(!columns.iter().any(|c| c == "title"));
+        for column in ["pinned", "body_hash", "preferred_style", "summary", "archived"] {
+            assert!(columns.iter().any(|c| c == column), "missing entries.{}", column);
+        }
+        let (mood, pinned): (String, i64) = sqlx::query_as("SELECT mood, pinned FROM entries WHERE id = 'old'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!((mood.as_str(), pinned), ("calm", 0));
+
+        // A second start has nothing left to run
+        assert_eq!(run_migrations(&pool).await.unwrap(), 0);
+        init_db(&pool).await.unwrap();
+        let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM schema_meta").fetch_one(&pool).await.unwrap();
+        assert_eq!(rows, 1);
+        assert_eq!(schema_version(&pool).await.unwrap(), Some(latest));
+        assert!(pending_migrations(&pool).await.unwrap().is_empty());
+        assert!(verify_schema(&pool, false).await.unwrap().ok);
+    }
+
+    #[tokio::test]
+    async fn deferred_migrations_leave_startup_usable_until_applied() {
+        let dir = std::env::temp_dir().join(format!("toonana-defer-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("toonana.sqlite");
+        {
+            let old = create_pool_without_migrations(&path).await;
+            sqlx::query(
+                "CREATE TABLE entries (id TEXT PRIMARY KEY, title TEXT, created_at TEXT NOT NULL, updated_at TEXT NOT NULL, \
+                 body_cipher BLOB NOT NULL, mood TEXT, tags TEXT, embedding BLOB)",
+            )
+            .execute(&old)
+            .await
+            .unwrap();
+            old.close().await;
+        }
+
+        let pool = create_pool(&path, true).await.unwrap();
+        assert!(table_columns(&pool, "comic_jobs").await.unwrap().is_empty());
+        // What tauri_startup runs next must not fail on the missing table
+        assert!(recover_interrupted_comic_jobs(&pool).await.unwrap().is_empty());
+        assert!(pending_migrations(&pool).await.unwrap().iter().any(|m| m.destructive));
+
+        assert!(apply_migrations(&pool).await.unwrap().is_empty());
+        assert!(recover_interrupted_comic_jobs(&pool).await.unwrap().is_empty());
+        pool.close().await;
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    async fn create_pool_without_migrations(path: &Path) -> Pool<Sqlite> {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(SqliteConnectOptions::new().filename(path).create_if_missing(true))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn restore_refuses_a_backup_from_a_newer_schema() {
+        let dir = std::env::temp_dir().join(format!("toonana-restore-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("backup.sqlite");
+        let pool = create_pool(&path, false).await.unwrap();
+        validate_restore_source(&path).await.unwrap();
+
+        let newer = MIGRATIONS.last().unwrap().version + 1;
+        sqlx::query("UPDATE schema_meta SET version = ?1").bind(newer).execute(&pool).await.unwrap();
+        pool.close().await;
+        let err = validate_restore_source(&path).await.unwrap_err();
+        assert!(err.contains("newer than this version"), "{}", err);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn fresh_database_starts_at_the_current_version() {
+        let pool = memory_pool().await;
+        assert_eq!(schema_version(&pool).await.unwrap(), Some(MIGRATIONS.last().unwrap().version));
+        let report = verify_schema(&pool, false).await.unwrap();
+        assert!(report.ok, "{:?}", report.issues.iter().map(|i| (&i.table, &i.column)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn preview_cuts_multibyte_text_on_character_boundaries() {
+        // The 50th character is an emoji in the middle of a word run
+        let emoji = "🌧".repeat(60);
+        assert_eq!(preview_text(&emoji, 50), format!("{}...", "🌧".repeat(50)));
+        let cjk = "今日は雨でした。傘を忘れて駅まで走った。".repeat(4);
+        let preview = preview_text(&cjk, 10);
+        assert_eq!(preview, "今日は雨でした。傘を...");
+        // Short multibyte text whose byte length exceeds the limit isn't cut
+        assert_eq!(preview_text("ありがとう", 5), "ありがとう");
+    }
+
+    #[tokio::test]
+    async fn finished_jobs_keep_meta_written_after_they_end() {
+        let pool = memory_pool().await;
+        insert_plain_entry(&pool, "e1", "2024-05-01T10:00:00Z").await;
+        upsert_comic_job(&pool, &job("j1", "e1", ComicStage::Done)).await.unwrap();
+
+        let meta = serde_json::json!({ "alt_text": "A cat naps in the sun.", "alt_text_source": "vision" });
+        set_comic_job_meta(&pool, "j1", &meta).await.unwrap();
+        let stored = get_comic_job(&pool, "j1").await.unwrap().unwrap();
+        assert_eq!(stored.meta, Some(meta));
+        assert!(matches!(stored.stage, ComicStage::Done));
+    }
+}
@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Row, Sqlite};
+use tracing::{info, warn};
+
+use crate::ollama;
+use crate::settings::Settings;
+
+// Used when `embedding_model` isn't configured
+pub const DEFAULT_EMBEDDING_MODEL: &str = "nomic-embed-text";
+
+#[derive(Debug, Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    embedding: Vec<f32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SimilarEntry {
+    pub id: String,
+    pub created_at: String,
+    pub summary: Option<String>,
+    pub score: f32,
+}
+
+// Embeddings are stored in `entries.embedding` as little-endian f32s
+fn decode_embedding(bytes: &[u8]) -> Result<Vec<f32>, String> {
+    if bytes.is_empty() || !bytes.len().is_multiple_of(4) {
+        return Err(format!("malformed embedding ({} bytes)", bytes.len()));
+    }
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect())
+}
+
+fn encode_embedding(v: &[f32]) -> Vec<u8> {
+    v.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+/// Embed `text` with Ollama's `/api/embeddings` using the `embedding_model` setting.
+pub async fn embed_text(text: &str, settings: &Settings) -> Result<Vec<f32>, String> {
+    let model = settings
+        .embedding_model
+        .as_deref()
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .unwrap_or(DEFAULT_EMBEDDING_MODEL);
+    let url = format!("{}/api/embeddings", ollama::ollama_base_url(settings));
+    let resp = ollama::http_client(settings)?
+        .post(url)
+        .json(&EmbeddingRequest { model, prompt: text })
+        .send()
+        .await
+        .map_err(|e| format!("embedding request failed: {e}"))?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        return Err(format!("embedding error: HTTP {} {}", status, body.trim()));
+    }
+    let parsed: EmbeddingResponse = resp
+        .json()
+        .await
+        .map_err(|e| format!("embedding response parse error: {e}"))?;
+    if parsed.embedding.is_empty() {
+        return Err(format!("model '{}' returned an empty embedding", model));
+    }
+    Ok(parsed.embedding)
+}
+
+/// Embed the entry's current body and store it, returning the vector. The vector isn't
+/// stored if the body changed while it was computed, so a slow refresh for an older save
+/// can't overwrite the embedding of a newer one.
+pub async fn refresh_embedding(pool: &Pool<Sqlite>, id: &str, settings: &Settings) -> Result<Vec<f32>, String> {
+    let body = crate::database::get_entry_body(pool, id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let hash = crate::database::body_hash(body.as_bytes());
+    let vector = embed_text(&body, settings).await?;
+    let stored = sqlx::query("UPDATE entries SET embedding = ?1 WHERE id = ?2 AND body_hash = ?3")
+        .bind(encode_embedding(&vector))
+        .bind(id)
+        .bind(&hash)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .rows_affected();
+    if stored == 0 {
+        info!(entry_id = %id, "entry changed while embedding; stale embedding not stored");
+    } else {
+        info!(entry_id = %id, dims = vector.len(), "entry embedding stored");
+    }
+    Ok(vector)
+}
+
+/// Cosine similarity mapped to 0..=1 (opposite or unrelated vectors score 0).
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> Result<f32, String> {
+    if a.len() != b.len() {
+        return Err(format!("embedding sizes differ ({} vs {})", a.len(), b.len()));
+    }
+    let (mut dot, mut na, mut nb) = (0f64, 0f64, 0f64);
+    for (x, y) in a.iter().zip(b) {
+        let (x, y) = (*x as f64, *y as f64);
+        dot += x * y;
+        na += x * x;
+        nb += y * y;
+    }
+    if na == 0.0 || nb == 0.0 {
+        return Err("embedding is all zeros".to_string());
+    }
+    Ok((dot / (na.sqrt() * nb.sqrt())).clamp(0.0, 1.0) as f32)
+}
+
+async fn load_embedding(pool: &Pool<Sqlite>, id: &str) -> Result<Vec<f32>, String> {
+    let blob: Option<Option<Vec<u8>>> = sqlx::query_scalar("SELECT embedding FROM entries WHERE id = ?1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    match blob {
+        None => Err(format!("entry not found: {}", id)),
+        Some(None) => Err(format!("entry {} has no embedding yet", id)),
+        Some(Some(bytes)) => decode_embedding(&bytes).map_err(|e| format!("entry {}: {}", id, e)),
+    }
+}
+
+/// Similarity (0..=1) of two entries from their stored embeddings.
+pub async fn entry_similarity(pool: &Pool<Sqlite>, id_a: &str, id_b: &str) -> Result<f32, String> {
+    let a = load_embedding(pool, id_a).await?;
+    let b = load_embedding(pool, id_b).await?;
+    cosine_similarity(&a, &b)
+}
+
+/// The `top_k` non-archived entries most similar to `id`, best first. Entries without an
+/// embedding (the target included) are embedded on the way; ones that fail are skipped.
+pub async fn find_similar_entries(
+    pool: &Pool<Sqlite>,
+    id: &str,
+    top_k: usize,
+    settings: &Settings,
+) -> Result<Vec<SimilarEntry>, String> {
+    let target = match load_embedding(pool, id).await {
+        Ok(v) => v,
+        Err(_) => refresh_embedding(pool, id, settings).await?,
+    };
+
+    let rows = sqlx::query(
+        "SELECT id, created_at, summary, embedding FROM entries WHERE archived = 0 AND id != ?1",
+    )
+    .bind(id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut scored = Vec::with_capacity(rows.len());
+    for row in rows {
+        let other_id: String = row.try_get("id").map_err(|e| e.to_string())?;
+        let stored = row
+            .try_get::<Option<Vec<u8>>, _>("embedding")
+            .ok()
+            .flatten()
+            .and_then(|bytes| decode_embedding(&bytes).ok());
+        let vector = match stored {
+            // A vector from a different model can't be compared; re-embed it
+            Some(v) if v.len() == target.len() => v,
+            _ => match refresh_embedding(pool, &other_id, settings).await {
+                Ok(v) => v,
+                Err(e) => {
+                    warn!(entry_id = %other_id, error = %e, "skipping entry without embedding");
+                    continue;
+                }
+            },
+        };
+        let Ok(score) = cosine_similarity(&target, &vector) else {
+            continue;
+        };
+        scored.push(SimilarEntry {
+            id: other_id,
+            created_at: row.try_get("created_at").unwrap_or_default(),
+            summary: row.try_get("summary").ok().flatten(),
+            score,
+        });
+    }
+    scored.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.id.cmp(&b.id)));
+    scored.truncate(top_k);
+    Ok(scored)
+}
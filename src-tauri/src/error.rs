@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+
+/// Error returned by Tauri commands. Serialized as a tagged object, e.g.
+/// `{ "kind": "missing_api_key", "message": "Gemini API key not set" }`, so the UI can
+/// branch on `kind` and show `message`.
+#[derive(Debug, Clone, thiserror::Error, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ToonanaError {
+    #[error("{message}")]
+    OllamaUnreachable { message: String },
+    #[error("{message}")]
+    MissingApiKey { message: String },
+    #[error("{message}")]
+    SafetyBlocked { message: String },
+    /// The selected image provider can't do what was asked (e.g. per-panel rendering)
+    #[error("{message}")]
+    UnsupportedProvider { message: String },
+    #[error("{message}")]
+    Db { message: String },
+    #[error("{message}")]
+    Io { message: String },
+    #[error("{message}")]
+    Decode { message: String },
+    #[error("{message}")]
+    Other { message: String },
+}
+
+/// The variant of a `ToonanaError` without its message, e.g. for `ComicStage::Failed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    OllamaUnreachable,
+    MissingApiKey,
+    SafetyBlocked,
+    UnsupportedProvider,
+    Db,
+    Io,
+    Decode,
+    Other,
+}
+
+impl ToonanaError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ToonanaError::OllamaUnreachable { .. } => ErrorKind::OllamaUnreachable,
+            ToonanaError::MissingApiKey { .. } => ErrorKind::MissingApiKey,
+            ToonanaError::SafetyBlocked { .. } => ErrorKind::SafetyBlocked,
+            ToonanaError::UnsupportedProvider { .. } => ErrorKind::UnsupportedProvider,
+            ToonanaError::Db { .. } => ErrorKind::Db,
+            ToonanaError::Io { .. } => ErrorKind::Io,
+            ToonanaError::Decode { .. } => ErrorKind::Decode,
+            ToonanaError::Other { .. } => ErrorKind::Other,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            ToonanaError::OllamaUnreachable { message }
+            | ToonanaError::MissingApiKey { message }
+            | ToonanaError::SafetyBlocked { message }
+            | ToonanaError::UnsupportedProvider { message }
+            | ToonanaError::Db { message }
+            | ToonanaError::Io { message }
+            | ToonanaError::Decode { message }
+            | ToonanaError::Other { message } => message,
+        }
+    }
+
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        let message = message.into();
+        match kind {
+            ErrorKind::OllamaUnreachable => ToonanaError::OllamaUnreachable { message },
+            ErrorKind::MissingApiKey => ToonanaError::MissingApiKey { message },
+            ErrorKind::SafetyBlocked => ToonanaError::SafetyBlocked { message },
+            ErrorKind::UnsupportedProvider => ToonanaError::UnsupportedProvider { message },
+            ErrorKind::Db => ToonanaError::Db { message },
+            ErrorKind::Io => ToonanaError::Io { message },
+            ErrorKind::Decode => ToonanaError::Decode { message },
+            ErrorKind::Other => ToonanaError::Other { message },
+        }
+    }
+
+    /// Pick the variant for a message produced by one of our own modules. Lower layers still
+    /// return `String`s; their wording for these cases is fixed, so it's matched here once
+    /// instead of in the UI.
+    pub fn classify(message: impl Into<String>) -> Self {
+        let message = message.into();
+        let lower = message.to_ascii_lowercase();
+        let kind = if lower.contains(crate::ollama::UNREACHABLE_MARKER) {
+            ErrorKind::OllamaUnreachable
+        } else if lower.contains("api key not set") {
+            ErrorKind::MissingApiKey
+        } else if lower.contains(crate::comic::UNSUPPORTED_PROVIDER_MARKER) {
+            ErrorKind::UnsupportedProvider
+        } else if lower.contains(crate::gemini::STYLE_REFERENCE_UNREADABLE_MARKER) {
+            ErrorKind::Io
+        } else if lower.contains("safety") || lower.contains("image provider declined") {
+            ErrorKind::SafetyBlocked
+        } else if lower.contains("decode") || lower.contains("parse error") || lower.contains("malformed") {
+            ErrorKind::Decode
+        } else {
+            ErrorKind::Other
+        };
+        ToonanaError::new(kind, message)
+    }
+}
+
+impl From<String> for ToonanaError {
+    fn from(message: String) -> Self {
+        ToonanaError::classify(message)
+    }
+}
+
+impl From<&str> for ToonanaError {
+    fn from(message: &str) -> Self {
+        ToonanaError::classify(message)
+    }
+}
+
+impl From<sqlx::Error> for ToonanaError {
+    fn from(e: sqlx::Error) -> Self {
+        ToonanaError::Db { message: e.to_string() }
+    }
+}
+
+impl From<std::io::Error> for ToonanaError {
+    fn from(e: std::io::Error) -> Self {
+        ToonanaError::Io { message: e.to_string() }
+    }
+}
+
+impl From<serde_json::Error> for ToonanaError {
+    fn from(e: serde_json::Error) -> Self {
+        ToonanaError::Decode { message: e.to_string() }
+    }
+}
+
+impl From<base64::DecodeError> for ToonanaError {
+    fn from(e: base64::DecodeError) -> Self {
+        ToonanaError::Decode { message: e.to_string() }
+    }
+}
+
+impl From<anyhow::Error> for ToonanaError {
+    // The message is the outermost context, as before; the variant comes from the cause
+    fn from(e: anyhow::Error) -> Self {
+        let message = e.to_string();
+        for cause in e.chain() {
+            if cause.is::<sqlx::Error>() {
+                return ToonanaError::Db { message };
+            }
+            if cause.is::<std::io::Error>() {
+                return ToonanaError::Io { message };
+            }
+            if cause.is::<serde_json::Error>() || cause.is::<base64::DecodeError>() || cause.is::<image::ImageError>() {
+                return ToonanaError::Decode { message };
+            }
+        }
+        let kind = ToonanaError::classify(format!("{:#}", e)).kind();
+        ToonanaError::new(kind, message)
+    }
+}
@@ -1,20 +1,227 @@
 use anyhow::{anyhow, Context, Result};
 use futures_util::StreamExt;
 use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
 use std::time::Duration;
 
+use tokio_util::sync::CancellationToken;
+
+use crate::image_cache::fetch_image_bytes;
 use crate::settings::Settings;
-use tracing::{info, error, instrument};
+use crate::utils::{http_client_builder, normalize_base_url, truncate_chars};
+use tracing::{debug, info, warn, error, instrument};
+
+/// Marks a stream failure that already attempted the non-streaming recovery,
+/// so `generate_image_with_progress` doesn't retry it a second time.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+struct StreamRecoveryFailed(String);
+
+/// The model answered with text (or a block reason) instead of an image. Retrying the
+/// same prompt won't help, so the explanation is surfaced to the user as-is.
+#[derive(Debug, thiserror::Error)]
+#[error("image provider declined: {0}")]
+struct ImageRefused(String);
+
+/// The caller's cancellation token fired before an image came back.
+#[derive(Debug, thiserror::Error)]
+#[error("cancelled")]
+pub struct Cancelled;
+
+// Per-call defaults used when `request_timeout_secs` / `connect_timeout_secs` are unset
+const REQUEST_TIMEOUT_SECS: u64 = 60;
+const STREAM_TIMEOUT_SECS: u64 = 90;
+const CONNECT_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_IMAGE_GEN_RETRIES: u32 = 1;
+// 429/503 handling when `rate_limit_max_retries` is unset; without a Retry-After header
+// waits double from the base delay, and every wait is capped
+const DEFAULT_RATE_LIMIT_RETRIES: u32 = 3;
+const RATE_LIMIT_BASE_DELAY_SECS: u64 = 2;
+const RATE_LIMIT_MAX_DELAY_SECS: u64 = 60;
+
+// Used when `gemini_image_model` / `gemini_text_model` aren't configured
+/// Lowercase phrase that starts the error for a style reference image that can't be read;
+/// the render fails rather than silently going ahead without the reference.
+pub const STYLE_REFERENCE_UNREADABLE_MARKER: &str = "style reference image could not be read";
+
+pub const DEFAULT_GEMINI_IMAGE_MODEL: &str = "gemini-2.5-flash-image-preview";
+pub const DEFAULT_GEMINI_TEXT_MODEL: &str = "gemini-2.0-flash";
+
+fn image_model(settings: &Settings) -> &str {
+    settings
+        .gemini_image_model
+        .as_deref()
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .unwrap_or(DEFAULT_GEMINI_IMAGE_MODEL)
+}
+
+fn text_model(settings: &Settings) -> &str {
+    settings
+        .gemini_text_model
+        .as_deref()
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .unwrap_or(DEFAULT_GEMINI_TEXT_MODEL)
+}
+
+fn client_builder(settings: &Settings, default_timeout_secs: u64) -> reqwest::ClientBuilder {
+    http_client_builder(settings, Some(default_timeout_secs), Some(CONNECT_TIMEOUT_SECS))
+}
+
+/// Aspect ratios Gemini's `imageConfig.aspectRatio` accepts.
+pub const GEMINI_ASPECT_RATIOS: &[&str] = &[
+    "1:1", "2:3", "3:2", "3:4", "4:3", "4:5", "5:4", "9:16", "16:9", "21:9",
+];
+
+fn parse_aspect_ratio(s: &str) -> Option<f64> {
+    let (w, h) = s.trim().split_once(':')?;
+    let (w, h) = (w.trim().parse::<f64>().ok()?, h.trim().parse::<f64>().ok()?);
+    (w > 0.0 && h > 0.0).then_some(w / h)
+}
+
+/// Check an `image_aspect_ratio` setting ("W:H" with positive numbers).
+pub fn validate_aspect_ratio(s: &str) -> Result<(), String> {
+    parse_aspect_ratio(s)
+        .map(|_| ())
+        .ok_or_else(|| format!("invalid aspect ratio '{}': expected W:H, e.g. 16:9", s))
+}
+
+/// The entry of `options` closest to `ratio` (compared on a log scale so 2:1 and 1:2
+/// are equally far from 1:1). Falls back to the first option for unparsable input.
+pub fn closest_aspect_ratio(ratio: &str, options: &[&'static str]) -> &'static str {
+    let target = parse_aspect_ratio(ratio).unwrap_or(1.0).ln();
+    options
+        .iter()
+        .copied()
+        .min_by(|a, b| {
+            let da = (parse_aspect_ratio(a).unwrap_or(1.0).ln() - target).abs();
+            let db = (parse_aspect_ratio(b).unwrap_or(1.0).ln() - target).abs();
+            da.total_cmp(&db)
+        })
+        .unwrap_or(options[0])
+}
+
+// generationConfig for image requests, with the configured aspect ratio when set
+fn image_generation_config(settings: &Settings) -> serde_json::Value {
+    let mut cfg = serde_json::json!({ "responseModalities": ["IMAGE"] });
+    if let Some(r) = settings.image_aspect_ratio.as_deref().filter(|r| !r.trim().is_empty()) {
+        cfg["imageConfig"] = serde_json::json!({ "aspectRatio": closest_aspect_ratio(r, GEMINI_ASPECT_RATIOS) });
+    }
+    cfg
+}
+
+// Concatenated `text` parts of every candidate in a (chunk of a) generateContent response
+fn response_text_parts(v: &serde_json::Value) -> String {
+    let mut out = String::new();
+    for cand in v.get("candidates").and_then(|c| c.as_array()).into_iter().flatten() {
+        let parts = cand.get("content").and_then(|c| c.get("parts")).and_then(|p| p.as_array());
+        for part in parts.into_iter().flatten() {
+            if let Some(t) = part.get("text").and_then(|t| t.as_str()) {
+                out.push_str(t);
+            }
+        }
+    }
+    out
+}
+
+// Why the response was cut short, when it wasn't a normal stop (e.g. "SAFETY", "IMAGE_SAFETY")
+fn block_reason(v: &serde_json::Value) -> Option<String> {
+    if let Some(r) = v.get("promptFeedback").and_then(|f| f.get("blockReason")).and_then(|r| r.as_str()) {
+        return Some(r.to_string());
+    }
+    v.get("candidates")
+        .and_then(|c| c.as_array())
+        .and_then(|c| c.first())
+        .and_then(|c| c.get("finishReason"))
+        .and_then(|r| r.as_str())
+        .filter(|r| !matches!(*r, "STOP" | "FINISH_REASON_UNSPECIFIED" | "MAX_TOKENS"))
+        .map(|r| r.to_string())
+}
+
+fn refusal_error(text: &str, reason: Option<&str>) -> Option<anyhow::Error> {
+    let text = text.trim();
+    let message = match (text.is_empty(), reason) {
+        (true, None) => return None,
+        (true, Some(r)) => format!("blocked ({})", r),
+        (false, None) => text.to_string(),
+        (false, Some(r)) => format!("{} ({})", text, r),
+    };
+    Some(ImageRefused(message).into())
+}
+
+/// Every inline image payload in a response (or stream chunk), in document order.
+/// Looks at `inlineData`/`inline_data`, `bytesBase64Encoded` and `b64_json` at any depth.
+pub fn collect_inline_images(v: &serde_json::Value, out: &mut Vec<String>) {
+    match v {
+        serde_json::Value::Object(obj) => {
+            for (key, val) in obj {
+                let data = match key.as_str() {
+                    "inlineData" | "inline_data" => val.get("data").and_then(|d| d.as_str()),
+                    "bytesBase64Encoded" | "b64_json" => val.as_str(),
+                    _ => None,
+                };
+                match data {
+                    Some(d) if !d.is_empty() => out.push(d.to_string()),
+                    Some(_) => {}
+                    None => collect_inline_images(val, out),
+                }
+            }
+        }
+        serde_json::Value::Array(arr) => {
+            for item in arr {
+                collect_inline_images(item, out);
+            }
+        }
+        _ => {}
+    }
+}
 
+// Pixel count of a base64 (or data: URI) image that fully decodes, None if it's truncated or corrupt
+fn complete_image_area(b64: &str) -> Option<u64> {
+    let data = match b64.split_once(";base64,") {
+        Some((prefix, rest)) if prefix.starts_with("data:") => rest,
+        _ => b64,
+    };
+    let bytes = B64.decode(data.trim()).ok()?;
+    let img = image::load_from_memory(&bytes).ok()?;
+    Some(img.width() as u64 * img.height() as u64)
+}
 
+/// Choose which of several images Gemini sent is the real result. It sometimes returns
+/// an intermediate draft before the final render, so:
+/// 1. only images that decode completely are considered;
+/// 2. the one with the most pixels wins (drafts are smaller);
+/// 3. ties go to the later image, since the final render comes last.
+///
+/// When none decode (e.g. an unknown format), the last image is returned as before.
+pub fn select_best_image(images: Vec<String>) -> Option<String> {
+    if images.len() <= 1 {
+        return images.into_iter().next();
+    }
+    let mut best: Option<(usize, u64)> = None;
+    for (i, img) in images.iter().enumerate() {
+        if let Some(area) = complete_image_area(img) {
+            if best.map(|(_, a)| area >= a).unwrap_or(true) {
+                best = Some((i, area));
+            }
+        }
+    }
+    let count = images.len();
+    let index = best.map(|(i, _)| i).unwrap_or(count - 1);
+    debug!(candidates = count, chosen = index, "gemini: picked image from multi-image response");
+    images.into_iter().nth(index)
+}
 
-#[instrument(skip(settings, on_progress), fields(model = "gemini-2.5-flash-image-preview"))]
+#[instrument(skip(settings, cancel, on_progress, on_rate_limited), fields(model = %image_model(settings)))]
 pub async fn generate_image_stream_progress(
     prompt: &str,
     settings: &Settings,
+    cancel: &CancellationToken,
     mut on_progress: impl FnMut(u32, u32),
+    mut on_rate_limited: impl FnMut(u64, u32) + Send,
 ) -> Result<String> {
     // Helper: recursively search for inline image data or data URIs in arbitrary JSON
     fn find_image_data(v: &serde_json::Value) -> Option<String> {
@@ -122,14 +329,15 @@ pub async fn generate_image_stream_progress(
         .or_else(|| std::env::var("GEMINI_API_KEY").ok())
         .context("Gemini API key not set")?;
     
-    let model_id = "gemini-2.5-flash-image-preview";
+    let model_id = image_model(settings);
     let url = format!(
         "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent",
         model_id
     );
     
     // Build parts: prompt text + optional avatar image and description
-    let parts: Vec<serde_json::Value> = vec![serde_json::json!({ "text": build_prompt_with_avatar_text(prompt, settings) })];
+    let mut parts: Vec<serde_json::Value> = vec![serde_json::json!({ "text": build_prompt_with_avatar_text(prompt, settings) })];
+    parts.extend(build_style_reference_parts(settings)?);
     let avatar_part_included = false;
     // For avatar generation, avoid conditioning on the previously saved avatar image
     // so the model is free to produce a fresh portrait.
@@ -141,24 +349,22 @@ pub async fn generate_image_stream_progress(
                 "parts": parts
             }
         ],
-        "generationConfig": {
-            "responseModalities": ["IMAGE"]
-        }
+        "generationConfig": image_generation_config(settings)
     });
     
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(90))
-        .connect_timeout(Duration::from_secs(10))
+    let client = client_builder(settings, STREAM_TIMEOUT_SECS)
         .build()?;
     info!(prompt_len = prompt.len(), parts_len = parts.len(), avatar_part_included, "gemini(stream): sending request");
     let api_key_for_header = api_key.clone();
-    let resp = client
+    let request = client
         .post(url)
         .header("X-goog-api-key", api_key_for_header)
-        .json(&body)
-        .send()
-        .await
-        .context("gemini image request failed")?;
+        .json(&body);
+    let request = send_with_backoff(request, settings, &mut on_rate_limited);
+    let resp = tokio::select! {
+        r = request => r.context("gemini image request failed")?,
+        _ = cancel.cancelled() => return Err(Cancelled.into()),
+    };
     
     if !resp.status().is_success() {
         let status = resp.status();
@@ -167,8 +373,8 @@ pub async fn generate_image_stream_progress(
         return Err(anyhow!("gemini image error: HTTP {} - {}", status, text));
     }
 
-    // Streamed NDJSON; collect last seen inlineData.data or HTTP file URI
-    let mut latest_b64: Option<String> = None;
+    // Streamed NDJSON; collect every inline image (the best one is picked at the end) or an HTTP file URI
+    let mut images: Vec<String> = Vec::new();
     let mut latest_http_uri: Option<String> = None;
     let mut logged_inline_once = false;
     let mut logged_http_once = false;
@@ -178,10 +384,30 @@ pub async fn generate_image_stream_progress(
     
     let mut buf = String::new();
     let mut last_json_debug: Option<String> = None;
+    let mut stream_error: Option<String> = None;
+    let mut refusal_text = String::new();
+    let mut refusal_reason: Option<String> = None;
     let mut stream = resp.bytes_stream();
     
-    while let Some(chunk) = stream.next().await {
-        let bytes = chunk.map_err(|e| anyhow!("gemini stream error: {}", e))?;
+    loop {
+        // Dropping the stream on cancel closes the connection instead of reading it to the end
+        let chunk = tokio::select! {
+            c = stream.next() => c,
+            _ = cancel.cancelled() => {
+                info!(progress, "gemini(stream): cancelled");
+                return Err(Cancelled.into());
+            }
+        };
+        let Some(chunk) = chunk else { break };
+        let bytes = match chunk {
+            Ok(b) => b,
+            Err(e) => {
+                // Keep whatever complete lines we already parsed; recovery happens below
+                warn!(error = %e, progress, have_image = !images.is_empty(), "gemini(stream): connection dropped mid-stream");
+                stream_error = Some(e.to_string());
+                break;
+            }
+        };
         let s = String::from_utf8_lossy(&bytes);
         buf.push_str(&s);
         let mut start = 0usize;
@@ -198,15 +424,22 @@ pub async fn generate_image_stream_progress(
                         if last_json_debug.is_none() {
                             // store a truncated pretty sample for debugging
                             let s = serde_json::to_string(&json).unwrap_or_default();
-                            let sample = if s.len() > 600 { format!("{}...", &s[..600]) } else { s };
+                            let sample = truncate_chars(&s, 600);
                             last_json_debug = Some(sample);
                         }
-                        if let Some(s) = find_image_data(&json) {
-                            if !logged_inline_once {
-                                info!(first_chunk_len = s.len(), "gemini(stream): found inline image data");
-                                logged_inline_once = true;
-                            }
-                            latest_b64 = Some(s);
+                        refusal_text.push_str(&response_text_parts(&json));
+                        if let Some(r) = block_reason(&json) {
+                            refusal_reason = Some(r);
+                        }
+                        let before = images.len();
+                        collect_inline_images(&json, &mut images);
+                        if images.len() == before {
+                            // Data URIs and other shapes the collector doesn't know
+                            images.extend(find_image_data(&json));
+                        }
+                        if images.len() > before && !logged_inline_once {
+                            info!(first_chunk_len = images[before].len(), "gemini(stream): found inline image data");
+                            logged_inline_once = true;
                         }
                         // Try to capture http(s) URIs as a fallback
                         fn find_http_uri(v: &serde_json::Value) -> Option<String> {
@@ -282,20 +515,54 @@ pub async fn generate_image_stream_progress(
         }
     }
     
+    // A dropped stream without any usable image: retry right away without streaming,
+    // ticking progress so the UI doesn't look frozen during recovery
+    if let Some(stream_err) = stream_error {
+        if images.is_empty() && latest_http_uri.is_none() {
+            if let Some(sample) = last_json_debug.as_ref() {
+                info!(sample = %sample, "gemini(stream): last good chunk before drop");
+            }
+            info!("gemini(stream): recovering via non-streaming request");
+            let recovery = generate_image_once(prompt, settings, &mut on_rate_limited);
+            tokio::pin!(recovery);
+            let res = loop {
+                tokio::select! {
+                    r = &mut recovery => { break r; }
+                    _ = cancel.cancelled() => return Err(Cancelled.into()),
+                    _ = tokio::time::sleep(Duration::from_millis(800)) => {
+                        if progress < 98 {
+                            progress += 1;
+                            on_progress(progress, total);
+                        }
+                    }
+                }
+            };
+            return match res {
+                Ok(b64) => {
+                    on_progress(100, total);
+                    info!("gemini(stream): recovered after dropped stream");
+                    Ok(b64)
+                }
+                Err(e) => Err(StreamRecoveryFailed(format!(
+                    "gemini stream dropped ({}); recovery failed: {}",
+                    stream_err, e
+                ))
+                .into()),
+            };
+        }
+    }
+
+    if cancel.is_cancelled() {
+        return Err(Cancelled.into());
+    }
     // Finalize progress
     on_progress(99, total);
-    let out = if let Some(b64) = latest_b64 {
+    let out = if let Some(b64) = select_best_image(images) {
         b64
     } else if let Some(uri) = latest_http_uri {
-        // Best-effort fetch of file URI
-        let mut req = client.get(uri.clone());
-        if uri.contains("generativelanguage.googleapis.com") {
-            req = req.header("X-goog-api-key", api_key.clone());
-        }
-        let bytes = req.send().await
-            .map_err(|e| anyhow!("gemini stream: fetch uri failed: {}", e))?
-            .bytes().await
-            .map_err(|e| anyhow!("gemini stream: read uri bytes failed: {}", e))?;
+        // Best-effort fetch of file URI (cached on disk so retries don't re-download)
+        let bytes = fetch_image_bytes(&client, &uri, Some(&api_key)).await
+            .map_err(|e| anyhow!("gemini stream: fetch uri failed: {}", e))?;
         info!(fetched_bytes = bytes.len(), uri = %uri, "gemini(stream): fetched image via HTTP URI");
         B64.encode(bytes)
     } else {
@@ -304,6 +571,10 @@ pub async fn generate_image_stream_progress(
         } else {
             error!("gemini(stream): no image data received from stream");
         }
+        if let Some(e) = refusal_error(&refusal_text, refusal_reason.as_deref()) {
+            warn!(error = %e, "gemini(stream): model returned text instead of an image");
+            return Err(e);
+        }
         return Err(anyhow!("gemini stream: no image data received"));
     };
     on_progress(100, total);
@@ -311,15 +582,15 @@ pub async fn generate_image_stream_progress(
     Ok(out)
 }
 
-#[instrument(skip(settings), fields(model = "gemini-2.5-flash-image-preview"))]
-pub async fn generate_image_once(prompt: &str, settings: &Settings) -> Result<String> {
+#[instrument(skip(settings, on_rate_limited), fields(model = %image_model(settings)))]
+pub async fn generate_image_once(prompt: &str, settings: &Settings, on_rate_limited: &mut (dyn FnMut(u64, u32) + Send)) -> Result<String> {
     let api_key = settings
         .gemini_api_key
         .clone()
         .or_else(|| std::env::var("GEMINI_API_KEY").ok())
         .context("Gemini API key not set")?;
     
-    let model_id = "gemini-2.5-flash-image-preview";
+    let model_id = image_model(settings);
     let url = format!(
         "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent",
         model_id
@@ -330,6 +601,7 @@ pub async fn generate_image_once(prompt: &str, settings: &Settings) -> Result<St
     if let Some(img_part) = try_build_avatar_image_part(settings) {
         parts.push(img_part);
     }
+    parts.extend(build_style_reference_parts(settings)?);
 
     let body = serde_json::json!({
         "contents": [
@@ -338,20 +610,16 @@ pub async fn generate_image_once(prompt: &str, settings: &Settings) -> Result<St
                 "parts": parts
             }
         ],
-        "generationConfig": {
-            "responseModalities": ["IMAGE"]
-        }
+        "generationConfig": image_generation_config(settings)
     });
     
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(60))
-        .connect_timeout(Duration::from_secs(10))
+    let client = client_builder(settings, REQUEST_TIMEOUT_SECS)
         .build()?;
-    let resp = client
+    let request = client
         .post(&url)
         .header("X-goog-api-key", api_key)
-        .json(&body)
-        .send()
+        .json(&body);
+    let resp = send_with_backoff(request, settings, on_rate_limited)
         .await
         .context("gemini image request failed")?;
     
@@ -389,7 +657,7 @@ pub async fn generate_image_once(prompt: &str, settings: &Settings) -> Result<St
                         let has_media = first_part.get("media").is_some();
                         let has_data_uris = first_part.get("dataUris").is_some() || first_part.get("data_uris").is_some();
                         let has_file_data = first_part.get("fileData").is_some() || first_part.get("file_data").is_some();
-                        let text_sample = first_part.get("text").and_then(|t| t.as_str()).map(|s| if s.len() > 200 { format!("{}...", &s[..200]) } else { s.to_string() });
+                        let text_sample = first_part.get("text").and_then(|t| t.as_str()).map(|s| truncate_chars(s, 200));
                         info!(first_part_keys = ?keys, has_inline, has_media, has_data_uris, has_file_data, text_sample = ?text_sample, "gemini(once): first part diagnostics");
                     }
                 }
@@ -500,7 +768,9 @@ pub async fn generate_image_once(prompt: &str, settings: &Settings) -> Result<St
         }
     }
 
-    if let Some(s) = find_image_data(&value) {
+    let mut images = Vec::new();
+    collect_inline_images(&value, &mut images);
+    if let Some(s) = select_best_image(images).or_else(|| find_image_data(&value)) {
         info!("gemini non-streaming image generation completed");
         return Ok(s);
     }
@@ -553,110 +823,196 @@ pub async fn generate_image_once(prompt: &str, settings: &Settings) -> Result<St
         }
     }
     if let Some(uri) = find_http_uri(&value) {
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(60))
-            .connect_timeout(Duration::from_secs(10))
+        let client = client_builder(settings, REQUEST_TIMEOUT_SECS)
             .build()?;
-        let mut req = client.get(uri.clone());
-        if uri.contains("generativelanguage.googleapis.com") {
-            // Some URIs require the same API key header to fetch
-            if let Some(key) = settings
-                .gemini_api_key
-                .clone()
-                .or_else(|| std::env::var("GEMINI_API_KEY").ok())
-            { req = req.header("X-goog-api-key", key); }
-        }
-        let bytes = req.send().await
-            .map_err(|e| anyhow!("gemini once: fetch uri failed: {}", e))?
-            .bytes().await
-            .map_err(|e| anyhow!("gemini once: read uri bytes failed: {}", e))?;
+        let key = settings
+            .gemini_api_key
+            .clone()
+            .or_else(|| std::env::var("GEMINI_API_KEY").ok());
+        let bytes = fetch_image_bytes(&client, &uri, key.as_deref()).await
+            .map_err(|e| anyhow!("gemini once: fetch uri failed: {}", e))?;
         info!("gemini non-streaming image fetched via file URI");
         return Ok(B64.encode(bytes));
     }
 
-    // Retry once with stricter guidance and extra diagnostics
-    info!("gemini(once): no image found, retrying with stricter IMAGE-only guidance");
-    let mut retry_parts: Vec<serde_json::Value> = vec![serde_json::json!({ "text": build_prompt_with_avatar_text(prompt, settings) })];
-    if let Some(img_part) = try_build_avatar_image_part(settings) {
-        retry_parts.push(img_part);
-    }
-    let retry_body = serde_json::json!({
-        "contents": [
-            { "role": "user", "parts": retry_parts }
-        ],
-        // Nudge the model harder toward emitting an image part only
-        "systemInstruction": { "parts": [ { "text": "Return exactly one IMAGE. Do not include any text parts. If unsafe, return an IMAGE-only safe illustration." } ] },
-        "generationConfig": {
-            "responseModalities": ["IMAGE"],
-            "temperature": 0.1
+    // Retry with stricter guidance and extra diagnostics
+    let max_retries = settings.image_gen_max_retries.unwrap_or(DEFAULT_IMAGE_GEN_RETRIES);
+    let mut last_retry: Option<serde_json::Value> = None;
+    for attempt in 1..=max_retries {
+        info!(attempt, max_retries, "gemini(once): no image found, retrying with stricter IMAGE-only guidance");
+        let mut retry_parts: Vec<serde_json::Value> = vec![serde_json::json!({ "text": build_prompt_with_avatar_text(prompt, settings) })];
+        if let Some(img_part) = try_build_avatar_image_part(settings) {
+            retry_parts.push(img_part);
         }
-    });
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(60))
-        .connect_timeout(Duration::from_secs(10))
-        .build()?;
-    let retry_resp = client
-        .post(&url)
-        .header("X-goog-api-key", settings
-            .gemini_api_key
-            .clone()
-            .or_else(|| std::env::var("GEMINI_API_KEY").ok())
-            .context("Gemini API key not set")?)
-        .json(&retry_body)
-        .send()
-        .await
-        .context("gemini image retry request failed")?;
-    if !retry_resp.status().is_success() {
-        let status = retry_resp.status();
-        let text = retry_resp.text().await.unwrap_or_else(|_| "<no body>".into());
-        error!(http = %status, body = %text, "gemini image error (once retry)");
-        return Err(anyhow!("gemini image failed (retry): HTTP {} - {}", status, text));
-    }
-    let retry_value: serde_json::Value = retry_resp.json().await
-        .context("gemini image retry parse error")?;
-    if let Some(s) = find_image_data(&retry_value) {
-        info!("gemini non-streaming image generation completed (retry)");
-        return Ok(s);
-    }
-    if let Some(uri) = find_http_uri(&retry_value) {
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(60))
-            .connect_timeout(Duration::from_secs(10))
+        retry_parts.extend(build_style_reference_parts(settings)?);
+        let mut retry_body = serde_json::json!({
+            "contents": [
+                { "role": "user", "parts": retry_parts }
+            ],
+            // Nudge the model harder toward emitting an image part only
+            "systemInstruction": { "parts": [ { "text": "Return exactly one IMAGE. Do not include any text parts. If unsafe, return an IMAGE-only safe illustration." } ] },
+            "generationConfig": image_generation_config(settings)
+        });
+        retry_body["generationConfig"]["temperature"] = serde_json::json!(0.1);
+        let client = client_builder(settings, REQUEST_TIMEOUT_SECS)
             .build()?;
-        let mut req = client.get(uri.clone());
-        if uri.contains("generativelanguage.googleapis.com") {
-            if let Some(key) = settings
+        let request = client
+            .post(&url)
+            .header("X-goog-api-key", settings
                 .gemini_api_key
                 .clone()
                 .or_else(|| std::env::var("GEMINI_API_KEY").ok())
-            { req = req.header("X-goog-api-key", key); }
+                .context("Gemini API key not set")?)
+            .json(&retry_body);
+        let retry_resp = send_with_backoff(request, settings, on_rate_limited)
+            .await
+            .context("gemini image retry request failed")?;
+        if !retry_resp.status().is_success() {
+            let status = retry_resp.status();
+            let text = retry_resp.text().await.unwrap_or_else(|_| "<no body>".into());
+            error!(http = %status, body = %text, attempt, "gemini image error (once retry)");
+            return Err(anyhow!("gemini image failed (retry {}): HTTP {} - {}", attempt, status, text));
+        }
+        let retry_value: serde_json::Value = retry_resp.json().await
+            .context("gemini image retry parse error")?;
+        if let Some(s) = find_image_data(&retry_value) {
+            info!(attempt, "gemini non-streaming image generation completed (retry)");
+            return Ok(s);
+        }
+        if let Some(uri) = find_http_uri(&retry_value) {
+            let client = client_builder(settings, REQUEST_TIMEOUT_SECS)
+                .build()?;
+            let key = settings
+                .gemini_api_key
+                .clone()
+                .or_else(|| std::env::var("GEMINI_API_KEY").ok());
+            let bytes = fetch_image_bytes(&client, &uri, key.as_deref()).await
+                .map_err(|e| anyhow!("gemini once retry: fetch uri failed: {}", e))?;
+            info!(attempt, "gemini non-streaming image fetched via file URI (retry)");
+            return Ok(B64.encode(bytes));
         }
-        let bytes = req.send().await
-            .map_err(|e| anyhow!("gemini once retry: fetch uri failed: {}", e))?
-            .bytes().await
-            .map_err(|e| anyhow!("gemini once retry: read uri bytes failed: {}", e))?;
-        info!("gemini non-streaming image fetched via file URI (retry)");
-        return Ok(B64.encode(bytes));
-    }
 
-    // Log a compact sample of the retry JSON to aid diagnosis
-    let sample = serde_json::to_string(&retry_value).unwrap_or_default();
-    let sample = if sample.len() > 800 { format!("{}...", &sample[..800]) } else { sample };
-    error!(sample = %sample, "gemini(once): no image data in retry response");
-    Err(anyhow!("gemini image: no inline image data in response (after retry)"))
+        // Log a compact sample of the retry JSON to aid diagnosis
+        let sample = serde_json::to_string(&retry_value).unwrap_or_default();
+        let sample = truncate_chars(&sample, 800);
+        error!(sample = %sample, attempt, "gemini(once): no image data in retry response");
+        last_retry = Some(retry_value);
+    }
+    // Prefer the last retry's explanation, falling back to the first response's
+    let refusal = last_retry
+        .as_ref()
+        .and_then(|v| refusal_error(&response_text_parts(v), block_reason(v).as_deref()))
+        .or_else(|| refusal_error(&response_text_parts(&value), block_reason(&value).as_deref()));
+    if let Some(e) = refusal {
+        warn!(error = %e, "gemini(once): model returned text instead of an image");
+        return Err(e);
+    }
+    if max_retries == 0 {
+        return Err(anyhow!("gemini image: no inline image data in response"));
+    }
+    Err(anyhow!("gemini image: no inline image data in response (after {} retries)", max_retries))
 }
 
+/// Stream an image, falling back to a single request when the stream fails. Once `cancel`
+/// fires, in-flight requests are dropped and the error is `"cancelled"`. Rate-limited
+/// requests are retried; `on_rate_limited(secs, attempt)` fires before each wait.
 pub async fn generate_image_with_progress(
     prompt: &str,
     settings: &Settings,
+    cancel: &CancellationToken,
     on_progress: impl FnMut(u32, u32),
+    mut on_rate_limited: impl FnMut(u64, u32) + Send,
 ) -> Result<String, String> {
-    match generate_image_stream_progress(prompt, settings, on_progress).await {
+    match generate_image_stream_progress(prompt, settings, cancel, on_progress, &mut on_rate_limited).await {
         Ok(b64) => Ok(b64),
-        Err(_) => generate_image_once(prompt, settings)
-            .await
-            .map_err(|e| format!("gemini image failed: {}", e)),
+        Err(e) if e.downcast_ref::<Cancelled>().is_some() => Err(e.to_string()),
+        Err(e) if e.downcast_ref::<StreamRecoveryFailed>().is_some() => {
+            Err(format!("gemini image failed: {}", e))
+        }
+        Err(e) if e.downcast_ref::<ImageRefused>().is_some() => Err(e.to_string()),
+        Err(_) => tokio::select! {
+            r = generate_image_once(prompt, settings, &mut on_rate_limited) => r.map_err(|e| format!("gemini image failed: {}", e)),
+            _ = cancel.cancelled() => Err(Cancelled.to_string()),
+        },
+    }
+}
+
+/// Plain text generation (optionally multimodal via extra `parts`) returning the concatenated text parts.
+#[instrument(skip(parts, settings), fields(model = %text_model(settings)))]
+pub async fn gemini_generate(parts: Vec<serde_json::Value>, settings: &Settings) -> Result<String> {
+    let api_key = settings
+        .gemini_api_key
+        .clone()
+        .or_else(|| std::env::var("GEMINI_API_KEY").ok())
+        .context("Gemini API key not set")?;
+
+    let model_id = text_model(settings);
+    let url = format!(
+        "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent",
+        model_id
+    );
+
+    let mut body = serde_json::json!({
+        "contents": [ { "role": "user", "parts": parts } ]
+    });
+    // Only send the knobs that are configured so the API defaults apply otherwise
+    let mut generation_config = serde_json::Map::new();
+    if let Some(t) = settings.gemini_temperature {
+        generation_config.insert("temperature".into(), serde_json::json!(t));
+    }
+    if let Some(n) = settings.gemini_max_output_tokens {
+        generation_config.insert("maxOutputTokens".into(), serde_json::json!(n));
+    }
+    if !generation_config.is_empty() {
+        body["generationConfig"] = serde_json::Value::Object(generation_config);
+    }
+
+    let client = client_builder(settings, REQUEST_TIMEOUT_SECS)
+        .build()?;
+    let request = client
+        .post(&url)
+        .header("X-goog-api-key", api_key)
+        .json(&body);
+    let resp = send_with_backoff(request, settings, &mut |_, _| {})
+        .await
+        .context("gemini text request failed")?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_else(|_| "<no body>".into());
+        error!(http = %status, body = %text, "gemini text error");
+        return Err(anyhow!("gemini text error: HTTP {} - {}", status, text));
     }
+
+    let value: serde_json::Value = resp.json().await.context("gemini text parse error")?;
+    let text = value
+        .get("candidates")
+        .and_then(|c| c.as_array())
+        .and_then(|c| c.first())
+        .and_then(|c| c.get("content"))
+        .and_then(|c| c.get("parts"))
+        .and_then(|p| p.as_array())
+        .map(|parts| {
+            parts
+                .iter()
+                .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                .collect::<Vec<_>>()
+                .join("")
+        })
+        .unwrap_or_default();
+    if text.trim().is_empty() {
+        return Err(anyhow!("gemini text: empty response"));
+    }
+    Ok(text.trim().to_string())
+}
+
+/// Ask a vision-capable Gemini model for screen-reader alt text of a comic image.
+pub async fn describe_image(image_b64: &str, mime: &str, settings: &Settings) -> Result<String> {
+    let parts = vec![
+        serde_json::json!({ "text": "Write concise alt text (at most 3 sentences) describing this comic for a screen reader. Summarize each panel in order and include any speech bubble or caption text verbatim. Reply with the alt text only." }),
+        serde_json::json!({ "inlineData": { "mimeType": mime, "data": image_b64 } }),
+    ];
+    gemini_generate(parts, settings).await
 }
 
 fn build_prompt_with_avatar_text(prompt: &str, settings: &Settings) -> String {
@@ -704,6 +1060,25 @@ fn try_build_avatar_image_part(settings: &Settings) -> Option<serde_json::Value>
     }))
 }
 
+// Reference image for style transfer: guidance text followed by the image itself. A
+// configured reference that can't be read is an error, since the job records it as applied.
+fn build_style_reference_parts(settings: &Settings) -> Result<Vec<serde_json::Value>> {
+    let Some(path) = settings.style_reference_image_path.as_ref() else {
+        return Ok(Vec::new());
+    };
+    let p = Path::new(path);
+    let bytes = fs::read(p).map_err(|e| anyhow!("{} ({}): {}", STYLE_REFERENCE_UNREADABLE_MARKER, path, e))?;
+    let mime = match p.extension().and_then(|e| e.to_str()).map(|s| s.to_ascii_lowercase()) {
+        Some(ext) if ext == "jpg" || ext == "jpeg" => "image/jpeg",
+        Some(ext) if ext == "webp" => "image/webp",
+        _ => "image/png",
+    };
+    Ok(vec![
+        serde_json::json!({ "text": "Style reference: match the art style of the provided reference image (line work, palette, shading). Do not copy its content or characters." }),
+        serde_json::json!({ "inlineData": { "mimeType": mime, "data": B64.encode(bytes) } }),
+    ])
+}
+
 // Build a prompt to cartoonify a provided real photo into a stylized avatar
 pub fn build_cartoonify_prompt() -> String {
     r#"Task: Transform the provided person photo into a clean, stylized cartoon portrait avatar.
@@ -721,11 +1096,12 @@ Deliverable:
 - One portrait image in cartoon style of the same person in the photo."#.to_string()
 }
 
-#[instrument(skip(settings, on_progress), fields(model = "gemini-2.5-flash-image-preview"))]
+#[instrument(skip(settings, cancel, on_progress), fields(model = %image_model(settings)))]
 pub async fn cartoonify_image_stream_progress(
     source_image_b64: &str,
     source_mime: &str,
     settings: &Settings,
+    cancel: &CancellationToken,
     mut on_progress: impl FnMut(u32, u32),
 ) -> Result<String> {
     // Reuse streaming machinery; change only the request parts
@@ -735,7 +1111,7 @@ pub async fn cartoonify_image_stream_progress(
         .or_else(|| std::env::var("GEMINI_API_KEY").ok())
         .context("Gemini API key not set")?;
 
-    let model_id = "gemini-2.5-flash-image-preview";
+    let model_id = image_model(settings);
     let url = format!(
         "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent",
         model_id
@@ -753,18 +1129,19 @@ pub async fn cartoonify_image_stream_progress(
         "generationConfig": { "responseModalities": ["IMAGE"] }
     });
 
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(90))
-        .connect_timeout(Duration::from_secs(10))
+    let client = client_builder(settings, STREAM_TIMEOUT_SECS)
         .build()?;
     info!(parts_len = 2usize, "gemini(stream cartoonify): sending request");
-    let resp = client
+    let request = client
         .post(url)
         .header("X-goog-api-key", api_key.clone())
-        .json(&body)
-        .send()
-        .await
-        .context("gemini cartoonify image request failed")?;
+        .json(&body);
+    let mut on_rate_limited = |_, _| {};
+    let request = send_with_backoff(request, settings, &mut on_rate_limited);
+    let resp = tokio::select! {
+        r = request => r.context("gemini cartoonify image request failed")?,
+        _ = cancel.cancelled() => return Err(Cancelled.into()),
+    };
 
     if !resp.status().is_success() {
         let status = resp.status();
@@ -784,7 +1161,12 @@ pub async fn cartoonify_image_stream_progress(
     let mut last_json_debug: Option<String> = None;
     let mut stream = resp.bytes_stream();
 
-    while let Some(chunk) = stream.next().await {
+    loop {
+        let chunk = tokio::select! {
+            c = stream.next() => c,
+            _ = cancel.cancelled() => return Err(Cancelled.into()),
+        };
+        let Some(chunk) = chunk else { break };
         let bytes = chunk.map_err(|e| anyhow!("gemini stream error: {}", e))?;
         let s = String::from_utf8_lossy(&bytes);
         buf.push_str(&s);
@@ -797,7 +1179,7 @@ pub async fn cartoonify_image_stream_progress(
                     if let Ok(json) = serde_json::from_str::<serde_json::Value>(line) {
                         if last_json_debug.is_none() {
                             let s = serde_json::to_string(&json).unwrap_or_default();
-                            let sample = if s.len() > 600 { format!("{}...", &s[..600]) } else { s };
+                            let sample = truncate_chars(&s, 600);
                             last_json_debug = Some(sample);
                         }
                         // reuse extractors
@@ -885,16 +1267,15 @@ pub async fn cartoonify_image_stream_progress(
         if start > 0 { buf = buf[start..].to_string(); }
     }
 
+    if cancel.is_cancelled() {
+        return Err(Cancelled.into());
+    }
     on_progress(99, total);
     let out = if let Some(b64) = latest_b64 {
         b64
     } else if let Some(uri) = latest_http_uri {
-        let mut req = client.get(uri.clone());
-        if uri.contains("generativelanguage.googleapis.com") { req = req.header("X-goog-api-key", api_key.clone()); }
-        let bytes = req.send().await
-            .map_err(|e| anyhow!("gemini cartoonify stream: fetch uri failed: {}", e))?
-            .bytes().await
-            .map_err(|e| anyhow!("gemini cartoonify stream: read uri bytes failed: {}", e))?;
+        let bytes = fetch_image_bytes(&client, &uri, Some(&api_key)).await
+            .map_err(|e| anyhow!("gemini cartoonify stream: fetch uri failed: {}", e))?;
         info!(fetched_bytes = bytes.len(), uri = %uri, "gemini(stream cartoonify): fetched image via HTTP URI");
         B64.encode(bytes)
     } else {
@@ -919,7 +1300,7 @@ pub async fn generate_image_once_cartoonify(
         .or_else(|| std::env::var("GEMINI_API_KEY").ok())
         .context("Gemini API key not set")?;
 
-    let model_id = "gemini-2.5-flash-image-preview";
+    let model_id = image_model(settings);
     let url = format!(
         "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent",
         model_id
@@ -935,15 +1316,13 @@ pub async fn generate_image_once_cartoonify(
         "generationConfig": { "responseModalities": ["IMAGE"] }
     });
 
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(60))
-        .connect_timeout(Duration::from_secs(10))
+    let client = client_builder(settings, REQUEST_TIMEOUT_SECS)
         .build()?;
-    let resp = client
+    let request = client
         .post(&url)
         .header("X-goog-api-key", api_key)
-        .json(&body)
-        .send()
+        .json(&body);
+    let resp = send_with_backoff(request, settings, &mut |_, _| {})
         .await
         .context("gemini cartoonify image request failed")?;
 
@@ -1004,20 +1383,16 @@ pub async fn generate_image_once_cartoonify(
         }
     }
     if let Some(uri) = find_http_uri(&value) {
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(60))
-            .connect_timeout(Duration::from_secs(10))
+        let client = client_builder(settings, REQUEST_TIMEOUT_SECS)
             .build()?;
-        let bytes = client.get(uri.clone()).send().await
-            .map_err(|e| anyhow!("gemini once cartoonify: fetch uri failed: {}", e))?
-            .bytes().await
-            .map_err(|e| anyhow!("gemini once cartoonify: read uri bytes failed: {}", e))?;
+        let bytes = fetch_image_bytes(&client, &uri, None).await
+            .map_err(|e| anyhow!("gemini once cartoonify: fetch uri failed: {}", e))?;
         info!("gemini non-streaming cartoonify fetched via file URI");
         return Ok(B64.encode(bytes));
     }
 
     let sample = serde_json::to_string(&value).unwrap_or_default();
-    let sample = if sample.len() > 800 { format!("{}...", &sample[..800]) } else { sample };
+    let sample = truncate_chars(&sample, 800);
     error!(sample = %sample, "gemini(once cartoonify): no image data in response");
     Err(anyhow!("gemini image: no inline image data in response"))
 }
@@ -1026,32 +1401,138 @@ pub async fn cartoonify_image_with_progress(
     source_image_b64: &str,
     source_mime: &str,
     settings: &Settings,
+    cancel: &CancellationToken,
     on_progress: impl FnMut(u32, u32),
 ) -> Result<String, String> {
-    match cartoonify_image_stream_progress(source_image_b64, source_mime, settings, on_progress).await {
+    match cartoonify_image_stream_progress(source_image_b64, source_mime, settings, cancel, on_progress).await {
         Ok(b64) => Ok(b64),
-        Err(_) => generate_image_once_cartoonify(source_image_b64, source_mime, settings)
-            .await
-            .map_err(|e| format!("gemini cartoonify failed: {}", e)),
+        Err(e) if e.downcast_ref::<Cancelled>().is_some() => Err(e.to_string()),
+        Err(_) => tokio::select! {
+            r = generate_image_once_cartoonify(source_image_b64, source_mime, settings) => {
+                r.map_err(|e| format!("gemini cartoonify failed: {}", e))
+            }
+            _ = cancel.cancelled() => Err(Cancelled.to_string()),
+        },
+    }
+}
+
+/// Cheap check that the Gemini key works: fetch the image model's metadata, which spends
+/// no generation quota.
+pub async fn check_api_key(settings: &Settings) -> Result<()> {
+    let api_key = settings
+        .gemini_api_key
+        .clone()
+        .or_else(|| std::env::var("GEMINI_API_KEY").ok())
+        .filter(|k| !k.trim().is_empty())
+        .context("Gemini API key not set")?;
+    let url = format!(
+        "https://generativelanguage.googleapis.com/v1beta/models/{}",
+        image_model(settings)
+    );
+    let client = client_builder(settings, REQUEST_TIMEOUT_SECS).build()?;
+    let resp = client
+        .get(url)
+        .header("X-goog-api-key", api_key)
+        .send()
+        .await
+        .context("gemini request failed")?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_else(|_| "<no body>".into());
+        return Err(anyhow!("gemini error: HTTP {} - {}", status, text));
+    }
+    Ok(())
+}
+
+// Only the delta-seconds form of Retry-After is used; an HTTP date falls back to backoff
+fn retry_after_secs(resp: &reqwest::Response) -> Option<u64> {
+    resp.headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Send `request`, retrying HTTP 429 and 503 up to `rate_limit_max_retries` times. Waits
+/// follow `Retry-After` when given, else exponential backoff; `on_wait(secs, attempt)` is
+/// called before each one. The final response is returned as-is for the caller to handle.
+async fn send_with_backoff(
+    request: reqwest::RequestBuilder,
+    settings: &Settings,
+    on_wait: &mut (dyn FnMut(u64, u32) + Send),
+) -> reqwest::Result<reqwest::Response> {
+    let max_retries = settings.rate_limit_max_retries.unwrap_or(DEFAULT_RATE_LIMIT_RETRIES);
+    let mut attempt = 0u32;
+    loop {
+        // Bodies here are always buffered JSON, so this only fails for streaming bodies
+        let Some(req) = request.try_clone() else { return request.send().await };
+        let resp = req.send().await?;
+        let status = resp.status();
+        let retryable = status == reqwest::StatusCode::TOO_MANY_REQUESTS || status == reqwest::StatusCode::SERVICE_UNAVAILABLE;
+        if !retryable || attempt >= max_retries {
+            return Ok(resp);
+        }
+        attempt += 1;
+        let backoff = RATE_LIMIT_BASE_DELAY_SECS.saturating_mul(1 << (attempt - 1).min(16));
+        let delay = retry_after_secs(&resp).unwrap_or(backoff).min(RATE_LIMIT_MAX_DELAY_SECS);
+        warn!(http = %status, attempt, max_retries, delay_secs = delay, "gemini rate limited; retrying");
+        on_wait(delay, attempt);
+        tokio::time::sleep(Duration::from_secs(delay)).await;
     }
 }
 
 // Nano-Banana integration
+/// A nano-banana failure, keeping the HTTP status and response body separate so callers
+/// can report them apart from any fallback outcome.
+#[derive(Debug, Clone, Serialize, Deserialize, thiserror::Error)]
+#[error("{message}")]
+pub struct NanoBananaError {
+    pub status: Option<u16>,
+    pub body: Option<String>,
+    pub message: String,
+}
+
+impl NanoBananaError {
+    fn new(message: impl Into<String>) -> Self {
+        Self { status: None, body: None, message: message.into() }
+    }
+}
+
+/// Whether the nano-banana server answers at all. Any HTTP response counts, since servers
+/// differ in what they serve at the root; the status code is returned.
+pub async fn nano_banana_ping(settings: &Settings) -> Result<u16, String> {
+    let base = settings
+        .nano_banana_base_url
+        .as_ref()
+        .ok_or_else(|| "nano-banana base URL not set in settings".to_string())?;
+    let base = normalize_base_url(base)?;
+    let client = client_builder(settings, REQUEST_TIMEOUT_SECS)
+        .build()
+        .map_err(|e| format!("http client error: {e}"))?;
+    let mut req = client.get(base);
+    if let Some(key) = &settings.nano_banana_api_key {
+        req = req.header("X-API-Key", key);
+    }
+    let resp = req.send().await.map_err(|e| format!("nano-banana request failed: {e}"))?;
+    Ok(resp.status().as_u16())
+}
+
 pub async fn nano_banana_generate_image(
     storyboard_text: &str,
     settings: &Settings,
-) -> Result<String, String> {
+) -> Result<String, NanoBananaError> {
     let base = settings
         .nano_banana_base_url
         .as_ref()
-        .ok_or_else(|| "nano-banana base URL not set in settings".to_string())?;
+        .ok_or_else(|| NanoBananaError::new("nano-banana base URL not set in settings"))?;
     
-    let url = format!("{}/generate", base.trim_end_matches('/'));
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(60))
-        .connect_timeout(Duration::from_secs(10))
+    let base = normalize_base_url(base).map_err(NanoBananaError::new)?;
+    let url = format!("{}/generate", base);
+    let client = client_builder(settings, REQUEST_TIMEOUT_SECS)
         .build()
-        .map_err(|e| format!("http client error: {e}"))?;
+        .map_err(|e| NanoBananaError::new(format!("http client error: {e}")))?;
     
     // Inject avatar guidance into storyboard text so downstream renderer can try to respect it
     let mut storyboard_plus = storyboard_text.to_string();
@@ -1060,25 +1541,34 @@ pub async fn nano_banana_generate_image(
         storyboard_plus.push_str(desc);
     }
 
-    let mut req = client.post(url).json(&serde_json::json!({
+    let mut payload = serde_json::json!({
         "storyboard": storyboard_plus,
-    }));
+    });
+    // Nano-banana servers commonly support only a few basic shapes
+    if let Some(r) = settings.image_aspect_ratio.as_deref().filter(|r| !r.trim().is_empty()) {
+        payload["aspect_ratio"] = serde_json::json!(closest_aspect_ratio(r, &["1:1", "4:3", "3:4", "16:9", "9:16"]));
+    }
+    let mut req = client.post(url).json(&payload);
     
     if let Some(key) = &settings.nano_banana_api_key {
         req = req.header("X-API-Key", key);
     }
     
     let resp = req.send().await
-        .map_err(|e| format!("nano-banana request failed: {e}"))?;
+        .map_err(|e| NanoBananaError::new(format!("nano-banana request failed: {e}")))?;
     
     if !resp.status().is_success() {
         let status = resp.status();
         let text = resp.text().await.unwrap_or_else(|_| "<no body>".into());
-        return Err(format!("nano-banana error: HTTP {} - {}", status, text));
+        return Err(NanoBananaError {
+            status: Some(status.as_u16()),
+            body: Some(text),
+            message: format!("nano-banana error: HTTP {}", status),
+        });
     }
     
     let value: serde_json::Value = resp.json().await
-        .map_err(|e| format!("nano-banana parse error: {e}"))?;
+        .map_err(|e| NanoBananaError::new(format!("nano-banana parse error: {e}")))?;
     
     if let Some(s) = value.get("image_base64").and_then(|v| v.as_str()) {
         return Ok(s.to_string());
@@ -1088,5 +1578,62 @@ pub async fn nano_banana_generate_image(
         return Ok(s.to_string());
     }
     
-    Err("nano-banana: no image in response".to_string())
-}
\ No newline at end of file
+    Err(NanoBananaError::new("nano-banana: no image in response"))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unreadable_style_reference_is_an_error() {
+        let missing = std::env::temp_dir().join(format!("toonana-style-{}.png", uuid::Uuid::new_v4().simple()));
+        let settings = Settings { style_reference_image_path: Some(missing.display().to_string()), ..Default::default() };
+        let err = build_style_reference_parts(&settings).unwrap_err().to_string();
+        assert!(err.starts_with(STYLE_REFERENCE_UNREADABLE_MARKER), "{err}");
+        assert!(build_style_reference_parts(&Settings::default()).unwrap().is_empty());
+    }
+
+    fn png_b64(width: u32, height: u32, shade: u8) -> String {
+        let img = image::RgbImage::from_pixel(width, height, image::Rgb([shade, 120, 40]));
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+        B64.encode(bytes.into_inner())
+    }
+
+    #[test]
+    fn multi_image_response_picks_the_largest_complete_image() {
+        let draft = png_b64(16, 16, 200);
+        let fin = png_b64(64, 48, 200);
+        // A bigger image cut off mid-stream must not win
+        let full = png_b64(128, 128, 200);
+        let truncated = full[..full.len() / 2].to_string();
+        let response = serde_json::json!({
+            "candidates": [{
+                "content": { "parts": [
+                    { "text": "Here is a draft." },
+                    { "inlineData": { "mimeType": "image/png", "data": draft } },
+                    { "inlineData": { "mimeType": "image/png", "data": fin } },
+                    { "inline_data": { "mime_type": "image/png", "data": truncated } },
+                ] }
+            }]
+        });
+
+        let mut images = Vec::new();
+        collect_inline_images(&response, &mut images);
+        assert_eq!(images.len(), 3);
+        assert_eq!(select_best_image(images), Some(fin));
+    }
+
+    #[test]
+    fn equal_sized_images_resolve_to_the_last_one() {
+        let first = png_b64(32, 32, 10);
+        let last = png_b64(32, 32, 250);
+        assert_eq!(select_best_image(vec![first, last.clone()]), Some(last));
+    }
+
+    #[test]
+    fn undecodable_images_fall_back_to_the_last_one() {
+        let images = vec!["bm90IGFuIGltYWdl".to_string(), "c3RpbGwgbm90".to_string()];
+        assert_eq!(select_best_image(images), Some("c3RpbGwgbm90".to_string()));
+    }
+}
@@ -0,0 +1,148 @@
+use anyhow::{anyhow, Context, Result};
+use futures_util::StreamExt;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info};
+
+use crate::utils::ensure_data_dir;
+
+pub fn image_cache_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("cache").join("images")
+}
+
+fn cache_key(url: &str) -> String {
+    format!("{:x}", Sha256::digest(url.as_bytes()))
+}
+
+const GEMINI_FILES_HOST: &str = "generativelanguage.googleapis.com";
+
+// Only https URLs on the Gemini API host itself get the API key; the URL comes from the
+// caller, so a substring match would hand the key to any host that mentions it
+fn sends_api_key(url: &str) -> bool {
+    reqwest::Url::parse(url)
+        .map(|u| u.scheme() == "https" && u.host_str() == Some(GEMINI_FILES_HOST))
+        .unwrap_or(false)
+}
+
+fn download_request(client: &reqwest::Client, url: &str, api_key: Option<&str>) -> reqwest::RequestBuilder {
+    let req = client.get(url);
+    match api_key {
+        // Some URIs require the same API key header to fetch
+        Some(key) if sends_api_key(url) => req.header("X-goog-api-key", key),
+        _ => req,
+    }
+}
+
+pub fn cached_path_for_url(data_dir: &Path, url: &str) -> PathBuf {
+    image_cache_dir(data_dir).join(cache_key(url))
+}
+
+/// Download `url` into the image cache (streaming to disk) unless it's already there.
+/// Returns the cached file path.
+pub async fn fetch_to_cache(
+    client: &reqwest::Client,
+    data_dir: &Path,
+    url: &str,
+    api_key: Option<&str>,
+) -> Result<PathBuf> {
+    let path = cached_path_for_url(data_dir, url);
+    if tokio::fs::metadata(&path).await.map(|m| m.len() > 0).unwrap_or(false) {
+        debug!(url = %url, path = %path.display(), "image cache: hit");
+        return Ok(path);
+    }
+    tokio::fs::create_dir_all(image_cache_dir(data_dir))
+        .await
+        .context("create image cache dir")?;
+
+    let resp = download_request(client, url, api_key).send().await.context("image download request failed")?;
+    if !resp.status().is_success() {
+        return Err(anyhow!("image download failed: HTTP {}", resp.status()));
+    }
+
+    // Stream into a temp file and rename so a dropped connection never leaves a truncated cache entry
+    // Unique per fetch, so concurrent downloads of one URL don't write into the same file
+    let tmp_path = path.with_extension(format!("{}.part", uuid::Uuid::new_v4().simple()));
+    let mut file = tokio::fs::File::create(&tmp_path)
+        .await
+        .context("create cache file")?;
+    let mut stream = resp.bytes_stream();
+    let mut written: u64 = 0;
+    while let Some(chunk) = stream.next().await {
+        let bytes = match chunk {
+            Ok(b) => b,
+            Err(e) => {
+                drop(file);
+                let _ = tokio::fs::remove_file(&tmp_path).await;
+                return Err(anyhow!("image download stream error: {}", e));
+            }
+        };
+        file.write_all(&bytes).await.context("write cache file")?;
+        written += bytes.len() as u64;
+    }
+    file.flush().await.context("flush cache file")?;
+    drop(file);
+    tokio::fs::rename(&tmp_path, &path)
+        .await
+        .context("finalize cache file")?;
+    info!(url = %url, bytes = written, path = %path.display(), "image cache: stored");
+    Ok(path)
+}
+
+/// Fetch image bytes by URL through the on-disk cache. Falls back to a plain download
+/// when the data dir can't be resolved.
+pub async fn fetch_image_bytes(
+    client: &reqwest::Client,
+    url: &str,
+    api_key: Option<&str>,
+) -> Result<Vec<u8>> {
+    match ensure_data_dir() {
+        Ok(data_dir) => {
+            let path = fetch_to_cache(client, &data_dir, url, api_key).await?;
+            tokio::fs::read(&path).await.context("read cached image")
+        }
+        Err(_) => {
+            let bytes = download_request(client, url, api_key)
+                .send()
+                .await
+                .context("image download request failed")?
+                .bytes()
+                .await
+                .context("read image bytes failed")?;
+            Ok(bytes.to_vec())
+        }
+    }
+}
+
+/// Remove every cached image. Returns the number of files deleted.
+pub async fn clear_cache(data_dir: &Path) -> Result<u64> {
+    let dir = image_cache_dir(data_dir);
+    let mut removed = 0u64;
+    let mut rd = match tokio::fs::read_dir(&dir).await {
+        Ok(rd) => rd,
+        Err(_) => return Ok(0),
+    };
+    while let Some(ent) = rd.next_entry().await? {
+        let path = ent.path();
+        if path.is_file() && tokio::fs::remove_file(&path).await.is_ok() {
+            removed += 1;
+        }
+    }
+    info!(removed, "image cache: cleared");
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sends_api_key;
+
+    #[test]
+    fn api_key_only_goes_to_the_gemini_host_over_https() {
+        assert!(sends_api_key("https://generativelanguage.googleapis.com/v1beta/files/abc:download"));
+        assert!(!sends_api_key("http://generativelanguage.googleapis.com/v1beta/files/abc"));
+        assert!(!sends_api_key("https://evil.example/?generativelanguage.googleapis.com"));
+        assert!(!sends_api_key("https://generativelanguage.googleapis.com.evil.example/x"));
+        assert!(!sends_api_key("https://evil.example/generativelanguage.googleapis.com/x"));
+        assert!(!sends_api_key("not a url"));
+    }
+}
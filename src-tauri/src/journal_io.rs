@@ -0,0 +1,362 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+use crate::comic_export::ExportFailure;
+use crate::database::{
+    all_entry_ids, body_hash, entry_comic_image_paths, find_entry_by_body_hash, get_entry, upsert_entry, EntryUpsert,
+};
+
+/// On-disk layout of a journal being imported or exported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalFormat {
+    /// A folder of `.md` files, one entry each, with optional `---` front matter
+    /// (`date`/`created_at`, `mood`, `tags`)
+    MarkdownDir,
+    /// One JSON file holding an array of `{ body, created_at, mood, tags }` objects
+    JsonArray,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportFailure {
+    /// File (and `#index` for JSON items) the entry came from
+    pub source: String,
+    pub error: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub imported: usize,
+    /// Empty bodies and bodies already in the journal (matched by content hash)
+    pub skipped: usize,
+    pub errors: Vec<ImportFailure>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JournalExportSummary {
+    pub path: String,
+    pub exported: usize,
+    pub failed: Vec<ExportFailure>,
+}
+
+// One entry as written by `export_entries`; the field names are the ones import reads
+#[derive(Debug, Serialize)]
+struct ExportedEntry {
+    id: String,
+    created_at: String,
+    mood: Option<String>,
+    tags: Option<serde_json::Value>,
+    body: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    images: Option<Vec<String>>,
+}
+
+// One entry as read from the source, before encryption
+#[derive(Debug, Default, Deserialize)]
+struct ImportedEntry {
+    body: String,
+    #[serde(default, alias = "date")]
+    created_at: Option<String>,
+    #[serde(default)]
+    mood: Option<String>,
+    #[serde(default)]
+    tags: Option<serde_json::Value>,
+}
+
+/// Import every entry found at `path`. Bodies are encrypted like any other save and
+/// `created_at` is kept from the source. Entries whose text is already in the journal are
+/// skipped, so importing the same folder twice adds nothing; a bad item is recorded in
+/// `errors` and the import moves on.
+pub async fn import_entries(pool: &Pool<Sqlite>, path: &Path, format: JournalFormat) -> Result<ImportSummary> {
+    let items = match format {
+        JournalFormat::MarkdownDir => read_markdown_dir(path)?,
+        JournalFormat::JsonArray => read_json_array(path)?,
+    };
+
+    let mut summary = ImportSummary::default();
+    let mut seen: HashSet<String> = HashSet::new();
+    for (source, item) in items {
+        let item = match item {
+            Ok(item) => item,
+            Err(error) => {
+                summary.errors.push(ImportFailure { source, error });
+                continue;
+            }
+        };
+        let body = item.body.trim();
+        let hash = body_hash(body.as_bytes());
+        if body.is_empty() || !seen.insert(hash.clone()) {
+            summary.skipped += 1;
+            continue;
+        }
+        match import_one(pool, body, &hash, item.created_at, item.mood, item.tags).await {
+            Ok(true) => summary.imported += 1,
+            Ok(false) => summary.skipped += 1,
+            Err(error) => {
+                warn!(source = %source, error = %error, "import entry failed");
+                summary.errors.push(ImportFailure { source, error });
+            }
+        }
+    }
+    info!(
+        path = %path.display(),
+        imported = summary.imported,
+        skipped = summary.skipped,
+        errors = summary.errors.len(),
+        "journal import finished"
+    );
+    Ok(summary)
+}
+
+// Returns false when an entry with the same text already exists
+async fn import_one(
+    pool: &Pool<Sqlite>,
+    body: &str,
+    hash: &str,
+    created_at: Option<String>,
+    mood: Option<String>,
+    tags: Option<serde_json::Value>,
+) -> Result<bool, String> {
+    if find_entry_by_body_hash(pool, hash).await?.is_some() {
+        return Ok(false);
+    }
+    upsert_entry(pool, EntryUpsert {
+        id: None,
+        body_cipher: crate::vault::encrypt(body.as_bytes())?,
+        mood: mood.map(|m| m.trim().to_string()).filter(|m| !m.is_empty()),
+        tags,
+        created_at: created_at.map(|c| expand_date(&c)),
+    })
+    .await?;
+    Ok(true)
+}
+
+// A bare `YYYY-MM-DD` becomes midnight UTC; anything else is left for RFC3339 parsing
+fn expand_date(value: &str) -> String {
+    let value = value.trim();
+    if is_plain_date(value) {
+        format!("{}T00:00:00Z", value)
+    } else {
+        value.to_string()
+    }
+}
+
+fn is_plain_date(value: &str) -> bool {
+    let b = value.as_bytes();
+    b.len() == 10
+        && b[4] == b'-'
+        && b[7] == b'-'
+        && b.iter().enumerate().all(|(i, c)| i == 4 || i == 7 || c.is_ascii_digit())
+}
+
+type SourcedEntry = (String, Result<ImportedEntry, String>);
+
+fn read_json_array(path: &Path) -> Result<Vec<SourcedEntry>> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+    let values: Vec<serde_json::Value> =
+        serde_json::from_str(&text).with_context(|| format!("{} is not a JSON array", path.display()))?;
+    Ok(values
+        .into_iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let item = serde_json::from_value::<ImportedEntry>(v).map_err(|e| format!("malformed entry: {}", e));
+            (format!("{}#{}", path.display(), i), item)
+        })
+        .collect())
+}
+
+fn read_markdown_dir(dir: &Path) -> Result<Vec<SourcedEntry>> {
+    if !dir.is_dir() {
+        return Err(anyhow!("not a directory: {}", dir.display()));
+    }
+    let mut files = Vec::new();
+    collect_markdown_files(dir, &mut files)?;
+    // Sorted so dated file names import oldest first
+    files.sort();
+    Ok(files
+        .into_iter()
+        .map(|file| {
+            let item = std::fs::read_to_string(&file)
+                .map_err(|e| format!("read failed: {}", e))
+                .map(|text| parse_markdown_entry(&text, &file));
+            (file.display().to_string(), item)
+        })
+        .collect())
+}
+
+// Walks sub-folders too, for journals kept as `2021/03/...`
+fn collect_markdown_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir).with_context(|| format!("read dir {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_markdown_files(&path, out)?;
+        } else if path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("md") || e.eq_ignore_ascii_case("markdown"))
+        {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn parse_markdown_entry(text: &str, file: &Path) -> ImportedEntry {
+    let (front, body) = split_front_matter(text);
+    let mut entry = ImportedEntry { body: body.to_string(), ..Default::default() };
+    for (key, value) in front {
+        match key.to_ascii_lowercase().as_str() {
+            "date" | "created_at" | "created" => entry.created_at = Some(value),
+            "mood" => entry.mood = Some(value),
+            "tags" => entry.tags = Some(parse_tag_list(&value)),
+            _ => {}
+        }
+    }
+    // Fall back to a leading date in the file name, e.g. `2021-03-14 rainy walk.md`
+    if entry.created_at.is_none() {
+        entry.created_at = file
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.get(..10))
+            .filter(|s| is_plain_date(s))
+            .map(str::to_string);
+    }
+    entry
+}
+
+// `key: value` pairs between a leading `---` line and the next one, and the text after it
+fn split_front_matter(text: &str) -> (Vec<(String, String)>, &str) {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut lines = text.split_inclusive('\n');
+    if lines.next().map(str::trim_end) != Some("---") {
+        return (Vec::new(), text);
+    }
+    let mut pairs = Vec::new();
+    let mut offset = text.split_inclusive('\n').next().map_or(0, str::len);
+    for line in lines {
+        offset += line.len();
+        let line = line.trim_end();
+        if line == "---" {
+            return (pairs, &text[offset..]);
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            pairs.push((key.trim().to_string(), unquote(value.trim()).to_string()));
+        }
+    }
+    // No closing fence: treat the whole file as the body
+    (Vec::new(), text)
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        .unwrap_or(value)
+}
+
+// `[a, b]` or `a, b`
+fn parse_tag_list(value: &str) -> serde_json::Value {
+    let inner = value.trim().trim_start_matches('[').trim_end_matches(']');
+    serde_json::Value::Array(
+        inner
+            .split(',')
+            .map(|t| unquote(t.trim()).trim())
+            .filter(|t| !t.is_empty())
+            .map(|t| serde_json::Value::String(t.to_string()))
+            .collect(),
+    )
+}
+
+/// Write every entry, archived ones included, with its decrypted body, `created_at`, mood
+/// and tags. `markdown_dir` creates `path` and writes `{date}-{id}.md` per entry;
+/// `json_array` writes a single file. Entries are read and written one at a time so large
+/// journals never sit in memory; an entry that can't be read is recorded and skipped.
+/// With `include_images`, the paths of each entry's finished comics are added.
+pub async fn export_entries(
+    pool: &Pool<Sqlite>,
+    path: &Path,
+    format: JournalFormat,
+    include_images: bool,
+) -> Result<JournalExportSummary> {
+    let ids = all_entry_ids(pool).await.map_err(|e| anyhow!(e))?;
+    let mut json_out = match format {
+        JournalFormat::MarkdownDir => {
+            tokio::fs::create_dir_all(path)
+                .await
+                .with_context(|| format!("create {}", path.display()))?;
+            None
+        }
+        JournalFormat::JsonArray => {
+            let file = tokio::fs::File::create(path)
+                .await
+                .with_context(|| format!("create {}", path.display()))?;
+            let mut out = tokio::io::BufWriter::new(file);
+            out.write_all(b"[").await?;
+            Some(out)
+        }
+    };
+
+    let mut summary = JournalExportSummary { path: path.display().to_string(), exported: 0, failed: Vec::new() };
+    for id in ids {
+        let entry = match load_exported_entry(pool, &id, include_images).await {
+            Ok(entry) => entry,
+            Err(error) => {
+                warn!(entry_id = %id, error = %error, "export entry failed");
+                summary.failed.push(ExportFailure { entry_id: id, error });
+                continue;
+            }
+        };
+        match json_out.as_mut() {
+            Some(out) => {
+                let sep: &[u8] = if summary.exported == 0 { b"\n" } else { b",\n" };
+                out.write_all(sep).await?;
+                out.write_all(&serde_json::to_vec(&entry)?).await?;
+            }
+            None => {
+                let date = entry.created_at.get(..10).unwrap_or("undated");
+                let file = path.join(format!("{}-{}.md", date, entry.id));
+                tokio::fs::write(&file, markdown_for(&entry))
+                    .await
+                    .with_context(|| format!("write {}", file.display()))?;
+            }
+        }
+        summary.exported += 1;
+    }
+    if let Some(mut out) = json_out {
+        out.write_all(b"\n]\n").await?;
+        out.flush().await?;
+    }
+    info!(path = %summary.path, exported = summary.exported, failed = summary.failed.len(), "journal export finished");
+    Ok(summary)
+}
+
+async fn load_exported_entry(pool: &Pool<Sqlite>, id: &str, include_images: bool) -> Result<ExportedEntry, String> {
+    let entry = get_entry(pool, id.to_string()).await?;
+    let body = crate::vault::decrypt_to_string(&entry.body_cipher)?;
+    let images = if include_images { Some(entry_comic_image_paths(pool, id).await?) } else { None };
+    Ok(ExportedEntry { id: entry.id, created_at: entry.created_at, mood: entry.mood, tags: entry.tags, body, images })
+}
+
+// Front matter in the shape `parse_markdown_entry` reads back
+fn markdown_for(entry: &ExportedEntry) -> String {
+    let mut out = format!("---\nid: {}\ndate: {}\n", entry.id, entry.created_at);
+    if let Some(mood) = entry.mood.as_deref() {
+        out.push_str(&format!("mood: {}\n", mood));
+    }
+    if let Some(tags) = &entry.tags {
+        out.push_str(&format!("tags: {}\n", tags));
+    }
+    if let Some(images) = &entry.images {
+        out.push_str(&format!("images: {}\n", serde_json::Value::from(images.clone())));
+    }
+    out.push_str("---\n\n");
+    out.push_str(entry.body.trim_end());
+    out.push('\n');
+    out
+}
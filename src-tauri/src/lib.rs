@@ -1,39 +1,55 @@
+mod archive;
+mod backup;
+mod caption;
 mod comic;
+mod comic_export;
+mod contact_sheet;
 mod database;
+mod embed;
+mod error;
 mod gemini;
+mod image_cache;
+mod journal_io;
+mod mood;
 mod ollama;
+mod openai;
+mod pdf_export;
+mod report;
+mod sd;
 mod settings;
+mod summary;
 mod utils;
+mod vault;
 
 use anyhow::Result;
 use dashmap::DashMap;
 use once_cell::sync::Lazy;
 use once_cell::sync::OnceCell;
+use tauri::Emitter;
+use tokio_util::sync::CancellationToken;
 use serde::{Deserialize, Serialize};
 use sqlx::{Pool, Sqlite};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::task::JoinHandle;
 use uuid::Uuid;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 use tracing_appender::rolling;
 
-use crate::comic::{ComicJobStatus, ComicStage, ExportPanel, JobId};
+use crate::error::ToonanaError;
+use crate::comic::{ComicJobOptions, ComicJobStatus, ComicStage, ExportPanel, JobId};
 use crate::database::{
-    create_pool, get_entry, list_entries, now_iso, upsert_entry, delete_entry,
-    Entry, EntryListItem, EntryUpsert, ListParams
+    average_stage_duration, create_pool, get_entry, get_entry_body, list_entries, list_storyboards,
+    now_iso, set_entry_mood, upsert_entry, delete_entry,
+    Entry, EntryListItem, EntryPage, EntryUpsert, ListParams, SchemaReport, StageTimingStats, StoryboardRecord, StylePreset, StylePresetInput
 };
-use crate::settings::{load_settings_from_dir, save_settings_to_dir, Settings};
+use crate::settings::{load_settings_from_dir, save_settings_to_dir, ImageProvider, Settings};
 use crate::utils::{db_path, ensure_data_dir};
-use crate::comic::{decode_base64_png, guess_image_extension};
+use crate::comic::{decode_base64_png, guess_image_extension, guess_image_mime};
 use crate::gemini::cartoonify_image_with_progress;
-
-// kept for potential future re-enable of encryption
-#[allow(dead_code)]
-static SERVICE_NAME: &str = "toonana";
-#[allow(dead_code)]
-static VAULT_KEY_LABEL: &str = "vault-key-v1";
+use crate::journal_io::{ImportSummary, JournalExportSummary, JournalFormat};
 
 static LOG_GUARD: OnceCell<tracing_appender::non_blocking::WorkerGuard> = OnceCell::new();
 
@@ -60,11 +76,12 @@ fn init_tracing(data_dir: &Path) -> Result<()> {
         .with_ansi(false)
         .with_writer(non_blocking);
 
-    tracing_subscriber::registry()
+    // try_init: startup may be retried via init_app, and the global subscriber can only be set once
+    let _ = tracing_subscriber::registry()
         .with(env_filter)
         .with(stdout_layer)
         .with(file_layer)
-        .init();
+        .try_init();
     Ok(())
 }
 
@@ -75,6 +92,21 @@ struct AppState {
     jobs: Arc<DashMap<String, JoinHandle<()>>>,
     comic_status: Arc<DashMap<String, ComicJobStatus>>,
     avatar_status: Arc<DashMap<String, AvatarJobStatus>>,
+    /// `true` while the comic queue is paused; queued jobs wait on it before starting
+    queue_paused: Arc<tokio::sync::watch::Sender<bool>>,
+    /// Per-job flags that end the storyboard stream early (see `stop_storyboard`)
+    storyboard_stops: Arc<DashMap<String, Arc<AtomicBool>>>,
+    /// Per-job tokens that end in-flight image requests before the task is aborted
+    job_cancels: Arc<DashMap<String, CancellationToken>>,
+    /// One permit per comic job allowed to run at once (`max_concurrent_jobs`)
+    job_slots: Arc<tokio::sync::Semaphore>,
+    /// Progress of `export_backup`/`import_backup` jobs
+    archive_status: Arc<DashMap<String, archive::ArchiveJobStatus>>,
+    archive_cancels: Arc<DashMap<String, Arc<AtomicBool>>>,
+    /// Stop flags for in-flight `ollama_generate_stream` requests, by request id
+    stream_stops: Arc<DashMap<String, Arc<AtomicBool>>>,
+    /// Local UTC offset captured at launch, for quiet hours
+    local_offset: time::UtcOffset,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -117,216 +149,1315 @@ struct AvatarJobStatus {
 
 // ===== Tauri Commands =====
 
-#[tauri::command]
-async fn health(state: tauri::State<'_, AppState>) -> Result<AppHealth, String> {
-    Ok(AppHealth {
+fn app_health(state: &AppState) -> AppHealth {
+    AppHealth {
         ok: true,
         data_dir: state.data_dir.display().to_string(),
         db_path: db_path(&state.data_dir).display().to_string(),
-        has_vault_key: true,
+        has_vault_key: vault::is_unlocked(),
+    }
+}
+
+#[tauri::command]
+async fn health(state: Backend) -> Result<AppHealth, ToonanaError> {
+    Ok(app_health(&state))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ProvidersConfigured {
+    gemini: bool,
+    nano_banana: bool,
+    /// Only false when a custom Ollama URL is set but empty; otherwise the local default is used
+    ollama: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VersionInfo {
+    app_version: String,
+    /// None until the database records a schema version (i.e. migrations are deferred)
+    db_schema_version: Option<i64>,
+    settings_version: u32,
+    data_dir: String,
+    providers: ProvidersConfigured,
+}
+
+/// Everything needed to triage an issue report in one call.
+#[tauri::command]
+async fn get_version_info(
+    app: tauri::AppHandle,
+    state: Backend,
+) -> Result<VersionInfo, ToonanaError> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    let is_set = |v: &Option<String>| v.as_deref().map(|s| !s.trim().is_empty()).unwrap_or(false);
+    Ok(VersionInfo {
+        app_version: app.package_info().version.to_string(),
+        db_schema_version: database::schema_version(&state.db).await?,
+        settings_version: settings::SETTINGS_VERSION,
+        data_dir: state.data_dir.display().to_string(),
+        providers: ProvidersConfigured {
+            gemini: is_set(&settings.gemini_api_key),
+            nano_banana: is_set(&settings.nano_banana_base_url),
+            ollama: settings.ollama_base_url.as_deref().map(|s| !s.trim().is_empty()).unwrap_or(true),
+        },
     })
 }
 
+/// Retry backend startup after a failure (e.g. the user fixed permissions or freed disk space).
+/// Succeeds immediately when the backend is already running.
+#[tauri::command]
+async fn init_app(app: tauri::AppHandle) -> Result<AppHealth, ToonanaError> {
+    let state = ensure_started().await?;
+    start_background_tasks(&app, &state);
+    Ok(app_health(&state))
+}
+
 #[tauri::command]
-async fn get_settings(state: tauri::State<'_, AppState>) -> Result<Settings, String> {
+async fn get_settings(state: Backend) -> Result<Settings, ToonanaError> {
     Ok(load_settings_from_dir(&state.data_dir))
 }
 
 #[tauri::command]
 async fn update_settings(
-    state: tauri::State<'_, AppState>,
-    settings: Settings,
-) -> Result<Settings, String> {
+    state: Backend,
+    mut settings: Settings,
+) -> Result<Settings, ToonanaError> {
+    if let Err(errors) = settings.validate() {
+        let lines: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        return Err(format!("invalid settings:\n{}", lines.join("\n")).into());
+    }
     save_settings_to_dir(&state.data_dir, &settings).map_err(|e| e.to_string())?;
     Ok(settings)
 }
 
+/// Validate a service URL the way settings do and return its normalized form.
+#[tauri::command]
+fn normalize_base_url(url: String) -> Result<String, ToonanaError> {
+    utils::normalize_base_url(&url).map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn verify_schema(
+    state: Backend,
+    heal: Option<bool>,
+) -> Result<SchemaReport, ToonanaError> {
+    let report = database::verify_schema(&state.db, heal.unwrap_or(false)).await?;
+    if !report.issues.is_empty() {
+        tracing::warn!(issues = report.issues.len(), ok = report.ok, "schema verification found issues");
+    }
+    Ok(report)
+}
+
+#[tauri::command]
+async fn get_pending_migrations(
+    state: Backend,
+) -> Result<Vec<database::PendingMigration>, ToonanaError> {
+    database::pending_migrations(&state.db).await.map_err(ToonanaError::from)
+}
+
+/// Run pending schema migrations now; returns whatever is still pending (normally nothing).
+/// The database is backed up first when a destructive migration is about to run.
+#[tauri::command]
+async fn apply_migrations(
+    state: Backend,
+) -> Result<Vec<database::PendingMigration>, ToonanaError> {
+    let pending = database::pending_migrations(&state.db).await?;
+    if pending.iter().any(|m| m.destructive) {
+        let path = backup::backup_database(&state.db, &state.data_dir)
+            .await
+            .map_err(|e| format!("backup before migrating failed: {}", e))?;
+        tracing::info!(path = %path.display(), "backed up database before destructive migration");
+    }
+    database::apply_migrations(&state.db).await.map_err(ToonanaError::from)
+}
+
 #[tauri::command]
-fn init_vault() -> Result<(), String> {
+async fn backup_database(state: Backend) -> Result<String, ToonanaError> {
+    let path = backup::backup_database(&state.db, &state.data_dir)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(path.display().to_string())
+}
+
+fn new_archive_job(state: &AppState, kind: &'static str) -> archive::ArchiveJob {
+    let job = archive::ArchiveJob {
+        job_id: Uuid::new_v4().to_string(),
+        kind,
+        status: state.archive_status.clone(),
+        cancel: Arc::new(AtomicBool::new(false)),
+    };
+    state.archive_cancels.insert(job.job_id.clone(), job.cancel.clone());
+    job.set(archive::ArchiveStage::Preparing);
+    job
+}
+
+/// Zip the database, settings and images into `path` in the background; poll
+/// `get_backup_status` with the returned job id. API keys are left out unless
+/// `redact_api_keys` is false. Entry text stays encrypted with this machine's key unless
+/// `plaintext_bodies` is true, which is needed to restore on another machine.
+#[tauri::command]
+async fn export_backup(
+    app: tauri::AppHandle,
+    state: Backend,
+    path: String,
+    redact_api_keys: Option<bool>,
+    plaintext_bodies: Option<bool>,
+) -> Result<String, ToonanaError> {
+    let job = new_archive_job(&state, "export");
+    let job_id = job.job_id.clone();
+    let (pool, data_dir, cancels) = (state.db.clone(), state.data_dir.clone(), state.archive_cancels.clone());
+    let app_version = app.package_info().version.to_string();
+    tauri::async_runtime::spawn(async move {
+        let result = archive::export_backup(
+            &pool,
+            &data_dir,
+            Path::new(&path),
+            redact_api_keys.unwrap_or(true),
+            plaintext_bodies.unwrap_or(false),
+            app_version,
+            &job,
+        )
+        .await;
+        archive::finish(&job, result);
+        cancels.remove(&job.job_id);
+    });
+    Ok(job_id)
+}
+
+/// Restore a backup made by `export_backup`, replacing the current entries. The current
+/// database is backed up first. Refused while comic jobs are running.
+#[tauri::command]
+async fn import_backup(state: Backend, path: String) -> Result<String, ToonanaError> {
+    if backup::any_job_running(&state.jobs, &state.comic_status) {
+        return Err("comic jobs are running; wait for them to finish before restoring a backup".into());
+    }
+    let job = new_archive_job(&state, "import");
+    let job_id = job.job_id.clone();
+    let (pool, data_dir, cancels) = (state.db.clone(), state.data_dir.clone(), state.archive_cancels.clone());
+    tauri::async_runtime::spawn(async move {
+        let result = archive::import_backup(&pool, &data_dir, Path::new(&path), &job).await;
+        archive::finish(&job, result);
+        cancels.remove(&job.job_id);
+    });
+    Ok(job_id)
+}
+
+/// Bring in entries from a folder of Markdown files or a JSON array, keeping their dates.
+/// Entries whose text is already in the journal are skipped.
+#[tauri::command]
+async fn import_entries(
+    state: Backend,
+    path: String,
+    format: JournalFormat,
+) -> Result<ImportSummary, ToonanaError> {
+    journal_io::import_entries(&state.db, Path::new(&path), format)
+        .await
+        .map_err(ToonanaError::from)
+}
+
+/// Dump the journal with decrypted bodies to a Markdown folder or a JSON file, in the
+/// layout `import_entries` reads. Comic image paths are included when `include_images` is true.
+#[tauri::command]
+async fn export_entries(
+    state: Backend,
+    path: String,
+    format: JournalFormat,
+    include_images: Option<bool>,
+) -> Result<JournalExportSummary, ToonanaError> {
+    journal_io::export_entries(&state.db, Path::new(&path), format, include_images.unwrap_or(false))
+        .await
+        .map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn get_backup_status(
+    state: Backend,
+    job_id: String,
+) -> Result<archive::ArchiveJobStatus, ToonanaError> {
+    state
+        .archive_status
+        .get(&job_id)
+        .map(|s| s.clone())
+        .ok_or_else(|| "backup job not found".to_string())
+        .map_err(ToonanaError::from)
+}
+
+/// Stop a running export/import. An export leaves no partial archive; an import stops
+/// before replacing the database, or part way through copying images.
+#[tauri::command]
+async fn cancel_backup(state: Backend, job_id: String) -> Result<(), ToonanaError> {
+    let flag = state
+        .archive_cancels
+        .get(&job_id)
+        .map(|f| f.clone())
+        .ok_or_else(|| "backup job not found or already finished".to_string())?;
+    flag.store(true, Ordering::Relaxed);
     Ok(())
 }
 
 #[tauri::command]
-fn encrypt(plaintext: String) -> Result<Vec<u8>, String> {
-    Ok(plaintext.into_bytes())
+fn init_vault() -> Result<(), ToonanaError> {
+    vault::init_vault().map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+fn encrypt(plaintext: String) -> Result<Vec<u8>, ToonanaError> {
+    vault::encrypt(plaintext.as_bytes()).map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+fn decrypt(cipher: Vec<u8>) -> Result<String, ToonanaError> {
+    vault::decrypt_to_string(&cipher).map_err(ToonanaError::from)
 }
 
+/// Re-encrypt every entry under a freshly generated vault key; returns entries rewritten.
 #[tauri::command]
-fn decrypt(cipher: Vec<u8>) -> Result<String, String> {
-    String::from_utf8(cipher).map_err(|e| e.to_string())
+async fn rotate_vault_key(state: Backend) -> Result<u64, ToonanaError> {
+    vault::rotate_key(&state.db).await.map_err(ToonanaError::from)
 }
 
 #[tauri::command]
 async fn db_upsert_entry(
-    state: tauri::State<'_, AppState>,
+    state: Backend,
     entry: EntryUpsert,
-) -> Result<Entry, String> {
-    upsert_entry(&state.db, entry).await
+) -> Result<Entry, ToonanaError> {
+    let saved = upsert_entry(&state.db, entry).await?;
+    if saved.embedding.is_none() {
+        // Embed in the background so saving never waits on (or fails because of) Ollama;
+        // `find_similar_entries` fills in anything missed here
+        let db = state.db.clone();
+        let settings = load_settings_from_dir(&state.data_dir);
+        let id = saved.id.clone();
+        tokio::spawn(async move {
+            if let Err(e) = embed::refresh_embedding(&db, &id, &settings).await {
+                tracing::warn!(entry_id = %id, error = %e, "entry embedding failed");
+            }
+        });
+    }
+    Ok(saved)
 }
 
 #[tauri::command]
-async fn db_get_entry(state: tauri::State<'_, AppState>, id: String) -> Result<Entry, String> {
-    get_entry(&state.db, id).await
+async fn db_get_entry(state: Backend, id: String) -> Result<Entry, ToonanaError> {
+    get_entry(&state.db, id).await.map_err(ToonanaError::from)
 }
 
 #[tauri::command]
 async fn db_list_entries(
-    state: tauri::State<'_, AppState>,
+    state: Backend,
     p: Option<ListParams>,
-) -> Result<Vec<EntryListItem>, String> {
-    list_entries(&state.db, p).await
+) -> Result<EntryPage, ToonanaError> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    let preview_length = settings.preview_length.unwrap_or(database::DEFAULT_PREVIEW_LENGTH);
+    list_entries(&state.db, p, preview_length).await.map_err(ToonanaError::from)
 }
 
 #[tauri::command]
-async fn ollama_health(state: tauri::State<'_, AppState>) -> Result<ollama::OllamaHealth, String> {
+async fn list_entries_changed_since(
+    state: Backend,
+    since: String,
+) -> Result<Vec<Entry>, ToonanaError> {
+    database::list_entries_changed_since(&state.db, &since).await.map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn backfill_body_hashes(state: Backend) -> Result<u64, ToonanaError> {
+    database::backfill_body_hashes(&state.db)
+        .await
+        .map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn toggle_pin(state: Backend, id: String) -> Result<bool, ToonanaError> {
+    database::toggle_pin(&state.db, &id).await.map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn archive_entry(state: Backend, id: String) -> Result<(), ToonanaError> {
+    database::set_archived(&state.db, &id, true).await.map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn unarchive_entry(state: Backend, id: String) -> Result<(), ToonanaError> {
+    database::set_archived(&state.db, &id, false).await.map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn list_archived_entries(
+    state: Backend,
+    p: Option<ListParams>,
+) -> Result<EntryPage, ToonanaError> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    let preview_length = settings.preview_length.unwrap_or(database::DEFAULT_PREVIEW_LENGTH);
+    database::list_archived_entries(&state.db, p, preview_length).await.map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn ollama_health(state: Backend) -> Result<ollama::OllamaHealth, ToonanaError> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    ollama::check_health(&settings).await.map_err(ToonanaError::from)
+}
+
+/// One provider's part of `check_all_providers`.
+#[derive(Debug, Serialize)]
+struct ProviderStatus {
+    /// Settings name this provider (always true for Ollama, which has a local default)
+    configured: bool,
+    ok: bool,
+    message: Option<String>,
+    duration_ms: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct ProvidersReport {
+    ollama: ProviderStatus,
+    ollama_models: Option<Vec<String>>,
+    openai_compatible: ProviderStatus,
+    gemini: ProviderStatus,
+    nano_banana: ProviderStatus,
+    a1111: ProviderStatus,
+    /// The selected `llm_provider` and `image_provider` (or its Gemini fallback) both work,
+    /// so a comic can be rendered
+    ready: bool,
+}
+
+// Per-provider limit in `check_all_providers`, so one slow service can't hold up the report
+const PROVIDER_CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+// Run one provider check under `PROVIDER_CHECK_TIMEOUT`
+async fn timed_check<T>(check: impl std::future::Future<Output = Result<T, String>>) -> (Result<T, String>, u64) {
+    let started = std::time::Instant::now();
+    let result = match tokio::time::timeout(PROVIDER_CHECK_TIMEOUT, check).await {
+        Ok(r) => r,
+        Err(_) => Err(format!("timed out after {}s", PROVIDER_CHECK_TIMEOUT.as_secs())),
+    };
+    (result, started.elapsed().as_millis() as u64)
+}
+
+/// Readiness check for every provider at once: Ollama reachability and models, whether
+/// the Gemini key works, and whether the nano-banana, Automatic1111 and OpenAI-compatible
+/// servers answer when their base URLs are set. The checks run concurrently, each with its
+/// own short timeout. `ready` only looks at the providers the settings select.
+#[tauri::command]
+async fn check_all_providers(state: Backend) -> Result<ProvidersReport, ToonanaError> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    let is_set = |v: &Option<String>| v.as_deref().is_some_and(|s| !s.trim().is_empty());
+    // Env overrides are already applied by `load_settings_from_dir`
+    let gemini_configured = is_set(&settings.gemini_api_key);
+    let nano_banana_configured = is_set(&settings.nano_banana_base_url);
+    let a1111_configured = is_set(&settings.a1111_base_url);
+    let openai_configured = is_set(&settings.openai_base_url);
+
+    let (
+        (ollama, ollama_ms),
+        (openai_compatible, openai_ms),
+        (gemini, gemini_ms),
+        (nano_banana, nano_banana_ms),
+        (a1111, a1111_ms),
+    ) = tokio::join!(
+        timed_check(ollama::check_health(&settings)),
+        timed_check(async {
+            if !openai_configured {
+                return Err("OpenAI-compatible base URL not set".to_string());
+            }
+            openai::list_models(&settings).await
+        }),
+        timed_check(async {
+            if !gemini_configured {
+                return Err("Gemini API key not set".to_string());
+            }
+            gemini::check_api_key(&settings).await.map_err(|e| e.to_string())
+        }),
+        timed_check(async {
+            if !nano_banana_configured {
+                return Err("nano-banana base URL not set".to_string());
+            }
+            gemini::nano_banana_ping(&settings).await
+        }),
+        timed_check(async {
+            if !a1111_configured {
+                return Err("a1111 base URL not set".to_string());
+            }
+            sd::a1111_ping(&settings).await
+        }),
+    );
+
+    let (ollama, ollama_models) = match ollama {
+        Ok(h) => (
+            ProviderStatus { configured: true, ok: h.ok, message: h.message, duration_ms: ollama_ms },
+            h.models,
+        ),
+        Err(e) => (ProviderStatus { configured: true, ok: false, message: Some(e), duration_ms: ollama_ms }, None),
+    };
+    let openai_compatible = match openai_compatible {
+        Ok(models) => ProviderStatus {
+            configured: true,
+            ok: true,
+            message: Some(format!("{} models", models.len())),
+            duration_ms: openai_ms,
+        },
+        Err(e) => ProviderStatus { configured: openai_configured, ok: false, message: Some(e), duration_ms: openai_ms },
+    };
+    let gemini = ProviderStatus {
+        configured: gemini_configured,
+        ok: gemini.is_ok(),
+        message: gemini.err(),
+        duration_ms: gemini_ms,
+    };
+    let nano_banana = match nano_banana {
+        Ok(status) => ProviderStatus {
+            configured: true,
+            ok: true,
+            message: Some(format!("HTTP {}", status)),
+            duration_ms: nano_banana_ms,
+        },
+        Err(e) => ProviderStatus { configured: nano_banana_configured, ok: false, message: Some(e), duration_ms: nano_banana_ms },
+    };
+    let a1111 = ProviderStatus {
+        configured: a1111_configured,
+        ok: a1111.is_ok(),
+        message: a1111.err(),
+        duration_ms: a1111_ms,
+    };
+
+    let llm_ready = if openai::is_selected(&settings) { openai_compatible.ok } else { ollama.ok };
+    let fallback_ready = settings.fallback_to_gemini() && gemini.ok;
+    let image_ready = match settings.image_provider() {
+        ImageProvider::Gemini => gemini.ok,
+        ImageProvider::NanoBanana => nano_banana.ok || fallback_ready,
+        ImageProvider::A1111 => a1111.ok || fallback_ready,
+    };
+    Ok(ProvidersReport {
+        ollama,
+        ollama_models,
+        openai_compatible,
+        gemini,
+        nano_banana,
+        a1111,
+        ready: llm_ready && image_ready,
+    })
+}
+
+#[tauri::command]
+async fn ollama_list_models(state: Backend) -> Result<Vec<String>, ToonanaError> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    ollama::list_models(&settings).await.map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn ollama_model_details(
+    state: Backend,
+    model: Option<String>,
+) -> Result<Vec<ollama::OllamaModelDetails>, ToonanaError> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    ollama::model_details(model, &settings).await.map_err(ToonanaError::from)
+}
+
+/// Raw NDJSON from an Ollama generation, for diagnosing odd model output. Requires `debug_mode`.
+#[tauri::command]
+async fn debug_ollama_generate(
+    state: Backend,
+    prompt: String,
+    model: Option<String>,
+) -> Result<String, ToonanaError> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    if !settings.debug_mode.unwrap_or(false) {
+        return Err("debug_ollama_generate requires debug_mode to be enabled in settings".into());
+    }
+    ollama::generate_raw(model, prompt, &settings).await.map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn ollama_test_model(
+    state: Backend,
+    model: Option<String>,
+) -> Result<ollama::ModelTestResult, ToonanaError> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    Ok(ollama::test_model(model, &settings).await)
+}
+
+/// Start loading the model in the background and return right away with a handle id
+/// (only used in logs). Failures are logged, never surfaced.
+#[tauri::command]
+async fn warm_up_ollama(
+    state: Backend,
+    model: Option<String>,
+) -> Result<String, ToonanaError> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    let handle = Uuid::new_v4().to_string();
+    let warm_id = handle.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = ollama::warm_up(model, &settings).await {
+            tracing::warn!(warm_id = %warm_id, error = %e, "ollama warm-up failed");
+        }
+    });
+    Ok(handle)
+}
+
+#[tauri::command]
+async fn infer_mood(
+    state: Backend,
+    entry_id: String,
+    save: Option<bool>,
+) -> Result<String, ToonanaError> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    let body = get_entry_body(&state.db, &entry_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let inferred = mood::infer_mood(&body, &settings).await?;
+    if save.unwrap_or(false) {
+        set_entry_mood(&state.db, &entry_id, Some(&inferred)).await?;
+    }
+    Ok(inferred)
+}
+
+/// Generate (or regenerate) the entry's short title and store it.
+#[tauri::command]
+async fn generate_summary(
+    state: Backend,
+    entry_id: String,
+) -> Result<String, ToonanaError> {
     let settings = load_settings_from_dir(&state.data_dir);
-    ollama::check_health(&settings).await
+    let body = get_entry_body(&state.db, &entry_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let summary = summary::generate_summary(&body, &settings).await?;
+    database::set_entry_summary(&state.db, &entry_id, Some(&summary)).await?;
+    Ok(summary)
 }
 
 #[tauri::command]
-async fn ollama_list_models(state: tauri::State<'_, AppState>) -> Result<Vec<String>, String> {
+async fn estimate_tokens(
+    state: Backend,
+    text: String,
+    model: Option<String>,
+) -> Result<ollama::TokenEstimate, ToonanaError> {
     let settings = load_settings_from_dir(&state.data_dir);
-    ollama::list_models(&settings).await
+    let prompt = comic::build_storyboard_prompt(&text, false, None);
+    Ok(ollama::estimate_tokens(&text, &prompt, model, &settings))
 }
 
 #[tauri::command]
-async fn ollama_generate(model: Option<String>, prompt: String) -> Result<String, String> {
-    let state = STARTUP.as_ref().map_err(|e| e.to_string())?.clone();
+async fn ollama_generate(model: Option<String>, prompt: String) -> Result<String, ToonanaError> {
+    let state = app_state()?;
     let settings = load_settings_from_dir(&state.data_dir);
-    ollama::generate(model, prompt, &settings).await
+    if openai::is_selected(&settings) {
+        return openai::generate(model, prompt, &settings).await.map_err(ToonanaError::from);
+    }
+    ollama::generate(model, prompt, &settings).await.map_err(ToonanaError::from)
+}
+
+/// Payload of `ollama://token` events; `request_id` tells concurrent streams apart.
+#[derive(Debug, Clone, Serialize)]
+struct OllamaTokenEvent {
+    request_id: String,
+    chunk: String,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaStreamResult {
+    request_id: String,
+    text: String,
+    done_reason: Option<String>,
+    truncated: bool,
+    /// Ended early by `stop_ollama_stream`
+    stopped: bool,
+}
+
+/// Like `ollama_generate`, but emits each chunk as an `ollama://token` event while the
+/// model generates. Resolves with the full text once the stream ends. `request_id` tags
+/// the events; one is generated if omitted.
+#[tauri::command]
+async fn ollama_generate_stream(
+    app: tauri::AppHandle,
+    state: Backend,
+    model: Option<String>,
+    prompt: String,
+    request_id: Option<String>,
+) -> Result<OllamaStreamResult, ToonanaError> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    let request_id = request_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let stop = Arc::new(AtomicBool::new(false));
+    state.stream_stops.insert(request_id.clone(), stop.clone());
+
+    let mut text = String::new();
+    let result = comic::generate_streaming(model, prompt, &settings, None, Some(&stop), |chunk| {
+        text.push_str(chunk);
+        let event = OllamaTokenEvent { request_id: request_id.clone(), chunk: chunk.to_string() };
+        if let Err(e) = app.emit("ollama://token", event) {
+            tracing::warn!(error = %e, "failed to emit ollama://token");
+        }
+    })
+    .await;
+    state.stream_stops.remove(&request_id);
+
+    let summary = result?;
+    Ok(OllamaStreamResult {
+        request_id,
+        text,
+        done_reason: summary.done_reason,
+        truncated: summary.truncated,
+        stopped: summary.stopped,
+    })
+}
+
+/// End an `ollama_generate_stream` request early; it resolves with the text so far.
+#[tauri::command]
+async fn stop_ollama_stream(state: Backend, request_id: String) -> Result<(), ToonanaError> {
+    let flag = state
+        .stream_stops
+        .get(&request_id)
+        .map(|f| f.clone())
+        .ok_or_else(|| "stream not found".to_string())?;
+    flag.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+#[tauri::command]
+async fn create_comic_job(
+    app: tauri::AppHandle,
+    state: Backend,
+    entry_id: String,
+    style: Option<String>,
+    options: Option<ComicJobOptions>,
+) -> Result<JobId, ToonanaError> {
+    let options = options.unwrap_or_default();
+    validate_comic_options(&options)?;
+    start_comic_job(app, &state, entry_id, style, options).await.map_err(ToonanaError::from)
+}
+
+/// Render a comic for an entry and return it inline without saving anything.
+/// `preview_id` tags the `comic://preview-progress` events; one is generated if omitted.
+/// `cancel_job(preview_id)` stops the render.
+#[tauri::command]
+async fn preview_comic(
+    app: tauri::AppHandle,
+    state: Backend,
+    entry_id: String,
+    style: Option<String>,
+    preview_id: Option<String>,
+) -> Result<comic::ComicPreview, ToonanaError> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    let style = match style.filter(|s| !s.trim().is_empty()) {
+        Some(s) => Some(s),
+        None => database::get_preferred_style(&state.db, &entry_id).await?,
+    };
+    let style = comic::resolve_comic_style(style.as_deref(), &settings);
+    let preview_id = preview_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let cancel = CancellationToken::new();
+    state.job_cancels.insert(preview_id.clone(), cancel.clone());
+    let result = comic::preview_comic(&app, &state.db, preview_id.clone(), entry_id, style, &settings, &cancel).await;
+    state.job_cancels.remove(&preview_id);
+    result.map_err(ToonanaError::from)
+}
+
+/// Re-render one panel (0-based `panel_idx`) of the entry's latest storyboard and return
+/// its new image path. Progress is in the status map under `sub_job_id` (generated if omitted),
+/// and `cancel_job(sub_job_id)` stops the render.
+#[tauri::command]
+async fn regenerate_panel(
+    state: Backend,
+    entry_id: String,
+    panel_idx: u32,
+    style: Option<String>,
+    sub_job_id: Option<String>,
+) -> Result<String, ToonanaError> {
+    let sub_job_id = sub_job_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let cancel = CancellationToken::new();
+    state.job_cancels.insert(sub_job_id.clone(), cancel.clone());
+    let result = comic::regenerate_panel(
+        sub_job_id.clone(),
+        entry_id,
+        panel_idx,
+        style.filter(|s| !s.trim().is_empty()),
+        state.comic_status.clone(),
+        state.db.clone(),
+        state.data_dir.clone(),
+        cancel,
+    )
+    .await;
+    state.job_cancels.remove(&sub_job_id);
+    result.map_err(ToonanaError::from)
+}
+
+/// Generate and return an entry's storyboard without rendering or saving it.
+/// `draft_id` tags the `comic://storyboard-draft` events; one is generated if omitted.
+#[tauri::command]
+async fn generate_storyboard_only(
+    app: tauri::AppHandle,
+    state: Backend,
+    entry_id: String,
+    style: Option<String>,
+    draft_id: Option<String>,
+) -> Result<comic::StoryboardDraft, ToonanaError> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    let style = match style.filter(|s| !s.trim().is_empty()) {
+        Some(s) => Some(s),
+        None => database::get_preferred_style(&state.db, &entry_id).await?,
+    };
+    let style = comic::resolve_comic_style(style.as_deref(), &settings);
+    let draft_id = draft_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    comic::generate_storyboard_draft(&app, &state.db, draft_id, entry_id, style, &settings).await.map_err(ToonanaError::from)
+}
+
+/// Queue a comic for just part of an entry, given as character offsets `[start, end)`.
+#[tauri::command]
+async fn create_comic_from_selection(
+    app: tauri::AppHandle,
+    state: Backend,
+    entry_id: String,
+    start: usize,
+    end: usize,
+    style: Option<String>,
+) -> Result<JobId, ToonanaError> {
+    let selection = comic::TextSelection { start, end };
+    // Reject bad ranges up front instead of failing the job later
+    let body = get_entry_body(&state.db, &entry_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    selection.slice(&body)?;
+    let options = ComicJobOptions { selection: Some(selection), ..Default::default() };
+    start_comic_job(app, &state, entry_id, style, options).await.map_err(ToonanaError::from)
+}
+
+/// Start a new job for a failed one, keeping the old record. When the failed job already
+/// has a storyboard it goes straight to rendering with that text; otherwise it runs from
+/// scratch. `options` (e.g. `render_mode`) apply to the new job.
+#[tauri::command]
+async fn retry_comic_job(
+    app: tauri::AppHandle,
+    state: Backend,
+    job_id: String,
+    options: Option<ComicJobOptions>,
+) -> Result<JobId, ToonanaError> {
+    let failed = match state.comic_status.get(&job_id).map(|s| s.clone()) {
+        Some(status) => status,
+        None => database::get_comic_job(&state.db, &job_id)
+            .await?
+            .ok_or_else(|| "job not found".to_string())?,
+    };
+    if !matches!(failed.stage, ComicStage::Failed { .. }) {
+        return Err("only failed jobs can be retried".into());
+    }
+    let mut options = options.unwrap_or_default();
+    validate_comic_options(&options)?;
+    options.storyboard_text = failed.storyboard_text.filter(|t| !t.trim().is_empty());
+    options.retry_of = Some(job_id.clone());
+    tracing::info!(job_id = %job_id, reuse_storyboard = options.storyboard_text.is_some(), "retrying comic job");
+    start_comic_job(app, &state, failed.entry_id, Some(failed.style), options).await.map_err(ToonanaError::from)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EntryAndJob {
+    entry_id: String,
+    job_id: JobId,
+}
+
+/// Save a new entry and queue its comic in one call ("quick comic").
+#[tauri::command]
+async fn create_entry_and_comic(
+    app: tauri::AppHandle,
+    state: Backend,
+    body: String,
+    mood: Option<String>,
+    tags: Option<serde_json::Value>,
+    style: Option<String>,
+    options: Option<ComicJobOptions>,
+) -> Result<EntryAndJob, ToonanaError> {
+    let options = options.unwrap_or_default();
+    // Validate before writing so a bad request doesn't leave an entry behind
+    validate_comic_options(&options)?;
+    let entry = upsert_entry(&state.db, EntryUpsert {
+        id: None,
+        body_cipher: vault::encrypt(body.as_bytes())?,
+        mood,
+        tags,
+        created_at: None,
+    })
+    .await?;
+    let job_id = start_comic_job(app, &state, entry.id.clone(), style, options).await?;
+    Ok(EntryAndJob { entry_id: entry.id, job_id })
+}
+
+fn validate_comic_options(options: &ComicJobOptions) -> Result<(), String> {
+    if let Some(p) = options.style_reference_image_path.as_deref() {
+        if !Path::new(p).is_file() {
+            return Err(format!("style reference image not found: {}", p));
+        }
+    }
+    Ok(())
+}
+
+async fn start_comic_job(
+    app: tauri::AppHandle,
+    state: &AppState,
+    entry_id: String,
+    style: Option<String>,
+    options: ComicJobOptions,
+) -> Result<JobId, String> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    let preset = match options.style_preset_id.as_deref() {
+        Some(id) => Some(database::get_style_preset(&state.db, id).await?),
+        None => None,
+    };
+    // Explicit style, then the preset's name, then the entry's preferred style, then the
+    // configured default
+    let style = match style.filter(|s| !s.trim().is_empty()) {
+        Some(s) => Some(s),
+        None => match preset {
+            Some(p) => Some(p.name),
+            None => database::get_preferred_style(&state.db, &entry_id).await?,
+        },
+    };
+    let style = comic::resolve_comic_style(style.as_deref(), &settings);
+    let job_id = Uuid::new_v4().to_string();
+
+    let queued = ComicJobStatus {
+        job_id: job_id.clone(),
+        entry_id: entry_id.clone(),
+        style: style.clone(),
+        stage: ComicStage::Queued,
+        updated_at: now_iso(),
+        result_image_path: None,
+        storyboard_text: None,
+        meta: None,
+        panel_image_paths: Vec::new(),
+    };
+    let persisted = match database::upsert_comic_job(&state.db, &queued).await {
+        Ok(()) => database::set_comic_job_options(&state.db, &job_id, &options).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = persisted {
+        tracing::warn!(job_id = %job_id, error = %e, "failed to persist queued comic job");
+    }
+    spawn_comic_job(app, state, queued, options).await;
+    Ok(job_id)
+}
+
+// Run a queued job: register it in the status map and its stop and cancel handles, then
+// hand it to `comic::create_comic_job`
+async fn spawn_comic_job(app: tauri::AppHandle, state: &AppState, queued: ComicJobStatus, options: ComicJobOptions) {
+    let (job_id, entry_id, style) = (queued.job_id.clone(), queued.entry_id.clone(), queued.style.clone());
+    state.comic_status.insert(job_id.clone(), queued);
+
+    let storyboard_stop = Arc::new(AtomicBool::new(false));
+    state.storyboard_stops.insert(job_id.clone(), storyboard_stop.clone());
+    let cancel = CancellationToken::new();
+    state.job_cancels.insert(job_id.clone(), cancel.clone());
+
+    let handle = comic::create_comic_job(
+        job_id.clone(),
+        entry_id,
+        style,
+        options,
+        app,
+        state.queue_paused.subscribe(),
+        storyboard_stop,
+        cancel,
+        state.job_slots.clone(),
+        state.comic_status.clone(),
+        state.db.clone(),
+        state.data_dir.clone(),
+        state.local_offset,
+    ).await;
+    
+    state.jobs.insert(job_id, handle);
+}
+
+#[tauri::command]
+async fn list_style_presets(state: Backend) -> Result<Vec<StylePreset>, ToonanaError> {
+    database::list_style_presets(&state.db).await.map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn create_style_preset(
+    state: Backend,
+    preset: StylePresetInput,
+) -> Result<StylePreset, ToonanaError> {
+    database::create_style_preset(&state.db, &preset).await.map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn update_style_preset(
+    state: Backend,
+    id: String,
+    preset: StylePresetInput,
+) -> Result<StylePreset, ToonanaError> {
+    database::update_style_preset(&state.db, &id, &preset).await.map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn delete_style_preset(state: Backend, id: String) -> Result<(), ToonanaError> {
+    database::delete_style_preset(&state.db, &id).await.map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn get_storyboards(
+    state: Backend,
+    entry_id: String,
+) -> Result<Vec<StoryboardRecord>, ToonanaError> {
+    list_storyboards(&state.db, &entry_id).await.map_err(ToonanaError::from)
+}
+
+/// The entry's latest storyboard with its structured panels, so it can be re-rendered
+/// without asking the model again.
+#[tauri::command]
+async fn get_storyboard(
+    state: Backend,
+    entry_id: String,
+) -> Result<Option<database::StoryboardDetail>, ToonanaError> {
+    database::latest_storyboard(&state.db, &entry_id).await.map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn get_comic_job_status(
+    state: Backend,
+    job_id: String,
+) -> Result<ComicJobStatus, ToonanaError> {
+    let mut status = match state.comic_status.get(&job_id).map(|v| v.clone()) {
+        Some(status) => status,
+        // Jobs from earlier sessions are only in the database
+        None => database::get_comic_job(&state.db, &job_id)
+            .await?
+            .ok_or_else(|| "job not found".to_string())?,
+    };
+    // Jobs still queued while the queue is paused are waiting on the flag, not on capacity
+    if matches!(status.stage, ComicStage::Queued) && *state.queue_paused.borrow() {
+        let meta = status.meta.get_or_insert_with(|| serde_json::json!({}));
+        meta["paused"] = serde_json::json!(true);
+    }
+    Ok(status)
+}
+
+/// Stop the job's storyboard generation, keeping the partial text. The job then ends in
+/// `storyboard_ready` instead of rendering. No effect once storyboarding has finished.
+#[tauri::command]
+async fn stop_storyboard(state: Backend, job_id: String) -> Result<(), ToonanaError> {
+    let flag = state
+        .storyboard_stops
+        .get(&job_id)
+        .map(|f| f.clone())
+        .ok_or_else(|| "job not found".to_string())?;
+    flag.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Stop starting new comic jobs; in-flight jobs run to completion.
+#[tauri::command]
+async fn pause_queue(state: Backend) -> Result<(), ToonanaError> {
+    state.queue_paused.send_replace(true);
+    tracing::info!("comic queue paused");
+    Ok(())
+}
+
+#[tauri::command]
+async fn resume_queue(state: Backend) -> Result<(), ToonanaError> {
+    state.queue_paused.send_replace(false);
+    tracing::info!("comic queue resumed");
+    Ok(())
+}
+
+/// Produce alt text for a finished comic, preferring a vision model and falling back to the
+/// storyboard captions. Jobs from earlier sessions are read from `comic_jobs`. The result
+/// is stored under `meta.alt_text` of the job, in memory and in the database.
+#[tauri::command]
+async fn describe_comic(
+    state: Backend,
+    job_id: String,
+) -> Result<String, ToonanaError> {
+    use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
+    let in_memory = state.comic_status.get(&job_id).map(|v| v.clone());
+    let status = match in_memory {
+        Some(status) => status,
+        None => database::get_comic_job(&state.db, &job_id)
+            .await?
+            .ok_or_else(|| "job not found".to_string())?,
+    };
+    let settings = load_settings_from_dir(&state.data_dir);
+
+    let mut source = "storyboard";
+    let mut alt_text: Option<String> = None;
+    if let Some(path) = status.result_image_path.as_ref() {
+        match tokio::fs::read(path).await {
+            Ok(bytes) => {
+                let mime = guess_image_mime(&bytes);
+                match gemini::describe_image(&B64.encode(&bytes), mime, &settings).await {
+                    Ok(text) => {
+                        alt_text = Some(text);
+                        source = "vision";
+                    }
+                    Err(e) => tracing::warn!(error = %e, "describe_comic: vision model unavailable, using storyboard"),
+                }
+            }
+            Err(e) => tracing::warn!(error = %e, "describe_comic: cannot read result image"),
+        }
+    }
+    let alt_text = match alt_text {
+        Some(t) => t,
+        None => status
+            .storyboard_text
+            .as_deref()
+            .and_then(comic::storyboard_alt_text)
+            .ok_or_else(|| "no image description available for this job".to_string())?,
+    };
+
+    let mut meta = status.meta.filter(|m| m.is_object()).unwrap_or_else(|| serde_json::json!({}));
+    meta["alt_text"] = serde_json::json!(alt_text);
+    meta["alt_text_source"] = serde_json::json!(source);
+    if let Some(mut entry) = state.comic_status.get_mut(&job_id) {
+        entry.meta = Some(meta.clone());
+    }
+    database::set_comic_job_meta(&state.db, &job_id, &meta).await?;
+    Ok(alt_text)
+}
+
+#[tauri::command]
+async fn average_render_time(
+    state: Backend,
+    provider: Option<String>,
+) -> Result<StageTimingStats, ToonanaError> {
+    average_stage_duration(&state.db, "rendering", provider.as_deref()).await.map_err(ToonanaError::from)
+}
+
+/// Merged timeline of entry and comic events, newest first.
+#[tauri::command]
+async fn recent_activity(
+    state: Backend,
+    limit: Option<i64>,
+) -> Result<Vec<database::ActivityItem>, ToonanaError> {
+    let limit = limit.unwrap_or(50).clamp(1, 500);
+    database::recent_activity(&state.db, limit).await.map_err(ToonanaError::from)
 }
 
+/// Persisted comic jobs (including ones from earlier sessions), newest first.
 #[tauri::command]
-async fn create_comic_job(
-    state: tauri::State<'_, AppState>,
-    entry_id: String,
-    style: String,
-) -> Result<JobId, String> {
-    let job_id = Uuid::new_v4().to_string();
-    
-    state.comic_status.insert(job_id.clone(), ComicJobStatus {
-        job_id: job_id.clone(),
-        entry_id: entry_id.clone(),
-        style: style.clone(),
-        stage: ComicStage::Queued,
-        updated_at: now_iso(),
-        result_image_path: None,
-        storyboard_text: None,
-    });
-
-    let handle = comic::create_comic_job(
-        job_id.clone(),
-        entry_id,
-        style,
-        state.comic_status.clone(),
-        state.db.clone(),
-        state.data_dir.clone(),
-    ).await;
-    
-    state.jobs.insert(job_id.clone(), handle);
-    Ok(job_id)
+async fn list_comic_jobs(
+    state: Backend,
+    entry_id: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<ComicJobStatus>, ToonanaError> {
+    let limit = limit.unwrap_or(100).clamp(1, 1000);
+    database::list_comic_jobs(&state.db, entry_id.as_deref(), limit).await.map_err(ToonanaError::from)
 }
 
-#[tauri::command]
-async fn get_comic_job_status(
-    state: tauri::State<'_, AppState>,
-    job_id: String,
-) -> Result<ComicJobStatus, String> {
-    state
-        .comic_status
-        .get(&job_id)
-        .map(|v| v.clone())
-        .ok_or_else(|| "job not found".to_string())
+// How long a cancelled job gets to wind down on its own before its task is aborted
+const CANCEL_GRACE: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Stop a comic job's task: fire its token so in-flight requests are dropped and no more
+/// files are written, give it `CANCEL_GRACE` to return, then abort whatever is left.
+async fn stop_comic_task(state: &AppState, job_id: &str) {
+    if let Some((_, cancel)) = state.job_cancels.remove(job_id) {
+        cancel.cancel();
+    }
+    if let Some((_, mut handle)) = state.jobs.remove(job_id) {
+        // A queued job holds no slot yet; a running one gives its slot back when the task drops
+        if tokio::time::timeout(CANCEL_GRACE, &mut handle).await.is_err() {
+            tracing::warn!(job_id = %job_id, "comic job didn't stop after cancel; aborting");
+            handle.abort();
+            // Wait for the task to actually stop; the cancellation error is expected
+            let _ = handle.await;
+        }
+    }
 }
 
 #[tauri::command]
-async fn cancel_job(state: tauri::State<'_, AppState>, job_id: String) -> Result<(), String> {
-    if let Some((_, handle)) = state.jobs.remove(&job_id) {
-        handle.abort();
+async fn cancel_job(state: Backend, job_id: String) -> Result<(), ToonanaError> {
+    stop_comic_task(&state, &job_id).await;
+    let cancelled = state.comic_status.get_mut(&job_id).and_then(|mut status| {
+        if status.stage.is_terminal() {
+            return None;
+        }
+        status.stage = ComicStage::failed("cancelled");
+        status.updated_at = now_iso();
+        Some(status.clone())
+    });
+    if let Some(status) = cancelled {
+        database::upsert_comic_job(&state.db, &status).await?;
+        tracing::info!(job_id = %job_id, "comic job cancelled");
     }
     Ok(())
 }
 
 #[tauri::command]
 async fn save_image_to_disk(
-    state: tauri::State<'_, AppState>,
+    state: Backend,
     base64_png: String,
     entry_id: String,
     panel_id: String,
-) -> Result<String, String> {
-    comic::save_image_to_disk(state.data_dir.clone(), base64_png, entry_id, panel_id).await
+) -> Result<String, ToonanaError> {
+    comic::save_image_to_disk(state.data_dir.clone(), base64_png, entry_id, panel_id).await.map_err(ToonanaError::from)
 }
 
 #[tauri::command]
 async fn export_pdf(
-    _state: tauri::State<'_, AppState>,
-    _entry_id: String,
-    _panels: Vec<ExportPanel>,
+    entry_id: String,
+    panels: Vec<ExportPanel>,
     path: String,
-) -> Result<(), String> {
-    // Placeholder: create an empty file so the UI can proceed
-    if let Some(parent) = Path::new(&path).parent() {
-        let _ = fs::create_dir_all(parent);
-    }
-    fs::write(&path, b"PDF export handled in frontend").map_err(|e| e.to_string())?;
-    Ok(())
+    page_size: Option<pdf_export::PageSize>,
+    panels_per_page: Option<u32>,
+) -> Result<pdf_export::PdfExportResult, ToonanaError> {
+    tracing::info!(entry_id = %entry_id, panels = panels.len(), "exporting comic pdf");
+    tokio::task::spawn_blocking(move || {
+        pdf_export::export_comic_pdf(
+            &panels,
+            Path::new(&path),
+            page_size.unwrap_or_default(),
+            panels_per_page.unwrap_or(2),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(ToonanaError::from)
 }
 
-#[tauri::command]
-async fn generate_avatar_image(prompt: String) -> Result<String, String> {
-    let state = STARTUP.as_ref().map_err(|e| e.to_string())?.clone();
-    let mut settings = load_settings_from_dir(&state.data_dir);
-    // Do not include previous avatar image as an input when generating a new avatar
+// Avatars start from the description alone: no previous avatar, style reference or
+// comic aspect ratio
+fn avatar_render_settings(data_dir: &Path) -> Settings {
+    let mut settings = load_settings_from_dir(data_dir);
     settings.avatar_image_path = None;
-    let full_prompt = gemini::build_avatar_image_prompt(&prompt);
-    tracing::info!(
-        nano_banana = %settings.nano_banana_base_url.as_deref().unwrap_or("(none)"),
-        desc_len = full_prompt.len(),
-        "avatar: start generation"
-    );
-    // Helper to ensure we always return a correctly-typed data URI
-    fn to_data_uri(s: String) -> String {
-        if s.starts_with("data:") {
-            return s;
-        }
-        let (mime, _ext) = match decode_base64_png(&s) {
-            Ok(bytes) => match guess_image_extension(&bytes) {
-                "jpg" => ("image/jpeg", "jpg"),
-                "webp" => ("image/webp", "webp"),
-                _ => ("image/png", "png"),
-            },
-            Err(_) => ("image/png", "png"),
-        };
-        format!("data:{};base64,{}", mime, s)
-    }
-    if settings.nano_banana_base_url.is_some() {
-        match gemini::nano_banana_generate_image(&full_prompt, &settings).await {
-            Ok(s) => {
-                tracing::info!("avatar: nano-banana success");
-                return Ok(to_data_uri(s));
+    settings.style_reference_image_path = None;
+    settings.image_aspect_ratio = None;
+    settings
+}
+
+/// Render a portrait for `description` (base64) with the configured `image_provider`, the
+/// same way comic images are: nano-banana falls back to Gemini only when
+/// `fallback_to_gemini` is on, and Automatic1111 and Gemini go through `render_prompt_image`.
+async fn render_avatar(description: &str, settings: &Settings, mut on_progress: impl FnMut(u32, u32)) -> Result<String, String> {
+    let full_prompt = gemini::build_avatar_image_prompt(description);
+    let provider = settings.image_provider();
+    tracing::info!(provider = provider.as_str(), desc_len = full_prompt.len(), "avatar: start generation");
+    if provider != ImageProvider::NanoBanana {
+        return match comic::render_prompt_image(&full_prompt, settings, &CancellationToken::new(), on_progress, |_, _| {}).await {
+            Ok((s, used)) => {
+                tracing::info!(provider = used.as_str(), "avatar: success");
+                Ok(s)
             }
             Err(e) => {
-                tracing::warn!(error = %e, "avatar: nano-banana failed, falling back to gemini (stream)");
+                tracing::error!(error = %e, "avatar: generation failed");
+                Err(format!("avatar generation failed: {}", e))
+            }
+        };
+    }
+
+    // Nano-banana reports no progress, so tick while waiting (capped to leave room for saving)
+    let mut tick: u32 = 0;
+    let fut = gemini::nano_banana_generate_image(&full_prompt, settings);
+    tokio::pin!(fut);
+    let res = loop {
+        tokio::select! {
+            r = &mut fut => break r,
+            _ = tokio::time::sleep(std::time::Duration::from_millis(800)) => {
+                if tick < 98 {
+                    tick = tick.saturating_add(2).min(98);
+                    on_progress(tick, 100);
+                }
             }
         }
+    };
+    let nano_banana_error = match res {
+        Ok(s) => {
+            tracing::info!("avatar: nano-banana success");
+            return Ok(s);
+        }
+        Err(e) => e,
+    };
+    if !settings.fallback_to_gemini() {
+        tracing::error!(error = %nano_banana_error, "avatar: nano-banana failed; fallback to gemini is off");
+        return Err(format!("avatar generation failed: {} (fallback to Gemini is off)", nano_banana_error));
     }
-    match gemini::generate_image_with_progress(&full_prompt, &settings, |_c, _t| {}).await {
+    tracing::warn!(error = %nano_banana_error, "avatar: nano-banana failed, falling back to gemini");
+    match gemini::generate_image_with_progress(&full_prompt, settings, &CancellationToken::new(), on_progress, |_, _| {}).await {
         Ok(s) => {
-            tracing::info!("avatar: gemini (stream) success");
-            Ok(to_data_uri(s))
+            tracing::info!("avatar: gemini fallback success");
+            Ok(s)
         }
         Err(e) => {
-            tracing::error!(error = %e, "avatar: gemini (stream) failed");
-            Err(format!("avatar generation failed: {}", e))
+            tracing::error!(error = %e, "avatar: gemini fallback failed");
+            Err(format!("avatar generation failed; nano-banana: {}; gemini: {}", nano_banana_error, e))
+        }
+    }
+}
+
+#[tauri::command]
+async fn generate_avatar_image(prompt: String) -> Result<String, ToonanaError> {
+    let state = app_state()?;
+    let settings = avatar_render_settings(&state.data_dir);
+    let s = render_avatar(&prompt, &settings, |_c, _t| {}).await?;
+    // Always return a correctly-typed data URI
+    if s.starts_with("data:") {
+        return Ok(s);
+    }
+    let mime = match decode_base64_png(&s) {
+        Ok(bytes) => guess_image_mime(&bytes),
+        Err(_) => "image/png",
+    };
+    Ok(format!("data:{};base64,{}", mime, s))
+}
+
+/// Render an avatar from `description` (or the saved `avatar_description`) and make it the
+/// avatar, saved as `images/avatar/avatar.png`. A given description is saved as well, so
+/// comic prompts describe the same person. Returns the saved avatar path.
+#[tauri::command]
+async fn generate_avatar(
+    state: Backend,
+    description: Option<String>,
+) -> Result<String, ToonanaError> {
+    let path = avatar_from_description(&state.data_dir, description, |_c, _t| {}).await?;
+    Ok(path.display().to_string())
+}
+
+/// `generate_avatar` with progress: `avatar://progress` events tagged with `request_id`
+/// (generated if omitted) report rendering as it happens.
+#[tauri::command]
+async fn generate_avatar_from_description(
+    app: tauri::AppHandle,
+    state: Backend,
+    description: Option<String>,
+    request_id: Option<String>,
+) -> Result<String, ToonanaError> {
+    let request_id = request_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let path = avatar_from_description(&state.data_dir, description, |completed, total| {
+        let event = AvatarProgress { request_id: request_id.clone(), completed, total };
+        if let Err(e) = app.emit("avatar://progress", event) {
+            tracing::warn!(error = %e, "failed to emit avatar://progress");
+        }
+    })
+    .await?;
+    Ok(path.display().to_string())
+}
+
+async fn avatar_from_description(
+    data_dir: &Path,
+    description: Option<String>,
+    on_progress: impl FnMut(u32, u32),
+) -> Result<PathBuf, String> {
+    let description = match description.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+        Some(d) => {
+            let mut saved = load_settings_from_dir(data_dir);
+            saved.avatar_description = Some(d.to_string());
+            save_settings_to_dir(data_dir, &saved).map_err(|e| e.to_string())?;
+            d.to_string()
         }
+        None => load_settings_from_dir(data_dir)
+            .avatar_description
+            .filter(|d| !d.trim().is_empty())
+            .ok_or_else(|| "no avatar description given or saved in settings".to_string())?,
+    };
+    let settings = avatar_render_settings(data_dir);
+    let b64 = render_avatar(&description, &settings, on_progress).await?;
+    let mut bytes = decode_base64_png(&b64).map_err(|e| e.to_string())?;
+    // Some providers answer with JPEG; a generated avatar is always `images/avatar/avatar.png`
+    if guess_image_extension(&bytes) != "png" {
+        bytes = comic::convert_image_format(&bytes, "png").map_err(|e| e.to_string())?;
     }
+    store_avatar(data_dir, &bytes)
 }
 
 #[tauri::command]
 async fn create_avatar_job(
-    state: tauri::State<'_, AppState>,
+    state: Backend,
     description: String,
-) -> Result<JobId, String> {
+) -> Result<JobId, ToonanaError> {
     let job_id = Uuid::new_v4().to_string();
     state.avatar_status.insert(job_id.clone(), AvatarJobStatus {
         job_id: job_id.clone(),
@@ -340,55 +1471,18 @@ async fn create_avatar_job(
 
     let job_id_for_task = job_id.clone();
     let handle = tokio::spawn(async move {
-        let mut settings = load_settings_from_dir(&data_dir);
-        // Do not condition avatar generation on previously saved avatar image
-        settings.avatar_image_path = None;
-        let full_prompt = gemini::build_avatar_image_prompt(&description);
+        let settings = avatar_render_settings(&data_dir);
         tracing::info!(job_id = %job_id_for_task, desc_len = description.len(), "avatar job: started");
 
-        // helper to update progress
-        let mut last_tick: u32 = 0;
-        let update_progress = |completed: u32, total: u32| {
+        let result_b64 = render_avatar(&description, &settings, |completed, total| {
             status_map.insert(job_id_for_task.clone(), AvatarJobStatus {
                 job_id: job_id_for_task.clone(),
                 updated_at: now_iso(),
                 stage: AvatarStage::Rendering { completed, total },
                 image_base64: None,
             });
-        };
-
-        // Try Nano-Banana first when configured, with periodic progress ticks
-        let result_b64: Result<String, String> = if settings.nano_banana_base_url.is_some() {
-            tracing::info!(job_id = %job_id_for_task, "avatar job: sending to nano-banana");
-            let fut = gemini::nano_banana_generate_image(&full_prompt, &settings);
-            tokio::pin!(fut);
-            let res = loop {
-                tokio::select! {
-                    r = &mut fut => { break r; }
-                    _ = tokio::time::sleep(std::time::Duration::from_millis(800)) => {
-                        if last_tick < 98 {
-                            last_tick = last_tick.saturating_add(2).min(98);
-                            update_progress(last_tick, 100);
-                        }
-                    }
-                }
-            };
-            match res {
-                Ok(s) => Ok(s),
-                Err(e) => {
-                    tracing::warn!(job_id = %job_id_for_task, error = %e, "avatar job: nano-banana failed, fallback to gemini");
-                    gemini::generate_image_with_progress(&full_prompt, &settings, |c, t| {
-                        if c > last_tick && c % 5 == 0 { last_tick = c; }
-                        update_progress(c, t);
-                    }).await
-                }
-            }
-        } else {
-            gemini::generate_image_with_progress(&full_prompt, &settings, |c, t| {
-                if c > last_tick && c % 5 == 0 { last_tick = c; }
-                update_progress(c, t);
-            }).await
-        };
+        })
+        .await;
 
         match result_b64 {
             Ok(b64) => {
@@ -434,9 +1528,9 @@ async fn create_avatar_job(
 
 #[tauri::command]
 async fn create_cartoonify_job(
-    state: tauri::State<'_, AppState>,
+    state: Backend,
     data_uri: String,
-) -> Result<JobId, String> {
+) -> Result<JobId, ToonanaError> {
     // Parse data URI: data:<mime>;base64,<data>
     let (mime, b64) = if data_uri.starts_with("data:") {
         let split_idx = data_uri.find(",").ok_or_else(|| "invalid data URI".to_string())?;
@@ -477,7 +1571,7 @@ async fn create_cartoonify_job(
             });
         };
 
-        let res = cartoonify_image_with_progress(&b64, &mime, &settings, |c, t| {
+        let res = cartoonify_image_with_progress(&b64, &mime, &settings, &CancellationToken::new(), |c, t| {
             if c > last_tick && c % 5 == 0 { last_tick = c; }
             update_progress(c, t);
         }).await;
@@ -522,18 +1616,19 @@ async fn create_cartoonify_job(
 
 #[tauri::command]
 async fn get_avatar_job_status(
-    state: tauri::State<'_, AppState>,
+    state: Backend,
     job_id: String,
-) -> Result<AvatarJobStatus, String> {
+) -> Result<AvatarJobStatus, ToonanaError> {
     state
         .avatar_status
         .get(&job_id)
         .map(|v| v.clone())
         .ok_or_else(|| "job not found".to_string())
+        .map_err(ToonanaError::from)
 }
 
 #[tauri::command]
-async fn cancel_avatar_job(state: tauri::State<'_, AppState>, job_id: String) -> Result<(), String> {
+async fn cancel_avatar_job(state: Backend, job_id: String) -> Result<(), ToonanaError> {
     if let Some((_, handle)) = state.jobs.remove(&job_id) {
         handle.abort();
     }
@@ -541,40 +1636,97 @@ async fn cancel_avatar_job(state: tauri::State<'_, AppState>, job_id: String) ->
 }
 
 #[tauri::command]
-async fn save_avatar_image(base64_png: String) -> Result<String, String> {
-    let state = STARTUP.as_ref().map_err(|e| e.to_string())?.clone();
+async fn save_avatar_image(base64_png: String) -> Result<String, ToonanaError> {
+    let state = app_state()?;
     let bytes = decode_base64_png(&base64_png).map_err(|e| e.to_string())?;
-    let ext = guess_image_extension(&bytes);
-    let avatars_dir = state.data_dir.join("avatars");
-    let _ = std::fs::create_dir_all(&avatars_dir);
-    // Clean older avatar files to avoid cache collisions
-    if let Ok(rd) = std::fs::read_dir(&avatars_dir) {
-        for ent in rd.flatten() {
-            if let Some(name) = ent.file_name().to_str() {
-                if name.starts_with("avatar") {
-                    let _ = std::fs::remove_file(ent.path());
-                }
+    let path = store_avatar(&state.data_dir, &bytes)?;
+    Ok(path.display().to_string())
+}
+
+/// Write `bytes` as the avatar at `images/avatar/avatar.<ext>`, delete the previous avatar
+/// file and point `avatar_image_path` at the new one. Avatars saved before this lived in
+/// `avatars/`; their saved path keeps working until the next avatar replaces it.
+fn store_avatar(data_dir: &Path, bytes: &[u8]) -> Result<PathBuf, String> {
+    let ext = guess_image_extension(bytes);
+    let avatar_dir = data_dir.join("images").join("avatar");
+    std::fs::create_dir_all(&avatar_dir).map_err(|e| e.to_string())?;
+    let path = avatar_dir.join(format!("avatar.{}", ext));
+    // Written beside the target and renamed, so a failed write keeps the current avatar
+    let part = avatar_dir.join(format!("avatar.{}.part", ext));
+    std::fs::write(&part, bytes).map_err(|e| e.to_string())?;
+    std::fs::rename(&part, &path).map_err(|e| e.to_string())?;
+    tracing::info!(path = %path.display(), ext = %ext, "avatar: saved image to disk");
+
+    let mut s = load_settings_from_dir(data_dir);
+    // Only files inside the data directory; an image picked from elsewhere is the user's
+    if let Some(previous) = s.avatar_image_path.as_deref().map(PathBuf::from) {
+        if previous != path && previous.starts_with(data_dir) {
+            match std::fs::remove_file(&previous) {
+                Ok(()) => tracing::info!(path = %previous.display(), "avatar: removed previous image"),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => tracing::warn!(path = %previous.display(), error = %e, "avatar: failed to remove previous image"),
             }
         }
     }
-    // Use a unique filename to bust caches
-    let ts = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-    let path = avatars_dir.join(format!("avatar-{}.{}", ts, ext));
-    std::fs::write(&path, &bytes).map_err(|e| e.to_string())?;
-    tracing::info!(path = %path.display(), ext = %ext, "avatar: saved image to disk");
-    // Update settings with saved path
-    let mut s = load_settings_from_dir(&state.data_dir);
     s.avatar_image_path = Some(path.display().to_string());
-    save_settings_to_dir(&state.data_dir, &s).map_err(|e| e.to_string())?;
+    save_settings_to_dir(data_dir, &s).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// Payload of `avatar://progress` and `avatar://cartoonify-progress` events.
+#[derive(Debug, Clone, Serialize)]
+struct AvatarProgress {
+    request_id: String,
+    completed: u32,
+    total: u32,
+}
+
+// Source formats `cartoonify_avatar` accepts
+const CARTOONIFY_MIMES: &[&str] = &["image/png", "image/jpeg", "image/webp"];
+
+/// Cartoonify a photo and make the result the avatar, in one call. Progress is emitted as
+/// `avatar://cartoonify-progress` events tagged with `request_id` (generated if omitted).
+/// `base64_image` may also be a data URI. Returns the saved avatar path.
+#[tauri::command]
+async fn cartoonify_avatar(
+    app: tauri::AppHandle,
+    state: Backend,
+    base64_image: String,
+    mime: String,
+    request_id: Option<String>,
+) -> Result<String, ToonanaError> {
+    let mime = match mime.trim().to_ascii_lowercase().as_str() {
+        "image/jpg" => "image/jpeg".to_string(),
+        m => m.to_string(),
+    };
+    if !CARTOONIFY_MIMES.contains(&mime.as_str()) {
+        return Err(format!("unsupported image type '{}': use PNG, JPEG or WebP", mime).into());
+    }
+    let b64 = match base64_image.split_once(',') {
+        Some((header, data)) if header.starts_with("data:") => data.to_string(),
+        _ => base64_image,
+    };
+    // Fail on bad input here rather than with an opaque provider error
+    decode_base64_png(&b64).map_err(|e| ToonanaError::new(error::ErrorKind::Decode, e.to_string()))?;
+
+    let settings = load_settings_from_dir(&state.data_dir);
+    let request_id = request_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    tracing::info!(request_id = %request_id, mime = %mime, "cartoonify avatar: started");
+    let out = cartoonify_image_with_progress(&b64, &mime, &settings, &CancellationToken::new(), |completed, total| {
+        let event = AvatarProgress { request_id: request_id.clone(), completed, total };
+        if let Err(e) = app.emit("avatar://cartoonify-progress", event) {
+            tracing::warn!(error = %e, "failed to emit avatar://cartoonify-progress");
+        }
+    })
+    .await?;
+    let bytes = decode_base64_png(&out)?;
+    let path = store_avatar(&state.data_dir, &bytes)?;
     Ok(path.display().to_string())
 }
 
 #[tauri::command]
-async fn delete_avatar_image() -> Result<(), String> {
-    let state = STARTUP.as_ref().map_err(|e| e.to_string())?.clone();
+async fn delete_avatar_image() -> Result<(), ToonanaError> {
+    let state = app_state()?;
     let mut s = load_settings_from_dir(&state.data_dir);
     if let Some(path_str) = s.avatar_image_path.take() {
         let p = std::path::Path::new(&path_str);
@@ -589,21 +1741,21 @@ async fn delete_avatar_image() -> Result<(), String> {
 
 #[tauri::command]
 async fn list_comics_by_day(
-    state: tauri::State<'_, AppState>,
+    state: Backend,
     limit_days: Option<i64>,
-) -> Result<Vec<ComicsByDay>, String> {
+) -> Result<Vec<ComicsByDay>, ToonanaError> {
     use std::collections::BTreeMap;
-    use std::fs;
-    // removed unused local import of Path
 
     let limit_days = limit_days.unwrap_or(120);
 
     // Fetch recent entries
     let entries = list_entries(
         &state.db,
-        Some(ListParams { limit: Some(2000), offset: Some(0) }),
+        Some(ListParams { limit: Some(2000), offset: Some(0), ..Default::default() }),
+        database::DEFAULT_PREVIEW_LENGTH,
     )
-    .await?;
+    .await?
+    .items;
 
     let mut by_day: BTreeMap<String, Vec<ComicItem>> = BTreeMap::new();
 
@@ -613,35 +1765,10 @@ async fn list_comics_by_day(
         if day.is_empty() { continue; }
 
         let entry_img_dir = state.data_dir.join("images").join(&e.id);
-        if !entry_img_dir.exists() { continue; }
-
-        // Find the newest generated image in the entry image folder
-        let mut best_path: Option<(String, std::time::SystemTime)> = None;
-        if let Ok(rd) = fs::read_dir(&entry_img_dir) {
-            for ent in rd.flatten() {
-                let path = ent.path();
-                if !path.is_file() { continue; }
-                let file_name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
-                let ext_ok = path.extension().and_then(|s| s.to_str()).map(|ext| {
-                    matches!(ext.to_ascii_lowercase().as_str(), "png" | "jpg" | "jpeg" | "webp")
-                }).unwrap_or(false);
-                if !ext_ok { continue; }
-                // Prefer the composite result image if present
-                if !file_name.contains("-result") { /* still acceptable */ }
-                let meta = ent.metadata().ok();
-                let modified = meta.and_then(|m| m.modified().ok()).unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-                let path_str = path.display().to_string();
-                match &best_path {
-                    Some((_, ts)) if modified <= *ts => {}
-                    _ => { best_path = Some((path_str, modified)); }
-                }
-            }
-        }
-
-        if let Some((img_path, _)) = best_path {
+        if let Some(img_path) = comic::newest_entry_image(&entry_img_dir) {
             by_day.entry(day).or_default().push(ComicItem {
                 entry_id: e.id,
-                image_path: img_path,
+                image_path: img_path.display().to_string(),
                 created_at: created,
             });
         }
@@ -661,68 +1788,504 @@ async fn list_comics_by_day(
     Ok(items)
 }
 
+#[tauri::command]
+async fn read_image_base64(
+    state: Backend,
+    path: String,
+) -> Result<String, ToonanaError> {
+    use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
+    // Only serve files that live under the app data dir
+    let data_dir = state.data_dir.canonicalize().map_err(|e| e.to_string())?;
+    let target = Path::new(&path)
+        .canonicalize()
+        .map_err(|e| format!("image not found: {}", e))?;
+    if !target.starts_with(&data_dir) {
+        return Err("path is outside the data directory".into());
+    }
+    if !target.is_file() {
+        return Err("path is not a file".into());
+    }
+    let bytes = tokio::fs::read(&target).await.map_err(|e| e.to_string())?;
+    let mime = guess_image_mime(&bytes);
+    Ok(format!("data:{};base64,{}", mime, B64.encode(&bytes)))
+}
+
+#[tauri::command]
+async fn cache_remote_image(
+    state: Backend,
+    url: String,
+) -> Result<String, ToonanaError> {
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return Err("only http(s) URLs can be cached".into());
+    }
+    let settings = load_settings_from_dir(&state.data_dir);
+    let client = utils::http_client_builder(&settings, Some(60), Some(10))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let key = settings
+        .gemini_api_key
+        .clone()
+        .or_else(|| std::env::var("GEMINI_API_KEY").ok());
+    let path = image_cache::fetch_to_cache(&client, &state.data_dir, &url, key.as_deref())
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(path.display().to_string())
+}
+
+#[tauri::command]
+async fn render_caption_card(
+    state: Backend,
+    text: String,
+    style_hint: Option<String>,
+    save: Option<bool>,
+) -> Result<caption::CaptionCard, ToonanaError> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    caption::caption_card(&text, style_hint.as_deref(), save.unwrap_or(false), &settings, &state.data_dir)
+        .await
+        .map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn export_month_contact_sheet(
+    state: Backend,
+    year: i32,
+    month: u32,
+    path: String,
+) -> Result<contact_sheet::ContactSheetResult, ToonanaError> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    contact_sheet::export_month_contact_sheet(&state.db, &state.data_dir, &settings, year, month, Path::new(&path))
+        .await
+        .map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn export_all_comics(
+    app: tauri::AppHandle,
+    state: Backend,
+    path: String,
+    format: Option<String>,
+) -> Result<comic_export::ExportAllResult, ToonanaError> {
+    comic_export::export_all_comics(
+        &app,
+        &state.db,
+        &state.data_dir,
+        Path::new(&path),
+        format.as_deref().unwrap_or("original"),
+    )
+    .await
+    .map_err(ToonanaError::from)
+}
+
+/// Dump the whole database as a human-readable `.sql` file.
+#[tauri::command]
+async fn export_sql_dump(
+    state: Backend,
+    path: String,
+) -> Result<backup::SqlDumpResult, ToonanaError> {
+    backup::export_sql_dump(&state.db, Path::new(&path))
+        .await
+        .map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn export_mood_report(
+    state: Backend,
+    year: i32,
+    path: String,
+) -> Result<report::MoodReportResult, ToonanaError> {
+    report::export_mood_report(&state.db, &state.data_dir, year, Path::new(&path))
+        .await
+        .map_err(ToonanaError::from)
+}
+
+/// Cosine similarity (0-1) of two entries; errors if either has no embedding.
+#[tauri::command]
+async fn entry_similarity(
+    state: Backend,
+    id_a: String,
+    id_b: String,
+) -> Result<f32, ToonanaError> {
+    embed::entry_similarity(&state.db, &id_a, &id_b).await.map_err(ToonanaError::from)
+}
+
+/// Thumbnails of the entry's comic results, newest first.
+#[tauri::command]
+async fn get_entry_thumbnails(
+    state: Backend,
+    entry_id: String,
+) -> Result<Vec<database::AssetRecord>, ToonanaError> {
+    database::list_entry_assets(&state.db, &entry_id, "thumbnail").await.map_err(ToonanaError::from)
+}
+
+/// Registered images (`comic`, `panel`, `thumbnail`), optionally of one kind, newest first.
+#[tauri::command]
+async fn list_assets(
+    state: Backend,
+    kind: Option<String>,
+) -> Result<Vec<database::AssetRecord>, ToonanaError> {
+    database::list_assets(&state.db, kind.as_deref()).await.map_err(ToonanaError::from)
+}
+
+/// Delete registered images that no panel or comic job points at any more, plus their
+/// asset rows. Images of jobs still running are left alone, as are files outside the
+/// data directory (only their rows are dropped).
+#[tauri::command]
+async fn gc_unreferenced_assets(state: Backend) -> Result<utils::Reclaimed, ToonanaError> {
+    let active: std::collections::HashSet<String> = state
+        .comic_status
+        .iter()
+        .filter(|s| !s.stage.is_terminal())
+        .map(|s| s.key().clone())
+        .collect();
+    let mut reclaimed = utils::Reclaimed::default();
+    for asset in database::unreferenced_assets(&state.db).await? {
+        let job_id = asset.meta.as_ref().and_then(|m| m.get("job_id")).and_then(|j| j.as_str());
+        if job_id.is_some_and(|j| active.contains(j)) {
+            continue;
+        }
+        let path = Path::new(&asset.path);
+        if path.starts_with(&state.data_dir) {
+            let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            match std::fs::remove_file(path) {
+                Ok(()) => {
+                    reclaimed.files += 1;
+                    reclaimed.bytes += size;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    tracing::warn!(path = %asset.path, error = %e, "failed to remove unreferenced asset");
+                    continue;
+                }
+            }
+        }
+        database::delete_asset(&state.db, &asset.id).await?;
+    }
+    tracing::info!(files = reclaimed.files, bytes = reclaimed.bytes, "removed unreferenced assets");
+    Ok(reclaimed)
+}
+
+/// Entries most similar to `id` by embedding, best first (default 5).
+#[tauri::command]
+async fn find_similar_entries(
+    state: Backend,
+    id: String,
+    top_k: Option<usize>,
+) -> Result<Vec<embed::SimilarEntry>, ToonanaError> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    embed::find_similar_entries(&state.db, &id, top_k.unwrap_or(5).max(1), &settings).await.map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn set_preferred_style(
+    state: Backend,
+    id: String,
+    style: Option<String>,
+) -> Result<(), ToonanaError> {
+    database::set_preferred_style(&state.db, &id, style.as_deref()).await.map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn entries_on_day(
+    state: Backend,
+    month: u8,
+    day: u8,
+    utc_offset_minutes: Option<i32>,
+) -> Result<Vec<EntryListItem>, ToonanaError> {
+    let settings = load_settings_from_dir(&state.data_dir);
+    let preview_length = settings.preview_length.unwrap_or(database::DEFAULT_PREVIEW_LENGTH);
+    database::entries_on_day(&state.db, month, day, utc_offset_minutes.unwrap_or(0), preview_length).await.map_err(ToonanaError::from)
+}
+
+#[tauri::command]
+async fn clear_image_cache(state: Backend) -> Result<u64, ToonanaError> {
+    image_cache::clear_cache(&state.data_dir)
+        .await
+        .map_err(ToonanaError::from)
+}
+
 #[tauri::command]
 async fn db_delete_entry(
-    state: tauri::State<'_, AppState>,
+    state: Backend,
     id: String,
-) -> Result<(), String> {
+) -> Result<utils::Reclaimed, ToonanaError> {
+    // Stop comic jobs for this entry first so they can't write into the images dir we remove below
+    let entry_jobs: Vec<String> = state
+        .comic_status
+        .iter()
+        .filter(|s| s.entry_id == id && !s.stage.is_terminal())
+        .map(|s| s.key().clone())
+        .collect();
+    for job_id in entry_jobs {
+        stop_comic_task(&state, &job_id).await;
+        if let Some(mut status) = state.comic_status.get_mut(&job_id) {
+            status.stage = ComicStage::failed("entry was deleted");
+            status.updated_at = now_iso();
+        }
+        tracing::info!(job_id = %job_id, entry_id = %id, "aborted comic job for deleted entry");
+    }
+
     delete_entry(&state.db, &id).await?;
+    // Generated images go with the entry; cleanup is best-effort and reports what it freed
     let img_dir = state.data_dir.join("images").join(&id);
-    if img_dir.exists() {
-        let _ = tokio::fs::remove_dir_all(&img_dir).await;
-    }
-    Ok(())
+    let reclaimed = tokio::task::spawn_blocking(move || utils::remove_dir_reclaiming(&img_dir))
+        .await
+        .unwrap_or_default();
+    tracing::info!(entry_id = %id, files = reclaimed.files, bytes = reclaimed.bytes, "deleted entry images");
+    Ok(reclaimed)
 }
 
 // ===== Startup and Main =====
 
-static STARTUP: Lazy<Result<AppState>> = Lazy::new(|| tauri_startup());
+static APP_STATE: OnceCell<AppState> = OnceCell::new();
+// Serializes startup attempts so concurrent init_app calls never build two pools
+static INIT_LOCK: Lazy<tokio::sync::Mutex<()>> = Lazy::new(|| tokio::sync::Mutex::new(()));
+static STARTUP_ERROR: Lazy<std::sync::Mutex<Option<String>>> = Lazy::new(|| std::sync::Mutex::new(None));
+// Read in `run()` while the process is single-threaded: on Unix, `time` refuses to look up
+// the local offset once other threads exist
+static LOCAL_OFFSET: OnceCell<Option<time::UtcOffset>> = OnceCell::new();
+
+/// The running backend state, or an actionable message explaining why startup failed.
+fn app_state() -> Result<AppState, String> {
+    if let Some(state) = APP_STATE.get() {
+        return Ok(state.clone());
+    }
+    let last = STARTUP_ERROR.lock().ok().and_then(|g| g.clone());
+    Err(last.unwrap_or_else(|| "backend is still starting up; call init_app to initialize it".to_string()))
+}
+
+/// Command argument for the backend state. Unlike `tauri::State`, which fails with a generic
+/// "state not managed" error, it rejects the call with the recorded startup error.
+struct Backend(AppState);
+
+impl std::ops::Deref for Backend {
+    type Target = AppState;
+
+    fn deref(&self) -> &AppState {
+        &self.0
+    }
+}
+
+impl<'de, R: tauri::Runtime> tauri::ipc::CommandArg<'de, R> for Backend {
+    fn from_command(_: tauri::ipc::CommandItem<'de, R>) -> Result<Self, tauri::ipc::InvokeError> {
+        app_state().map(Backend).map_err(|e| ToonanaError::from(e).into())
+    }
+}
+
+async fn ensure_started() -> Result<AppState, String> {
+    let _guard = INIT_LOCK.lock().await;
+    if let Some(state) = APP_STATE.get() {
+        return Ok(state.clone());
+    }
+    match tauri_startup().await {
+        Ok(state) => {
+            let _ = APP_STATE.set(state.clone());
+            if let Ok(mut g) = STARTUP_ERROR.lock() {
+                *g = None;
+            }
+            Ok(state)
+        }
+        Err(e) => {
+            let msg = describe_startup_error(&e);
+            tracing::error!(error = %e, "backend startup failed");
+            if let Ok(mut g) = STARTUP_ERROR.lock() {
+                *g = Some(msg.clone());
+            }
+            Err(msg)
+        }
+    }
+}
+
+/// Turn a startup failure into something the user can act on.
+fn describe_startup_error(e: &anyhow::Error) -> String {
+    let hint = e
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<std::io::Error>())
+        .and_then(|io| match io.kind() {
+            std::io::ErrorKind::PermissionDenied => {
+                Some("toonana cannot write to its data directory; check folder permissions")
+            }
+            std::io::ErrorKind::StorageFull => Some("the disk is full; free up space"),
+            _ => None,
+        })
+        .or_else(|| {
+            let text = format!("{:#}", e).to_ascii_lowercase();
+            if text.contains("disk is full") || text.contains("database or disk is full") {
+                Some("the disk is full; free up space")
+            } else if text.contains("readonly") || text.contains("permission denied") {
+                Some("toonana cannot write to its data directory; check folder permissions")
+            } else if text.contains("unable to open database file") {
+                Some("the database file could not be opened; check that the data directory exists and is writable")
+            } else {
+                None
+            }
+        });
+    match hint {
+        Some(h) => format!("startup failed: {} ({:#}). Fix this and retry with init_app.", h, e),
+        None => format!("startup failed: {:#}. Retry with init_app.", e),
+    }
+}
+
+static BACKGROUND_STARTED: OnceCell<()> = OnceCell::new();
+
+/// Spawn long-running maintenance tasks once the backend state exists.
+fn start_background_tasks(app: &tauri::AppHandle, state: &AppState) {
+    if BACKGROUND_STARTED.set(()).is_err() {
+        return;
+    }
+    let (app, resume_state) = (app.clone(), state.clone());
+    tauri::async_runtime::spawn(async move { resume_queued_comic_jobs(app, resume_state).await });
+    backup::spawn_auto_backup(
+        state.db.clone(),
+        state.data_dir.clone(),
+        state.jobs.clone(),
+        state.comic_status.clone(),
+    );
+}
+
+// Start again the jobs that were still queued when the app last stopped, under their
+// original ids and options; they wait for a slot, the queue and quiet hours as usual
+async fn resume_queued_comic_jobs(app: tauri::AppHandle, state: AppState) {
+    let queued = match database::queued_comic_jobs(&state.db).await {
+        Ok(queued) => queued,
+        Err(e) => {
+            tracing::warn!(error = %e, "failed to load queued comic jobs");
+            return;
+        }
+    };
+    if !queued.is_empty() {
+        tracing::info!(count = queued.len(), "resuming queued comic jobs");
+    }
+    for (status, options) in queued {
+        spawn_comic_job(app.clone(), &state, status, options).await;
+    }
+}
 
-fn tauri_startup() -> Result<AppState> {
+async fn tauri_startup() -> Result<AppState> {
     let data_dir = ensure_data_dir()?;
     let db_file = db_path(&data_dir);
     // Initialize structured logging early
     let _ = init_tracing(&data_dir);
-    
-    // We need a synchronous runtime here to construct the pool
-    let rt = tokio::runtime::Runtime::new()?;
-    let pool = rt.block_on(create_pool(&db_file))?;
+
+    let settings = load_settings_from_dir(&data_dir);
+    let pool = create_pool(&db_file, settings.defer_destructive_migrations.unwrap_or(false)).await?;
+
+    let local_offset = LOCAL_OFFSET.get().copied().flatten().unwrap_or_else(|| {
+        tracing::warn!("local UTC offset unavailable; evaluating quiet hours in UTC");
+        time::UtcOffset::UTC
+    });
+
+    // Jobs that were running when the app closed can't resume; surface them as failed
+    let comic_status = Arc::new(DashMap::new());
+    let interrupted = database::recover_interrupted_comic_jobs(&pool)
+        .await
+        .map_err(|e| anyhow::anyhow!("recover comic jobs: {}", e))?;
+    if !interrupted.is_empty() {
+        tracing::info!(count = interrupted.len(), "marked interrupted comic jobs as failed");
+    }
+    for status in interrupted {
+        comic_status.insert(status.job_id.clone(), status);
+    }
+    // Queued jobs never started; they stay queued and `start_background_tasks` resumes them
+    let queued = database::queued_comic_jobs(&pool)
+        .await
+        .map_err(|e| anyhow::anyhow!("load queued comic jobs: {}", e))?;
+    for (status, _) in queued {
+        comic_status.insert(status.job_id.clone(), status);
+    }
 
     Ok(AppState {
         db: pool,
         data_dir,
         jobs: Arc::new(DashMap::new()),
-        comic_status: Arc::new(DashMap::new()),
+        comic_status,
         avatar_status: Arc::new(DashMap::new()),
+        queue_paused: Arc::new(tokio::sync::watch::channel(false).0),
+        storyboard_stops: Arc::new(DashMap::new()),
+        job_cancels: Arc::new(DashMap::new()),
+        job_slots: Arc::new(tokio::sync::Semaphore::new(settings.max_concurrent_jobs.unwrap_or(2).max(1))),
+        archive_status: Arc::new(DashMap::new()),
+        archive_cancels: Arc::new(DashMap::new()),
+        stream_stops: Arc::new(DashMap::new()),
+        local_offset,
     })
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let state = STARTUP.as_ref().expect("startup failed").clone();
-    tracing::info!(data_dir = %state.data_dir.display(), "backend initialized");
-    
+    // Before the runtime starts any threads (see `LOCAL_OFFSET`)
+    let _ = LOCAL_OFFSET.set(time::UtcOffset::current_local_offset().ok());
+    // We need a synchronous runtime here to construct the pool
+    let startup = tokio::runtime::Runtime::new()
+        .map_err(|e| format!("startup failed: cannot create async runtime: {e}"))
+        .and_then(|rt| rt.block_on(ensure_started()));
+
+    match startup {
+        Ok(state) => tracing::info!(data_dir = %state.data_dir.display(), "backend initialized"),
+        // Keep the window up so the UI can show the error and retry via init_app; until then
+        // every command that needs the backend returns this error (see `Backend`)
+        Err(e) => tracing::error!(error = %e, "backend unavailable; waiting for init_app"),
+    }
+
     tauri::Builder::default()
-        .manage(state)
+        .setup(|app| {
+            if let Ok(state) = app_state() {
+                start_background_tasks(app.handle(), &state);
+            }
+            Ok(())
+        })
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
             health,
+            get_version_info,
+            init_app,
             get_settings,
             update_settings,
+            normalize_base_url,
             init_vault,
+            rotate_vault_key,
+            verify_schema,
+            get_pending_migrations,
+            apply_migrations,
+            backup_database,
             encrypt,
             decrypt,
             db_upsert_entry,
             db_get_entry,
             db_list_entries,
             db_delete_entry,
+            toggle_pin,
+            archive_entry,
+            unarchive_entry,
+            list_archived_entries,
+            backfill_body_hashes,
+            list_entries_changed_since,
             save_image_to_disk,
             export_pdf,
             create_comic_job,
+            create_entry_and_comic,
+            create_comic_from_selection,
+            retry_comic_job,
+            preview_comic,
             get_comic_job_status,
+            pause_queue,
+            stop_storyboard,
+            resume_queue,
+            get_storyboards,
+            get_storyboard,
             cancel_job,
+            average_render_time,
+            recent_activity,
+            list_comic_jobs,
+            describe_comic,
             ollama_health,
             ollama_list_models,
+            ollama_model_details,
+            ollama_test_model,
+            warm_up_ollama,
+            debug_ollama_generate,
+            infer_mood,
+            generate_summary,
+            estimate_tokens,
             ollama_generate,
             list_comics_by_day
             , generate_avatar_image
@@ -732,6 +2295,39 @@ pub fn run() {
             , get_avatar_job_status
             , cancel_avatar_job
             , create_cartoonify_job
+            , read_image_base64
+            , cache_remote_image
+            , clear_image_cache
+            , render_caption_card
+            , export_month_contact_sheet
+            , entries_on_day
+            , set_preferred_style
+            , export_mood_report
+            , export_all_comics
+            , entry_similarity
+            , export_sql_dump
+            , export_backup
+            , import_backup
+            , get_backup_status
+            , cancel_backup
+            , find_similar_entries
+            , get_entry_thumbnails
+            , generate_storyboard_only
+            , regenerate_panel
+            , list_style_presets
+            , create_style_preset
+            , update_style_preset
+            , delete_style_preset
+            , ollama_generate_stream
+            , stop_ollama_stream
+            , list_assets
+            , gc_unreferenced_assets
+            , check_all_providers
+            , cartoonify_avatar
+            , generate_avatar
+            , generate_avatar_from_description
+            , import_entries
+            , export_entries
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,105 @@
+use tracing::{info, warn};
+
+use crate::ollama;
+use crate::settings::Settings;
+
+/// The moods the UI knows how to display.
+pub const CANONICAL_MOODS: &[&str] = &[
+    "happy",
+    "sad",
+    "excited",
+    "anxious",
+    "calm",
+    "frustrated",
+    "grateful",
+    "thoughtful",
+];
+
+// Common off-list answers mapped onto the canonical set
+const MOOD_SYNONYMS: &[(&str, &str)] = &[
+    ("joyful", "happy"),
+    ("content", "happy"),
+    ("cheerful", "happy"),
+    ("upset", "sad"),
+    ("down", "sad"),
+    ("melancholy", "sad"),
+    ("thrilled", "excited"),
+    ("enthusiastic", "excited"),
+    ("nervous", "anxious"),
+    ("worried", "anxious"),
+    ("stressed", "anxious"),
+    ("relaxed", "calm"),
+    ("peaceful", "calm"),
+    ("angry", "frustrated"),
+    ("annoyed", "frustrated"),
+    ("irritated", "frustrated"),
+    ("thankful", "grateful"),
+    ("reflective", "thoughtful"),
+    ("pensive", "thoughtful"),
+    ("contemplative", "thoughtful"),
+];
+
+/// Map a model answer onto a canonical mood, if it clearly names one.
+pub fn normalize_mood(answer: &str) -> Option<&'static str> {
+    let cleaned = answer
+        .to_ascii_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphabetic() { c } else { ' ' })
+        .collect::<String>();
+    let words: Vec<&str> = cleaned.split_whitespace().collect();
+    for w in &words {
+        if let Some(m) = CANONICAL_MOODS.iter().find(|m| *m == w) {
+            return Some(m);
+        }
+    }
+    for w in &words {
+        if let Some((_, m)) = MOOD_SYNONYMS.iter().find(|(syn, _)| syn == w) {
+            return Some(m);
+        }
+    }
+    None
+}
+
+fn build_mood_prompt(entry_text: &str) -> String {
+    format!(
+        r#"Classify the overall mood of the journal entry below.
+
+Answer with exactly one word from this list and nothing else:
+{}
+
+Journal Entry:
+{}
+"#,
+        CANONICAL_MOODS.join(", "),
+        entry_text
+    )
+}
+
+fn build_mood_retry_prompt(entry_text: &str, previous: &str) -> String {
+    format!(
+        r#"Your previous answer "{}" is not an allowed mood.
+Reply with ONLY one of these words, lowercase, no punctuation: {}
+
+Journal Entry:
+{}
+"#,
+        previous.trim(),
+        CANONICAL_MOODS.join(", "),
+        entry_text
+    )
+}
+
+/// Ask Ollama for the entry's mood, retrying once with stricter guidance when the
+/// answer falls outside the canonical set.
+pub async fn infer_mood(entry_text: &str, settings: &Settings) -> Result<String, String> {
+    let answer = ollama::generate(None, build_mood_prompt(entry_text), settings).await?;
+    if let Some(m) = normalize_mood(&answer) {
+        info!(mood = m, "mood inferred");
+        return Ok(m.to_string());
+    }
+    warn!(answer = %answer.trim(), "mood answer off-list, retrying");
+    let retry = ollama::generate(None, build_mood_retry_prompt(entry_text, &answer), settings).await?;
+    normalize_mood(&retry)
+        .map(|m| m.to_string())
+        .ok_or_else(|| format!("could not infer a mood (model answered: {})", retry.trim()))
+}
@@ -0,0 +1,228 @@
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::ollama::{resolve_model, OllamaOptions, StreamSummary};
+use crate::settings::Settings;
+use crate::utils::{http_client_builder, normalize_base_url};
+
+pub const PROVIDER_OPENAI_COMPATIBLE: &str = "openai_compatible";
+
+/// True when `llm_provider` selects an OpenAI-compatible server; anything else means Ollama.
+pub fn is_selected(settings: &Settings) -> bool {
+    settings
+        .llm_provider
+        .as_deref()
+        .map(|p| p.trim().eq_ignore_ascii_case(PROVIDER_OPENAI_COMPATIBLE))
+        .unwrap_or(false)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatCompletionRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    pub stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<i32>,
+}
+
+impl ChatCompletionRequest {
+    // Same model and sampling settings as the Ollama path, so switching backends keeps them
+    fn new(model: Option<String>, prompt: String, settings: &Settings, options: Option<OllamaOptions>, stream: bool) -> Self {
+        let options = options.unwrap_or_default();
+        Self {
+            model: resolve_model(model, settings),
+            messages: vec![ChatMessage { role: "user".to_string(), content: prompt }],
+            stream,
+            temperature: options.temperature.or(settings.ollama_temperature),
+            top_p: options.top_p.or(settings.ollama_top_p),
+            seed: options.seed,
+            max_tokens: options.num_predict,
+        }
+    }
+}
+
+/// `<base>/v1/<path>`; a base that already ends in `/v1` isn't doubled.
+fn api_url(settings: &Settings, path: &str) -> Result<String, String> {
+    let raw = settings
+        .openai_base_url
+        .as_deref()
+        .filter(|s| !s.trim().is_empty())
+        .ok_or_else(|| "openai_base_url is not set".to_string())?;
+    let base = normalize_base_url(raw).unwrap_or_else(|_| raw.trim().trim_end_matches('/').to_string());
+    if base.ends_with("/v1") {
+        Ok(format!("{}/{}", base, path))
+    } else {
+        Ok(format!("{}/v1/{}", base, path))
+    }
+}
+
+fn chat_completions_url(settings: &Settings) -> Result<String, String> {
+    api_url(settings, "chat/completions")
+}
+
+/// Model ids from `<base>/v1/models`; used as the reachability check in `check_all_providers`.
+pub async fn list_models(settings: &Settings) -> Result<Vec<String>, String> {
+    let client = http_client_builder(settings, Some(10), Some(5))
+        .build()
+        .map_err(|e| format!("http client error: {e}"))?;
+    let mut req = client.get(api_url(settings, "models")?);
+    if let Some(key) = settings.openai_api_key.as_deref().filter(|k| !k.trim().is_empty()) {
+        req = req.bearer_auth(key.trim());
+    }
+    let resp = req.send().await.map_err(|e| format!("openai request failed: {e}"))?;
+    if !resp.status().is_success() {
+        return Err(format!("openai error: HTTP {}", resp.status()));
+    }
+    let value: serde_json::Value = resp.json().await.map_err(|e| format!("response parse error: {e}"))?;
+    Ok(value
+        .get("data")
+        .and_then(|d| d.as_array())
+        .map(|models| models.iter().filter_map(|m| m.get("id")?.as_str().map(str::to_string)).collect())
+        .unwrap_or_default())
+}
+
+async fn send(settings: &Settings, body: &ChatCompletionRequest) -> Result<reqwest::Response, String> {
+    let url = chat_completions_url(settings)?;
+    // Like the Ollama path, no overall limit unless `request_timeout_secs` is set
+    let client = http_client_builder(settings, None, None)
+        .build()
+        .map_err(|e| format!("http client error: {e}"))?;
+    let mut req = client.post(&url).json(body);
+    if let Some(key) = settings.openai_api_key.as_deref().filter(|k| !k.trim().is_empty()) {
+        req = req.bearer_auth(key.trim());
+    }
+    let resp = req.send().await.map_err(|e| format!("openai request failed: {e}"))?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("openai error: HTTP {} {}", status, text.trim()));
+    }
+    Ok(resp)
+}
+
+pub async fn generate(model: Option<String>, prompt: String, settings: &Settings) -> Result<String, String> {
+    let body = ChatCompletionRequest::new(model, prompt, settings, None, false);
+    let value: serde_json::Value = send(settings, &body)
+        .await?
+        .json()
+        .await
+        .map_err(|e| format!("response parse error: {e}"))?;
+    value
+        .pointer("/choices/0/message/content")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .ok_or_else(|| "Unexpected OpenAI response format".to_string())
+}
+
+// Handle one SSE line; returns the `finish_reason` once a choice reports one
+fn handle_sse_line(line: &str, on_chunk: &mut impl FnMut(&str)) -> Option<String> {
+    let data = line.strip_prefix("data:")?.trim();
+    if data == "[DONE]" {
+        return None;
+    }
+    let json = serde_json::from_str::<serde_json::Value>(data).ok()?;
+    let choice = json.get("choices")?.get(0)?;
+    if let Some(s) = choice.pointer("/delta/content").and_then(|v| v.as_str()) {
+        if !s.is_empty() {
+            on_chunk(s);
+        }
+    }
+    choice.get("finish_reason").and_then(|v| v.as_str()).map(|s| s.to_string())
+}
+
+// Remove and decode every complete line in `buf`, leaving a trailing partial line for the
+// next chunk. Decoding whole lines keeps a character split across chunks intact.
+fn take_complete_lines(buf: &mut Vec<u8>) -> Vec<String> {
+    let Some(last_newline) = buf.iter().rposition(|&b| b == b'\n') else {
+        return Vec::new();
+    };
+    let rest = buf.split_off(last_newline + 1);
+    let complete = std::mem::replace(buf, rest);
+    String::from_utf8_lossy(&complete).lines().map(str::to_string).collect()
+}
+
+/// Stream a chat completion, calling `on_chunk` with each `choices[0].delta.content`.
+/// Mirrors `ollama::generate_streaming`, except truncated output isn't auto-continued.
+pub async fn generate_streaming(
+    model: Option<String>,
+    prompt: String,
+    settings: &Settings,
+    options: Option<OllamaOptions>,
+    stop: Option<&AtomicBool>,
+    mut on_chunk: impl FnMut(&str),
+) -> Result<StreamSummary, String> {
+    let body = ChatCompletionRequest::new(model, prompt, settings, options, true);
+    let resp = send(settings, &body).await?;
+
+    let mut summary = StreamSummary::default();
+    let mut buf: Vec<u8> = Vec::new();
+    let mut stream = resp.bytes_stream();
+    while let Some(item) = stream.next().await {
+        // Dropping the stream closes the connection, which ends generation server-side
+        if stop.map(|s| s.load(Ordering::Relaxed)).unwrap_or(false) {
+            tracing::info!("openai generation stopped by request");
+            summary.stopped = true;
+            return Ok(summary);
+        }
+        let bytes = item.map_err(|e| format!("stream error: {e}"))?;
+        buf.extend_from_slice(&bytes);
+        for line in take_complete_lines(&mut buf) {
+            if let Some(reason) = handle_sse_line(line.trim(), &mut on_chunk) {
+                summary.done_reason = Some(reason);
+            }
+        }
+    }
+    if let Some(reason) = handle_sse_line(String::from_utf8_lossy(&buf).trim(), &mut on_chunk) {
+        summary.done_reason = Some(reason);
+    }
+
+    summary.truncated = summary.done_reason.as_deref() == Some("length");
+    if summary.truncated {
+        tracing::warn!("openai output ended at the token limit");
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn characters_split_across_chunks_are_decoded_whole() {
+        let event = "data: {\"choices\":[{\"delta\":{\"content\":\"café 😀\"}}]}\n\n";
+        let bytes = event.as_bytes();
+        // Cut inside the emoji's four bytes
+        let cut = event.find('😀').unwrap() + 2;
+        let mut buf = Vec::new();
+        let mut text = String::new();
+        for chunk in [&bytes[..cut], &bytes[cut..]] {
+            buf.extend_from_slice(chunk);
+            for line in take_complete_lines(&mut buf) {
+                handle_sse_line(line.trim(), &mut |s: &str| text.push_str(s));
+            }
+        }
+        assert_eq!(text, "café 😀");
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn partial_lines_stay_buffered() {
+        let mut buf = b"data: one\ndata: tw".to_vec();
+        assert_eq!(take_complete_lines(&mut buf), ["data: one"]);
+        assert_eq!(buf, b"data: tw");
+        assert!(take_complete_lines(&mut buf).is_empty());
+    }
+}
@@ -0,0 +1,241 @@
+use anyhow::{anyhow, Context, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::{Rgb, RgbImage};
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref, Str};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::info;
+
+use crate::comic::ExportPanel;
+
+const MARGIN: f32 = 36.0;
+const GUTTER: f32 = 18.0;
+const FONT_SIZE: f32 = 11.0;
+const LEADING: f32 = 14.0;
+const CAPTION_LINES: usize = 3;
+const MAX_PANELS_PER_PAGE: u32 = 6;
+const JPEG_QUALITY: u8 = 90;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageSize {
+    #[default]
+    A4,
+    Letter,
+}
+
+impl PageSize {
+    // Width and height in points
+    fn dimensions(self) -> (f32, f32) {
+        match self {
+            PageSize::A4 => (595.0, 842.0),
+            PageSize::Letter => (612.0, 792.0),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PdfExportResult {
+    pub path: String,
+    pub pages: usize,
+    pub panels: usize,
+}
+
+struct PreparedPanel {
+    jpeg: Vec<u8>,
+    width: u32,
+    height: u32,
+    caption: Option<String>,
+}
+
+// Decode the panel image, flatten any transparency onto white and re-encode as JPEG so
+// it can be embedded with DCTDecode
+fn prepare_panel(panel: &ExportPanel) -> Result<PreparedPanel> {
+    let path = panel
+        .image_path
+        .as_deref()
+        .filter(|p| !p.trim().is_empty())
+        .ok_or_else(|| anyhow!("panel {} has no image", panel.panel_id))?;
+    if !Path::new(path).is_file() {
+        return Err(anyhow!("panel {}: image not found at {}", panel.panel_id, path));
+    }
+    let img = image::open(path).with_context(|| format!("panel {}: read {}", panel.panel_id, path))?;
+    let rgba = img.to_rgba8();
+    let rgb = RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let p = rgba.get_pixel(x, y).0;
+        let a = p[3] as u32;
+        let blend = |c: u8| ((c as u32 * a + 255 * (255 - a)) / 255) as u8;
+        Rgb([blend(p[0]), blend(p[1]), blend(p[2])])
+    });
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY)
+        .encode_image(&rgb)
+        .with_context(|| format!("panel {}: encode image", panel.panel_id))?;
+    let caption = match &panel.dialogue_cipher {
+        Some(cipher) => {
+            let text = crate::vault::decrypt_to_string(cipher)
+                .map_err(|e| anyhow!("panel {}: dialogue: {}", panel.panel_id, e))?;
+            Some(text.trim().to_string()).filter(|t| !t.is_empty())
+        }
+        None => None,
+    };
+    Ok(PreparedPanel { jpeg, width: rgb.width(), height: rgb.height(), caption })
+}
+
+// Helvetica uses WinAnsiEncoding, which matches Latin-1 for printable characters;
+// common typographic punctuation is mapped to ASCII and anything else becomes '?'
+fn win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            ' '..='~' | '\u{A0}'..='\u{FF}' => c as u8,
+            '\u{2018}' | '\u{2019}' => b'\'',
+            '\u{201C}' | '\u{201D}' => b'"',
+            '\u{2013}' | '\u{2014}' => b'-',
+            '\u{2026}' => b'.',
+            '\n' | '\t' => b' ',
+            _ => b'?',
+        })
+        .collect()
+}
+
+// Greedy wrap using an average Helvetica glyph width; the last line gets an ellipsis
+// when the text doesn't fit in `max_lines`
+fn wrap_caption(text: &str, width: f32, max_lines: usize) -> Vec<String> {
+    let max_chars = ((width / (FONT_SIZE * 0.5)).floor() as usize).max(8);
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let needed = if current.is_empty() { word.chars().count() } else { current.chars().count() + 1 + word.chars().count() };
+        if needed > max_chars && !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        let last = lines.last_mut().expect("max_lines > 0");
+        let keep = max_chars.saturating_sub(3);
+        if last.chars().count() > keep {
+            *last = last.chars().take(keep).collect();
+        }
+        last.push_str("...");
+    }
+    lines
+}
+
+/// Lay the panels out top to bottom, `panels_per_page` per page, each image scaled to fit
+/// its slot with the panel's decrypted dialogue as a caption underneath. Every panel
+/// must have a readable image; a missing one fails the export instead of being skipped.
+pub fn export_comic_pdf(
+    panels: &[ExportPanel],
+    path: &Path,
+    page_size: PageSize,
+    panels_per_page: u32,
+) -> Result<PdfExportResult> {
+    if panels.is_empty() {
+        return Err(anyhow!("no panels to export"));
+    }
+    if !(1..=MAX_PANELS_PER_PAGE).contains(&panels_per_page) {
+        return Err(anyhow!("panels_per_page must be between 1 and {}", MAX_PANELS_PER_PAGE));
+    }
+    let prepared = panels.iter().map(prepare_panel).collect::<Result<Vec<_>>>()?;
+
+    let (page_w, page_h) = page_size.dimensions();
+    let per_page = panels_per_page as usize;
+    let page_count = prepared.len().div_ceil(per_page);
+    let content_w = page_w - 2.0 * MARGIN;
+    let slot_h = (page_h - 2.0 * MARGIN - GUTTER * (per_page as f32 - 1.0)) / per_page as f32;
+    let caption_h = LEADING * CAPTION_LINES as f32;
+
+    let mut pdf = Pdf::new();
+    let mut next_id = 1;
+    let mut alloc = || {
+        let r = Ref::new(next_id);
+        next_id += 1;
+        r
+    };
+    let catalog_id = alloc();
+    let tree_id = alloc();
+    let font_id = alloc();
+    let font_name = Name(b"F1");
+    let page_ids: Vec<Ref> = (0..page_count).map(|_| alloc()).collect();
+    let content_ids: Vec<Ref> = (0..page_count).map(|_| alloc()).collect();
+    let image_ids: Vec<Ref> = (0..prepared.len()).map(|_| alloc()).collect();
+    let image_names: Vec<String> = (0..prepared.len()).map(|i| format!("Im{}", i + 1)).collect();
+
+    pdf.catalog(catalog_id).pages(tree_id);
+    pdf.pages(tree_id).kids(page_ids.iter().copied()).count(page_count as i32);
+    pdf.type1_font(font_id)
+        .base_font(Name(b"Helvetica"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+
+    for (i, panel) in prepared.iter().enumerate() {
+        let mut image = pdf.image_xobject(image_ids[i], &panel.jpeg);
+        image.filter(Filter::DctDecode);
+        image.width(panel.width as i32);
+        image.height(panel.height as i32);
+        image.color_space().device_rgb();
+        image.bits_per_component(8);
+        image.finish();
+    }
+
+    for (page_index, chunk) in prepared.chunks(per_page).enumerate() {
+        let first = page_index * per_page;
+        let mut content = Content::new();
+        for (slot, panel) in chunk.iter().enumerate() {
+            // PDF coordinates start at the bottom left; slots fill from the top
+            let slot_top = page_h - MARGIN - slot as f32 * (slot_h + GUTTER);
+            let reserved = if panel.caption.is_some() { caption_h } else { 0.0 };
+            let box_h = (slot_h - reserved).max(1.0);
+            let scale = (content_w / panel.width as f32).min(box_h / panel.height as f32);
+            let (w, h) = (panel.width as f32 * scale, panel.height as f32 * scale);
+            let x = MARGIN + (content_w - w) / 2.0;
+            let y = slot_top - h;
+            content.save_state();
+            content.transform([w, 0.0, 0.0, h, x, y]);
+            content.x_object(Name(image_names[first + slot].as_bytes()));
+            content.restore_state();
+
+            if let Some(caption) = &panel.caption {
+                let mut line_y = y - LEADING;
+                content.begin_text();
+                content.set_font(font_name, FONT_SIZE);
+                for line in wrap_caption(caption, content_w, CAPTION_LINES) {
+                    content.set_text_matrix([1.0, 0.0, 0.0, 1.0, MARGIN, line_y]);
+                    content.show(Str(&win_ansi(&line)));
+                    line_y -= LEADING;
+                }
+                content.end_text();
+            }
+        }
+        pdf.stream(content_ids[page_index], &content.finish());
+
+        let mut page = pdf.page(page_ids[page_index]);
+        page.media_box(Rect::new(0.0, 0.0, page_w, page_h));
+        page.parent(tree_id);
+        page.contents(content_ids[page_index]);
+        let mut resources = page.resources();
+        resources.fonts().pair(font_name, font_id);
+        let mut x_objects = resources.x_objects();
+        for (slot, _) in chunk.iter().enumerate() {
+            x_objects.pair(Name(image_names[first + slot].as_bytes()), image_ids[first + slot]);
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).context("create export dir")?;
+    }
+    std::fs::write(path, pdf.finish()).with_context(|| format!("write {}", path.display()))?;
+    info!(path = %path.display(), pages = page_count, panels = prepared.len(), "comic pdf exported");
+    Ok(PdfExportResult {
+        path: path.display().to_string(),
+        pages: page_count,
+        panels: prepared.len(),
+    })
+}
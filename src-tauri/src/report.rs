@@ -0,0 +1,107 @@
+use anyhow::{anyhow, Context, Result};
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Row, Sqlite};
+use std::path::Path;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tracing::info;
+
+use crate::comic::newest_entry_image;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MoodReportRow {
+    pub date: String,
+    pub entry_id: String,
+    pub mood: Option<String>,
+    pub word_count: usize,
+    pub has_comic: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MoodReportResult {
+    pub path: String,
+    pub rows: u64,
+}
+
+// Quote a CSV field when it contains a separator, quote or newline
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Write one row per entry created in `year` (UTC) to `path`, oldest first. The format is
+/// JSON when the path ends in `.json` and CSV otherwise. Rows are streamed from the
+/// database to the file so large years never build up in memory.
+pub async fn export_mood_report(
+    pool: &Pool<Sqlite>,
+    data_dir: &Path,
+    year: i32,
+    path: &Path,
+) -> Result<MoodReportResult> {
+    let json = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.eq_ignore_ascii_case("json"))
+        .unwrap_or(false);
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await.context("create export dir")?;
+    }
+    let file = tokio::fs::File::create(path)
+        .await
+        .with_context(|| format!("create {}", path.display()))?;
+    let mut out = BufWriter::new(file);
+
+    out.write_all(if json { b"[\n" } else { b"date,entry_id,mood,word_count,has_comic\n" })
+        .await?;
+
+    let mut rows = sqlx::query(
+        r#"SELECT id, created_at, mood, body_cipher FROM entries
+           WHERE created_at >= ?1 AND created_at < ?2 ORDER BY created_at ASC"#,
+    )
+    .bind(format!("{:04}-01-01", year))
+    .bind(format!("{:04}-01-01", year + 1))
+    .fetch(pool);
+
+    let mut count = 0u64;
+    while let Some(row) = rows.try_next().await.map_err(|e| anyhow!("db: {}", e))? {
+        let entry_id: String = row.try_get("id")?;
+        let created_at: String = row.try_get("created_at")?;
+        let body: Vec<u8> = row.try_get("body_cipher")?;
+        let record = MoodReportRow {
+            date: created_at.split('T').next().unwrap_or_default().to_string(),
+            has_comic: newest_entry_image(&data_dir.join("images").join(&entry_id)).is_some(),
+            entry_id,
+            mood: row.try_get("mood")?,
+            // Unreadable bodies count as empty rather than failing the whole report
+            word_count: crate::vault::decrypt(&body)
+                .map(|b| String::from_utf8_lossy(&b).split_whitespace().count())
+                .unwrap_or(0),
+        };
+
+        let line = if json {
+            let sep = if count == 0 { "  " } else { ",\n  " };
+            format!("{}{}", sep, serde_json::to_string(&record)?)
+        } else {
+            format!(
+                "{},{},{},{},{}\n",
+                record.date,
+                csv_field(&record.entry_id),
+                csv_field(record.mood.as_deref().unwrap_or("")),
+                record.word_count,
+                record.has_comic
+            )
+        };
+        out.write_all(line.as_bytes()).await?;
+        count += 1;
+    }
+
+    if json {
+        out.write_all(b"\n]\n").await?;
+    }
+    out.flush().await?;
+    info!(path = %path.display(), rows = count, year, "mood report exported");
+    Ok(MoodReportResult { path: path.display().to_string(), rows: count })
+}
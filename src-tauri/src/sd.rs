@@ -0,0 +1,140 @@
+use serde::Deserialize;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, instrument, warn};
+
+use crate::settings::Settings;
+use crate::utils::{http_client_builder, normalize_base_url, truncate_chars};
+
+pub const PROVIDER_A1111: &str = "a1111";
+
+// Local generation on modest GPUs can take minutes
+const REQUEST_TIMEOUT_SECS: u64 = 300;
+const PROGRESS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const STEPS: u32 = 25;
+const NEGATIVE_PROMPT: &str = "blurry, low quality, watermark, signature, deformed hands";
+// Sizes near one megapixel (multiples of 64) for the aspect ratios SD models handle well
+const SIZES: &[(&str, u32, u32)] = &[
+    ("1:1", 768, 768),
+    ("4:3", 896, 672),
+    ("3:4", 672, 896),
+    ("16:9", 1024, 576),
+    ("9:16", 576, 1024),
+];
+
+#[derive(Debug, Deserialize)]
+struct Txt2ImgResponse {
+    #[serde(default)]
+    images: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProgressResponse {
+    #[serde(default)]
+    progress: f64,
+}
+
+fn base_url(settings: &Settings) -> Result<String, String> {
+    let base = settings.a1111_base_url.as_deref().ok_or("a1111 base URL not set in settings")?;
+    normalize_base_url(base).map_err(|e| format!("a1111 base URL: {}", e))
+}
+
+fn image_size(settings: &Settings) -> (u32, u32) {
+    let ratios: Vec<&'static str> = SIZES.iter().map(|(r, _, _)| *r).collect();
+    let ratio = settings
+        .image_aspect_ratio
+        .as_deref()
+        .map(|r| crate::gemini::closest_aspect_ratio(r, &ratios))
+        .unwrap_or("1:1");
+    SIZES.iter().find(|(r, _, _)| *r == ratio).map(|(_, w, h)| (*w, *h)).unwrap_or((768, 768))
+}
+
+/// Render `prompt` with Automatic1111's `/sdapi/v1/txt2img` and return the base64 image.
+/// While the request runs, `/sdapi/v1/progress` is polled and reported as
+/// `on_progress(percent, 100)`. Once `cancel` fires the server is asked to interrupt and
+/// the error is `"cancelled"`.
+#[instrument(skip(prompt, settings, cancel, on_progress))]
+pub async fn a1111_generate_image(
+    prompt: &str,
+    settings: &Settings,
+    cancel: &CancellationToken,
+    mut on_progress: impl FnMut(u32, u32),
+) -> Result<String, String> {
+    let base = base_url(settings)?;
+    let client = http_client_builder(settings, Some(REQUEST_TIMEOUT_SECS), Some(10))
+        .build()
+        .map_err(|e| format!("a1111 http client error: {e}"))?;
+    let (width, height) = image_size(settings);
+    let payload = serde_json::json!({
+        "prompt": prompt,
+        "negative_prompt": NEGATIVE_PROMPT,
+        "steps": STEPS,
+        "width": width,
+        "height": height,
+    });
+    info!(width, height, steps = STEPS, "a1111: sending txt2img request");
+    let request = client.post(format!("{}/sdapi/v1/txt2img", base)).json(&payload).send();
+    tokio::pin!(request);
+
+    let mut last_percent = 0u32;
+    let resp = loop {
+        tokio::select! {
+            r = &mut request => break r.map_err(|e| format!("a1111 request failed: {e}"))?,
+            _ = cancel.cancelled() => {
+                // Best effort; the server otherwise keeps rendering an image nobody will read
+                if let Err(e) = client.post(format!("{}/sdapi/v1/interrupt", base)).send().await {
+                    warn!(error = %e, "a1111: interrupt failed");
+                }
+                return Err("cancelled".to_string());
+            }
+            _ = tokio::time::sleep(PROGRESS_POLL_INTERVAL) => {
+                match poll_progress(&client, &base).await {
+                    Ok(p) => {
+                        // Capped below 100 until the image is actually back
+                        let percent = ((p * 100.0).round() as u32).min(99);
+                        if percent > last_percent {
+                            last_percent = percent;
+                            on_progress(percent, 100);
+                        }
+                    }
+                    Err(e) => debug!(error = %e, "a1111: progress poll failed"),
+                }
+            }
+        }
+    };
+
+    let status = resp.status();
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(format!("a1111 error: HTTP {}: {}", status, truncate_chars(&body, 300)));
+    }
+    let parsed: Txt2ImgResponse = resp.json().await.map_err(|e| format!("a1111 parse error: {e}"))?;
+    let image = parsed.images.into_iter().next().ok_or("a1111: no image in response")?;
+    on_progress(100, 100);
+    Ok(image)
+}
+
+/// Reachability check for `check_all_providers`: the server answers `/sdapi/v1/progress`.
+pub async fn a1111_ping(settings: &Settings) -> Result<(), String> {
+    let base = base_url(settings)?;
+    let client = http_client_builder(settings, Some(10), Some(5))
+        .build()
+        .map_err(|e| format!("a1111 http client error: {e}"))?;
+    poll_progress(&client, &base)
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("a1111 not reachable: {}", e))
+}
+
+async fn poll_progress(client: &reqwest::Client, base: &str) -> Result<f64, String> {
+    let resp = client
+        .get(format!("{}/sdapi/v1/progress?skip_current_image=true", base))
+        .timeout(PROGRESS_POLL_INTERVAL * 2)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .error_for_status()
+        .map_err(|e| e.to_string())?;
+    let progress: ProgressResponse = resp.json().await.map_err(|e| e.to_string())?;
+    Ok(progress.progress.clamp(0.0, 1.0))
+}
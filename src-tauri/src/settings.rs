@@ -3,36 +3,432 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
 
+/// Version of the settings.json layout; bump when a field changes meaning or is renamed.
+pub const SETTINGS_VERSION: u32 = 1;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Settings {
     pub gemini_api_key: Option<String>,
+    /// Gemini model for comic and avatar images (default "gemini-2.5-flash-image-preview")
+    pub gemini_image_model: Option<String>,
+    /// Gemini model for text requests (default "gemini-2.0-flash")
+    pub gemini_text_model: Option<String>,
+    /// Sampling temperature for Gemini text requests (API default when unset)
+    pub gemini_temperature: Option<f32>,
+    /// Upper bound on tokens in Gemini text responses (API default when unset)
+    pub gemini_max_output_tokens: Option<u32>,
+    /// Backend for storyboard text: "ollama" (default) or "openai_compatible"
+    pub llm_provider: Option<String>,
+    /// Base URL of an OpenAI-compatible server (`/v1/chat/completions`), used with `llm_provider`
+    pub openai_base_url: Option<String>,
+    pub openai_api_key: Option<String>,
     pub ollama_base_url: Option<String>,
     pub default_ollama_model: Option<String>,
     pub ollama_temperature: Option<f32>,
     pub ollama_top_p: Option<f32>,
+    pub ollama_num_ctx: Option<u32>,
+    /// Ask Ollama to keep going when a storyboard stops at the token limit
+    pub ollama_auto_continue: Option<bool>,
+    /// Sent as Ollama's `keep_alive` so the model stays loaded between requests (e.g. "30m")
+    pub ollama_keep_alive: Option<String>,
+    /// Ollama model used for entry embeddings (default "nomic-embed-text")
+    pub embedding_model: Option<String>,
+    /// Clean up line endings, blank lines and chatty preambles in storyboards (default on)
+    pub normalize_storyboard: Option<bool>,
+    /// Save the storyboard to the database while it streams, not just once it's complete (default off)
+    pub persist_partial_storyboard: Option<bool>,
+    /// Re-prompt once for missing panels when a storyboard comes back short, failing if it still is
+    pub strict_panel_count: Option<bool>,
+    /// Word cap for storyboard captions and dialogue lines, enforced after generation (default 12, 0 = off)
+    pub max_caption_words: Option<usize>,
+    /// Format comic images are saved in: "png", "jpg" or "webp" (unset keeps what the provider returned)
+    pub output_image_format: Option<String>,
+    /// Free space (MiB) that must remain on the data volume for a comic to render
+    pub min_free_disk_mb: Option<u64>,
+    /// Enables diagnostic commands such as `debug_ollama_generate`
+    pub debug_mode: Option<bool>,
+    /// Hold destructive schema migrations at startup until confirmed via `apply_migrations`
+    pub defer_destructive_migrations: Option<bool>,
+    /// Characters shown in entry list previews (cut on a word boundary)
+    pub preview_length: Option<usize>,
+    /// Comic image backend: "gemini", "nano_banana" or "a1111". Unset picks nano-banana when
+    /// its base URL is set, otherwise Gemini
+    pub image_provider: Option<String>,
+    /// Retry with Gemini when the chosen image provider fails (default false: the job fails
+    /// with that provider's error instead)
+    pub fallback_to_gemini: Option<bool>,
     pub nano_banana_base_url: Option<String>,
     pub nano_banana_api_key: Option<String>,
+    /// Automatic1111 / Stable Diffusion web UI started with `--api`, used with `image_provider = "a1111"`
+    pub a1111_base_url: Option<String>,
+    /// How the author looks; added to comic image prompts and used by `generate_avatar`
     pub avatar_description: Option<String>,
+    /// Saved avatar image, sent with comic prompts as a character reference
     pub avatar_image_path: Option<String>,
+    pub default_comic_style: Option<String>,
+    pub style_reference_image_path: Option<String>,
+    /// TTF/OTF font used for caption cards; falls back to common system fonts
+    pub caption_font_path: Option<String>,
+    pub auto_backup: Option<AutoBackupSettings>,
+    /// Local-time window during which new comic jobs wait instead of running
+    pub quiet_hours: Option<QuietHours>,
+    /// Panels rendered in parallel in per-panel mode (default 2, at least 1; see
+    /// `comic::panel_render_concurrency`)
+    pub panel_render_concurrency: Option<usize>,
+    /// Comic jobs that may run at once; later ones stay queued (default 2, read at startup)
+    pub max_concurrent_jobs: Option<usize>,
+    /// Overall limit for a single HTTP request, in seconds. Unset keeps each call's own
+    /// default: 60 for Gemini and nano-banana images, 90 for Gemini streams, none for the local LLM
+    pub request_timeout_secs: Option<u64>,
+    /// Limit for establishing HTTP connections, in seconds (default 10 for remote APIs, none for the local LLM)
+    pub connect_timeout_secs: Option<u64>,
+    /// Extra attempts, with stricter image-only guidance, when Gemini answers without an image (default 1)
+    pub image_gen_max_retries: Option<u32>,
+    /// Retries after Gemini answers 429 or 503, waiting per `Retry-After` or with exponential backoff (default 3)
+    pub rate_limit_max_retries: Option<u32>,
+    /// Shape of generated comics as "W:H" (e.g. "16:9"); snapped to what each backend supports
+    pub image_aspect_ratio: Option<String>,
+}
+
+/// A problem with one settings field, as reported by `Settings::validate`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+// Trimmed value, with blank strings treated as unset
+fn trimmed(value: &Option<String>) -> Option<String> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+}
+
+fn check_range(errors: &mut Vec<FieldError>, field: &str, value: Option<f32>, min: f32, max: f32) {
+    if let Some(v) = value {
+        if !(min..=max).contains(&v) {
+            errors.push(FieldError {
+                field: field.to_string(),
+                message: format!("must be between {:.1} and {:.1}, got {}", min, max, v),
+            });
+        }
+    }
+}
+
+impl Settings {
+    /// Trim text fields (blank becomes unset), normalize service URLs, and check values
+    /// are in range. Every problem is reported, not just the first.
+    pub fn validate(&mut self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        for slot in [
+            &mut self.gemini_api_key,
+            &mut self.openai_api_key,
+            &mut self.nano_banana_api_key,
+            &mut self.default_ollama_model,
+            &mut self.embedding_model,
+            &mut self.llm_provider,
+            &mut self.image_provider,
+            &mut self.image_aspect_ratio,
+            &mut self.avatar_description,
+        ] {
+            *slot = trimmed(slot);
+        }
+        for (field, url) in [
+            ("ollama_base_url", &mut self.ollama_base_url),
+            ("nano_banana_base_url", &mut self.nano_banana_base_url),
+            ("openai_base_url", &mut self.openai_base_url),
+            ("a1111_base_url", &mut self.a1111_base_url),
+        ] {
+            *url = match trimmed(url) {
+                Some(u) => match crate::utils::normalize_base_url(&u) {
+                    Ok(normalized) => Some(normalized),
+                    Err(message) => {
+                        errors.push(FieldError { field: field.to_string(), message });
+                        Some(u)
+                    }
+                },
+                None => None,
+            };
+        }
+        for (field, model, example) in [
+            ("gemini_image_model", &mut self.gemini_image_model, crate::gemini::DEFAULT_GEMINI_IMAGE_MODEL),
+            ("gemini_text_model", &mut self.gemini_text_model, crate::gemini::DEFAULT_GEMINI_TEXT_MODEL),
+        ] {
+            // Model names end up in the request path
+            if let Some(m) = model.as_deref().map(str::trim) {
+                if m.is_empty() || m.contains(char::is_whitespace) || m.contains('/') {
+                    errors.push(FieldError {
+                        field: field.to_string(),
+                        message: format!("must be a model name such as \"{}\", got '{}'", example, m),
+                    });
+                } else {
+                    *model = Some(m.to_string());
+                }
+            }
+        }
+        check_range(&mut errors, "ollama_temperature", self.ollama_temperature, 0.0, 2.0);
+        check_range(&mut errors, "ollama_top_p", self.ollama_top_p, 0.0, 1.0);
+        if let Some(p) = self.llm_provider.as_deref() {
+            if !["ollama", crate::openai::PROVIDER_OPENAI_COMPATIBLE].contains(&p) {
+                errors.push(FieldError {
+                    field: "llm_provider".to_string(),
+                    message: format!("must be \"ollama\" or \"{}\"", crate::openai::PROVIDER_OPENAI_COMPATIBLE),
+                });
+            }
+        }
+        if let Some(p) = self.image_provider.as_deref() {
+            if ImageProvider::parse(p).is_none() {
+                let names: Vec<String> = ImageProvider::ALL.iter().map(|p| format!("\"{}\"", p.as_str())).collect();
+                errors.push(FieldError {
+                    field: "image_provider".to_string(),
+                    message: format!("must be one of {}", names.join(", ")),
+                });
+            }
+        }
+        if let Some(r) = self.image_aspect_ratio.as_deref() {
+            if let Err(message) = crate::gemini::validate_aspect_ratio(r) {
+                errors.push(FieldError { field: "image_aspect_ratio".to_string(), message });
+            }
+        }
+        for (field, secs) in [
+            ("request_timeout_secs", self.request_timeout_secs),
+            ("connect_timeout_secs", self.connect_timeout_secs),
+        ] {
+            if secs == Some(0) {
+                errors.push(FieldError { field: field.to_string(), message: "must be at least 1 second".to_string() });
+            }
+        }
+        if self.panel_render_concurrency == Some(0) {
+            errors.push(FieldError {
+                field: "panel_render_concurrency".to_string(),
+                message: "must render at least 1 panel at a time".to_string(),
+            });
+        }
+        if let Some(q) = &self.quiet_hours {
+            for (field, value) in [("quiet_hours.start", &q.start), ("quiet_hours.end", &q.end)] {
+                if parse_hhmm(value).is_none() {
+                    errors.push(FieldError { field: field.to_string(), message: format!("expected HH:MM, got '{}'", value) });
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Backend that renders comic images, see `Settings::image_provider`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageProvider {
+    Gemini,
+    NanoBanana,
+    A1111,
+}
+
+impl ImageProvider {
+    pub const ALL: [ImageProvider; 3] = [ImageProvider::Gemini, ImageProvider::NanoBanana, ImageProvider::A1111];
+
+    /// Name used in settings and in job metadata.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ImageProvider::Gemini => "gemini",
+            ImageProvider::NanoBanana => "nano_banana",
+            ImageProvider::A1111 => crate::sd::PROVIDER_A1111,
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        Self::ALL.into_iter().find(|p| p.as_str().eq_ignore_ascii_case(value))
+    }
+}
+
+impl Settings {
+    /// The configured image backend. Without an explicit `image_provider` this keeps the
+    /// older implicit choice: nano-banana when its base URL is set, otherwise Gemini.
+    pub fn image_provider(&self) -> ImageProvider {
+        match self.image_provider.as_deref().and_then(ImageProvider::parse) {
+            Some(p) => p,
+            None if self.nano_banana_base_url.is_some() => ImageProvider::NanoBanana,
+            None => ImageProvider::Gemini,
+        }
+    }
+
+    pub fn fallback_to_gemini(&self) -> bool {
+        self.fallback_to_gemini.unwrap_or(false)
+    }
+}
+
+/// A daily window in local time, "HH:MM" to "HH:MM". `end` before `start` wraps past midnight.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+}
+
+fn parse_hhmm(s: &str) -> Option<u32> {
+    let (h, m) = s.trim().split_once(':')?;
+    let (h, m) = (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?);
+    (h < 24 && m < 60).then_some(h * 60 + m)
+}
+
+impl QuietHours {
+    /// Minutes until the window ends if `minute_of_day` falls inside it. An empty or
+    /// unparsable window never matches.
+    pub fn remaining_minutes(&self, minute_of_day: u32) -> Option<u32> {
+        let (start, end) = (parse_hhmm(&self.start)?, parse_hhmm(&self.end)?);
+        let inside = if start <= end {
+            (start..end).contains(&minute_of_day)
+        } else {
+            minute_of_day >= start || minute_of_day < end
+        };
+        inside.then(|| (end + 24 * 60 - minute_of_day) % (24 * 60))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoBackupSettings {
+    pub enabled: bool,
+    pub interval_hours: u32,
+    pub keep_count: u32,
+}
+
+impl Default for AutoBackupSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_hours: 24,
+            keep_count: 7,
+        }
+    }
 }
 
 pub fn settings_path(data_dir: &Path) -> PathBuf {
     data_dir.join("settings.json")
 }
 
-pub fn load_settings_from_dir(data_dir: &Path) -> Settings {
-    let path = settings_path(data_dir);
-    if let Ok(bytes) = fs::read(&path) {
-        if let Ok(s) = serde_json::from_slice::<Settings>(&bytes) {
-            return s;
+/// Environment variables that override provider settings, as (variable, setting) pairs.
+/// Earlier variables win when several map to the same setting.
+pub const ENV_OVERRIDES: &[(&str, &str)] = &[
+    ("TOONANA_GEMINI_API_KEY", "gemini_api_key"),
+    ("GEMINI_API_KEY", "gemini_api_key"),
+    ("TOONANA_OLLAMA_BASE_URL", "ollama_base_url"),
+    ("TOONANA_OLLAMA_MODEL", "default_ollama_model"),
+    ("TOONANA_NANO_BANANA_BASE_URL", "nano_banana_base_url"),
+    ("TOONANA_NANO_BANANA_API_KEY", "nano_banana_api_key"),
+    ("TOONANA_A1111_BASE_URL", "a1111_base_url"),
+    ("TOONANA_IMAGE_PROVIDER", "image_provider"),
+    ("TOONANA_OPENAI_BASE_URL", "openai_base_url"),
+    ("TOONANA_OPENAI_API_KEY", "openai_api_key"),
+];
+
+fn env_value(var: &str) -> Option<String> {
+    std::env::var(var).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+// The setting an `ENV_OVERRIDES` entry names
+fn env_slot<'a>(s: &'a mut Settings, setting: &str) -> Option<&'a mut Option<String>> {
+    Some(match setting {
+        "gemini_api_key" => &mut s.gemini_api_key,
+        "ollama_base_url" => &mut s.ollama_base_url,
+        "default_ollama_model" => &mut s.default_ollama_model,
+        "nano_banana_base_url" => &mut s.nano_banana_base_url,
+        "nano_banana_api_key" => &mut s.nano_banana_api_key,
+        "a1111_base_url" => &mut s.a1111_base_url,
+        "image_provider" => &mut s.image_provider,
+        "openai_base_url" => &mut s.openai_base_url,
+        "openai_api_key" => &mut s.openai_api_key,
+        _ => return None,
+    })
+}
+
+/// Overwrite provider settings with any non-empty variables from `ENV_OVERRIDES`.
+fn apply_env_overrides(s: &mut Settings) {
+    for (var, setting) in ENV_OVERRIDES.iter().rev() {
+        let Some(value) = env_value(var) else { continue };
+        if let Some(slot) = env_slot(s, setting) {
+            *slot = Some(value);
         }
     }
-    Settings::default()
 }
 
+/// Undo `apply_env_overrides` before saving: a setting that still holds the value from
+/// `env` gets back the one in `file`, so values from the environment (API keys included)
+/// are never written to settings.json. A value the user changed is saved as given.
+fn strip_env_overrides(s: &mut Settings, env: &Settings, file: &Settings) {
+    let (mut env, mut file) = (env.clone(), file.clone());
+    for (_, setting) in ENV_OVERRIDES {
+        let Some(env_value) = env_slot(&mut env, setting).and_then(|v| v.clone()) else { continue };
+        let saved = env_slot(&mut file, setting).and_then(|v| v.clone());
+        if let Some(slot) = env_slot(s, setting) {
+            if slot.as_deref() == Some(env_value.as_str()) {
+                *slot = saved;
+            }
+        }
+    }
+}
+
+/// Settings exactly as stored in settings.json, without environment overrides.
+pub fn load_file_settings(data_dir: &Path) -> Settings {
+    fs::read(settings_path(data_dir))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<Settings>(&bytes).ok())
+        .unwrap_or_default()
+}
+
+/// Load settings with precedence environment > settings.json > defaults. A missing or
+/// unreadable settings.json counts as empty, so headless runs can rely on env alone.
+pub fn load_settings_from_dir(data_dir: &Path) -> Settings {
+    let mut settings = load_file_settings(data_dir);
+    apply_env_overrides(&mut settings);
+    settings
+}
+
+/// Save settings to settings.json. Values that only come from the environment are left out
+/// (see `strip_env_overrides`), so settings loaded with `load_settings_from_dir` can be
+/// changed and saved back safely.
 pub fn save_settings_to_dir(data_dir: &Path, s: &Settings) -> Result<()> {
     let path = settings_path(data_dir);
-    let json = serde_json::to_vec_pretty(s)?;
+    let mut env = Settings::default();
+    apply_env_overrides(&mut env);
+    let mut s = s.clone();
+    strip_env_overrides(&mut s, &env, &load_file_settings(data_dir));
+    let json = serde_json::to_vec_pretty(&s)?;
     fs::write(path, json).context("write settings")?;
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_values_are_not_saved_but_user_edits_are() {
+        let env = Settings {
+            gemini_api_key: Some("env-key".into()),
+            ollama_base_url: Some("http://env:11434".into()),
+            ..Default::default()
+        };
+        let file = Settings { gemini_api_key: Some("file-key".into()), ..Default::default() };
+        // What the UI sends back after loading env-overridden settings and editing the URL
+        let mut edited = Settings {
+            gemini_api_key: Some("env-key".into()),
+            ollama_base_url: Some("http://localhost:11434".into()),
+            ..Default::default()
+        };
+        strip_env_overrides(&mut edited, &env, &file);
+        assert_eq!(edited.gemini_api_key.as_deref(), Some("file-key"));
+        assert_eq!(edited.ollama_base_url.as_deref(), Some("http://localhost:11434"));
+
+        let mut untouched = env.clone();
+        strip_env_overrides(&mut untouched, &env, &Settings::default());
+        assert_eq!(untouched.gemini_api_key, None);
+        assert_eq!(untouched.ollama_base_url, None);
+    }
+}
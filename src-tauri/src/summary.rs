@@ -0,0 +1,50 @@
+use tracing::info;
+
+use crate::ollama;
+use crate::settings::Settings;
+
+// Titles longer than this are cut; the prompt asks for 5-8 words
+const MAX_SUMMARY_WORDS: usize = 10;
+
+fn build_summary_prompt(entry_text: &str) -> String {
+    format!(
+        r#"Write a short title for the journal entry below.
+
+Rules:
+- 5 to 8 words
+- No quotes, no trailing punctuation, no emojis
+- Reply with the title only, nothing else
+
+Journal Entry:
+{}
+"#,
+        entry_text
+    )
+}
+
+/// Reduce a model answer to a bare title: first non-empty line, no label like "Title:",
+/// no wrapping quotes or markdown, no trailing punctuation, at most `MAX_SUMMARY_WORDS` words.
+pub fn clean_summary(answer: &str) -> Option<String> {
+    let line = answer.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let line = match line.split_once(':') {
+        Some((label, rest)) if label.trim().eq_ignore_ascii_case("title") => rest.trim(),
+        _ => line,
+    };
+    let trim_chars: &[char] = &['"', '\'', '“', '”', '‘', '’', '*', '_', '#', '`'];
+    let line = line.trim_matches(|c: char| trim_chars.contains(&c) || c.is_whitespace());
+    let words: Vec<&str> = line.split_whitespace().take(MAX_SUMMARY_WORDS).collect();
+    let title = words
+        .join(" ")
+        .trim_end_matches(|c: char| matches!(c, '.' | '!' | '?' | ',' | ';' | ':') || trim_chars.contains(&c))
+        .to_string();
+    (!title.is_empty()).then_some(title)
+}
+
+/// Ask Ollama for a 5-8 word title for the entry.
+pub async fn generate_summary(entry_text: &str, settings: &Settings) -> Result<String, String> {
+    let answer = ollama::generate(None, build_summary_prompt(entry_text), settings).await?;
+    let summary = clean_summary(&answer)
+        .ok_or_else(|| format!("could not generate a summary (model answered: {})", answer.trim()))?;
+    info!(words = summary.split_whitespace().count(), "summary generated");
+    Ok(summary)
+}
@@ -1,7 +1,10 @@
 use anyhow::{anyhow, Context, Result};
 use directories::ProjectDirs;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::settings::Settings;
 
 pub fn app_dirs() -> Result<ProjectDirs> {
     ProjectDirs::from("app", "toonana", "toonana")
@@ -17,4 +20,148 @@ pub fn ensure_data_dir() -> Result<PathBuf> {
 
 pub fn db_path(data_dir: &PathBuf) -> PathBuf {
     data_dir.join("app.sqlite")
-}
\ No newline at end of file
+}
+
+// Used when `min_free_disk_mb` isn't configured
+pub const DEFAULT_MIN_FREE_DISK_MB: u64 = 200;
+
+/// Fail with a readable message when the volume holding `dir` has less than
+/// `min_free_mb` MiB free after writing `needed_bytes`.
+pub fn ensure_free_space(dir: &Path, needed_bytes: u64, min_free_mb: u64) -> Result<(), String> {
+    let available = match fs2::available_space(dir) {
+        Ok(v) => v,
+        // Can't measure (e.g. unsupported filesystem): don't block the job on it
+        Err(e) => {
+            tracing::warn!(dir = %dir.display(), error = %e, "could not read free disk space");
+            return Ok(());
+        }
+    };
+    let required = min_free_mb.saturating_mul(1024 * 1024).saturating_add(needed_bytes);
+    if available < required {
+        return Err(format!(
+            "insufficient disk space: {} MB free, need at least {} MB",
+            available / (1024 * 1024),
+            required.div_ceil(1024 * 1024)
+        ));
+    }
+    Ok(())
+}
+
+/// Clean up a user-entered service URL: trims whitespace, adds `http://` when no scheme
+/// is given, and strips trailing slashes. Rejects non-HTTP schemes and URLs without a host.
+pub fn normalize_base_url(url: &str) -> Result<String, String> {
+    let url = url.trim();
+    if url.is_empty() {
+        return Err("URL is empty".to_string());
+    }
+    if url.chars().any(char::is_whitespace) {
+        return Err(format!("URL contains spaces: '{}'", url));
+    }
+    let with_scheme = if url.contains("://") { url.to_string() } else { format!("http://{}", url) };
+    let parsed = reqwest::Url::parse(&with_scheme).map_err(|e| format!("invalid URL '{}': {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("URL must use http or https, got '{}'", parsed.scheme()));
+    }
+    if parsed.host_str().map(str::is_empty).unwrap_or(true) {
+        return Err(format!("URL has no host: '{}'", url));
+    }
+    if parsed.query().is_some() || parsed.fragment().is_some() {
+        return Err(format!("base URL should not have a query or fragment: '{}'", url));
+    }
+    Ok(with_scheme.trim_end_matches('/').to_string())
+}
+
+/// A `reqwest` client builder with the `request_timeout_secs` / `connect_timeout_secs`
+/// settings applied, falling back to the caller's defaults. `None` leaves that limit off.
+pub fn http_client_builder(
+    settings: &Settings,
+    default_timeout_secs: Option<u64>,
+    default_connect_secs: Option<u64>,
+) -> reqwest::ClientBuilder {
+    let mut builder = reqwest::Client::builder();
+    if let Some(secs) = settings.request_timeout_secs.or(default_timeout_secs) {
+        builder = builder.timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = settings.connect_timeout_secs.or(default_connect_secs) {
+        builder = builder.connect_timeout(Duration::from_secs(secs));
+    }
+    builder
+}
+
+/// At most `max_chars` characters of `s`, with `...` appended when anything was cut.
+/// Counts characters rather than bytes, so multibyte text (emoji, CJK) is never split.
+pub fn truncate_chars(s: &str, max_chars: usize) -> String {
+    match s.char_indices().nth(max_chars) {
+        Some((idx, _)) => format!("{}...", &s[..idx]),
+        None => s.to_string(),
+    }
+}
+
+/// Files and bytes freed by a cleanup.
+#[derive(Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct Reclaimed {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Recursively delete `dir`, counting what was removed. Best-effort: a missing directory
+/// or a file that can't be removed is skipped, so this never fails.
+pub fn remove_dir_reclaiming(dir: &Path) -> Reclaimed {
+    let mut reclaimed = Reclaimed::default();
+    let Ok(read) = fs::read_dir(dir) else { return reclaimed };
+    for entry in read.flatten() {
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(t) if t.is_dir() => {
+                let sub = remove_dir_reclaiming(&path);
+                reclaimed.files += sub.files;
+                reclaimed.bytes += sub.bytes;
+            }
+            Ok(_) => {
+                let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                match fs::remove_file(&path) {
+                    Ok(()) => {
+                        reclaimed.files += 1;
+                        reclaimed.bytes += size;
+                    }
+                    Err(e) => tracing::warn!(path = %path.display(), error = %e, "could not remove file"),
+                }
+            }
+            Err(_) => {}
+        }
+    }
+    let _ = fs::remove_dir(dir);
+    reclaimed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_chars_cuts_between_emoji() {
+        let s = "😀😃😄😁😆";
+        // Byte 5 would land inside the second emoji
+        assert!(!s.is_char_boundary(5));
+        assert_eq!(truncate_chars(s, 2), "😀😃...");
+        assert_eq!(truncate_chars(s, 5), s);
+    }
+
+    #[test]
+    fn truncate_chars_counts_cjk_as_single_characters() {
+        let s = "今日は雨でした。傘を忘れた。";
+        assert_eq!(truncate_chars(s, 3), "今日は...");
+        assert_eq!(truncate_chars(s, 100), s);
+    }
+
+    #[test]
+    fn truncate_chars_handles_mixed_text_at_every_cut() {
+        let s = "a😀b日c\u{301}d";
+        for max in 0..=s.chars().count() + 1 {
+            let out = truncate_chars(s, max);
+            let kept = out.strip_suffix("...").unwrap_or(&out);
+            assert!(s.starts_with(kept));
+            assert_eq!(kept.chars().count(), max.min(s.chars().count()));
+        }
+    }
+}
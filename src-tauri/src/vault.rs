@@ -0,0 +1,249 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
+use once_cell::sync::Lazy;
+use rand::RngCore;
+use sqlx::{Pool, Row, Sqlite};
+use std::sync::RwLock;
+use tracing::{info, warn};
+
+pub const SERVICE_NAME: &str = "toonana";
+pub const VAULT_KEY_LABEL: &str = "vault-key-v1";
+// The key in use before the last rotation; rows a rotation didn't reach still open with it
+const PREVIOUS_KEY_LABEL: &str = "vault-key-v1-previous";
+// A rotation's new key, stored before its rows are rewritten so they stay readable if the
+// app stops between the commit and the keychain update
+const PENDING_KEY_LABEL: &str = "vault-key-v1-pending";
+
+// Ciphertext layout: MAGIC || 12-byte nonce || AES-256-GCM ciphertext+tag.
+// Anything without the prefix is a body saved before encryption was enabled.
+const MAGIC: &[u8; 4] = b"TNV1";
+const NONCE_LEN: usize = 12;
+
+type Key = [u8; 32];
+
+#[derive(Clone, Copy)]
+struct VaultKeys {
+    current: Key,
+    previous: Option<Key>,
+    pending: Option<Key>,
+}
+
+static KEYS: Lazy<RwLock<Option<VaultKeys>>> = Lazy::new(|| RwLock::new(None));
+
+fn keychain_entry(label: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(SERVICE_NAME, label).map_err(|e| format!("keychain unavailable: {}", e))
+}
+
+fn read_key(label: &str) -> Result<Option<Key>, String> {
+    match keychain_entry(label)?.get_password() {
+        Ok(encoded) => {
+            let bytes = B64
+                .decode(encoded.trim())
+                .map_err(|e| format!("vault key '{}' is corrupt: {}", label, e))?;
+            let key: Key = bytes
+                .try_into()
+                .map_err(|_| format!("vault key '{}' has the wrong length", label))?;
+            Ok(Some(key))
+        }
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("read vault key '{}': {}", label, e)),
+    }
+}
+
+fn write_key(label: &str, key: &Key) -> Result<(), String> {
+    keychain_entry(label)?
+        .set_password(&B64.encode(key))
+        .map_err(|e| format!("store vault key '{}': {}", label, e))
+}
+
+fn delete_key(label: &str) -> Result<(), String> {
+    match keychain_entry(label)?.delete_password() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!("delete vault key '{}': {}", label, e)),
+    }
+}
+
+fn set_keys(keys: VaultKeys) -> Result<(), String> {
+    *KEYS.write().map_err(|_| "vault lock poisoned".to_string())? = Some(keys);
+    Ok(())
+}
+
+fn generate_key() -> Key {
+    let mut key = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut key);
+    key
+}
+
+/// Load the vault key from the OS keychain, creating and storing one on first use.
+pub fn init_vault() -> Result<(), String> {
+    keys().map(|_| ())
+}
+
+/// Whether a key has been loaded in this session.
+pub fn is_unlocked() -> bool {
+    KEYS.read().map(|k| k.is_some()).unwrap_or(false)
+}
+
+fn keys() -> Result<VaultKeys, String> {
+    if let Some(keys) = *KEYS.read().map_err(|_| "vault lock poisoned".to_string())? {
+        return Ok(keys);
+    }
+    let mut guard = KEYS.write().map_err(|_| "vault lock poisoned".to_string())?;
+    if let Some(keys) = *guard {
+        return Ok(keys);
+    }
+    let current = match read_key(VAULT_KEY_LABEL)? {
+        Some(key) => key,
+        None => {
+            let key = generate_key();
+            write_key(VAULT_KEY_LABEL, &key)?;
+            info!("vault: generated a new key");
+            key
+        }
+    };
+    // Missing or unreadable extra keys only matter for rows a rotation missed
+    let optional = |label: &str| {
+        read_key(label).unwrap_or_else(|e| {
+            warn!(error = %e, label, "vault: key unavailable");
+            None
+        })
+    };
+    let keys = VaultKeys { current, previous: optional(PREVIOUS_KEY_LABEL), pending: optional(PENDING_KEY_LABEL) };
+    *guard = Some(keys);
+    Ok(keys)
+}
+
+/// True when `data` was produced by `encrypt` (as opposed to a legacy plaintext body).
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.len() > MAGIC.len() + NONCE_LEN && data.starts_with(MAGIC)
+}
+
+fn seal(key: &Key, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| e.to_string())?;
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let sealed = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| "encryption failed".to_string())?;
+    let mut out = Vec::with_capacity(MAGIC.len() + NONCE_LEN + sealed.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&sealed);
+    Ok(out)
+}
+
+fn open(key: &Key, data: &[u8]) -> Option<Vec<u8>> {
+    let cipher = Aes256Gcm::new_from_slice(key).ok()?;
+    let (nonce, sealed) = data[MAGIC.len()..].split_at(NONCE_LEN);
+    cipher.decrypt(Nonce::from_slice(nonce), sealed).ok()
+}
+
+/// Encrypt with the current vault key; output is nonce-prefixed and authenticated.
+pub fn encrypt(plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    seal(&keys()?.current, plaintext)
+}
+
+/// Reverse `encrypt`. Legacy plaintext (no vault prefix) is returned unchanged so it can
+/// be read and re-encrypted on its next save. Tampered data or a wrong key is an error.
+pub fn decrypt(data: &[u8]) -> Result<Vec<u8>, String> {
+    if !is_encrypted(data) {
+        return Ok(data.to_vec());
+    }
+    let keys = keys()?;
+    open(&keys.current, data)
+        .or_else(|| keys.previous.and_then(|prev| open(&prev, data)))
+        .or_else(|| keys.pending.and_then(|pending| open(&pending, data)))
+        .ok_or_else(|| "decryption failed: the data was modified or encrypted with another key".to_string())
+}
+
+pub fn decrypt_to_string(data: &[u8]) -> Result<String, String> {
+    String::from_utf8(decrypt(data)?).map_err(|e| format!("decrypted body is not UTF-8: {}", e))
+}
+
+/// Replace the vault key and re-encrypt every entry body under it.
+///
+/// The new key is first stored as the pending key, then every row is re-sealed with it
+/// inside one transaction. The current and previous keys only change after that
+/// transaction commits, so a failed rotation leaves the old key and every row as they
+/// were, and a stop between the commit and the keychain update is finished by the next
+/// rotation (see `settle_pending_key`). The old key is kept as the previous key for bodies
+/// saved while the rotation ran. Returns rows re-encrypted.
+pub async fn rotate_key(pool: &Pool<Sqlite>) -> Result<u64, String> {
+    let old = settle_pending_key(pool).await?;
+    let new_key = generate_key();
+    write_key(PENDING_KEY_LABEL, &new_key)?;
+    set_keys(VaultKeys { pending: Some(new_key), ..old })?;
+
+    let rotated = match reseal_all(pool, &new_key).await {
+        Ok(n) => n,
+        Err(e) => {
+            // Nothing was committed, so the pending key opens no row
+            set_keys(old)?;
+            if let Err(del) = delete_key(PENDING_KEY_LABEL) {
+                warn!(error = %del, "vault: could not remove the unused pending key");
+            }
+            return Err(e);
+        }
+    };
+
+    promote_pending(old.current, new_key)?;
+    info!(rows = rotated, "vault: key rotated");
+    Ok(rotated)
+}
+
+// Re-seal every body under `key` in one transaction
+async fn reseal_all(pool: &Pool<Sqlite>, key: &Key) -> Result<u64, String> {
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    let rows = sqlx::query("SELECT id, body_cipher FROM entries")
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut rotated = 0u64;
+    for row in rows {
+        let id: String = row.try_get("id").map_err(|e| e.to_string())?;
+        let body: Vec<u8> = row.try_get("body_cipher").map_err(|e| e.to_string())?;
+        let plaintext = decrypt(&body).map_err(|e| format!("entry {}: {}", id, e))?;
+        let sealed = seal(key, &plaintext)?;
+        sqlx::query("UPDATE entries SET body_cipher = ?1 WHERE id = ?2")
+            .bind(&sealed)
+            .bind(&id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        rotated += 1;
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(rotated)
+}
+
+// Make `new_key` current and `old_current` previous, then drop the pending copy
+fn promote_pending(old_current: Key, new_key: Key) -> Result<(), String> {
+    write_key(PREVIOUS_KEY_LABEL, &old_current)?;
+    write_key(VAULT_KEY_LABEL, &new_key)?;
+    set_keys(VaultKeys { current: new_key, previous: Some(old_current), pending: None })?;
+    delete_key(PENDING_KEY_LABEL)
+}
+
+/// Resolve a pending key left by a rotation that stopped part-way. When some body opens
+/// only with it, the rotation had committed and the key is promoted; otherwise it is
+/// discarded. The previous key is never overwritten while a row may still need it.
+async fn settle_pending_key(pool: &Pool<Sqlite>) -> Result<VaultKeys, String> {
+    let loaded = keys()?;
+    let Some(pending) = loaded.pending else { return Ok(loaded) };
+    let bodies: Vec<Vec<u8>> = sqlx::query_scalar("SELECT body_cipher FROM entries")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    let committed = bodies
+        .iter()
+        .any(|b| is_encrypted(b) && open(&loaded.current, b).is_none() && open(&pending, b).is_some());
+    if committed {
+        warn!("vault: finishing an interrupted key rotation");
+        promote_pending(loaded.current, pending)?;
+    } else {
+        delete_key(PENDING_KEY_LABEL)?;
+        set_keys(VaultKeys { pending: None, ..loaded })?;
+    }
+    keys()
+}
@@ -13,6 +13,7 @@ import "./App.css";
 import { ComicProgressModal } from "./components/ComicProgressModal";
 import { GalleryModal } from "./components/GalleryModal";
 import { AvatarModal } from "./components/AvatarModal";
+import { errorMessage, isToonanaError, type ToonanaError } from "./lib/utils";
 
 type OllamaHealth = {
   ok: boolean;
@@ -51,9 +52,11 @@ type ComicStage =
   | { stage: "storyboarding" }
   | { stage: "prompting" }
   | { stage: "rendering"; completed: number; total: number }
+  | { stage: "rate_limited"; retry_in_secs: number; attempt: number }
   | { stage: "saving" }
   | { stage: "done" }
-  | { stage: "failed"; error: string };
+  | { stage: "storyboard_ready"; partial: boolean }
+  | { stage: "failed"; error: string; kind?: ToonanaError["kind"] };
 
 type ComicJobStatus = {
   job_id: string;
@@ -81,7 +84,8 @@ function useInit() {
 function useEntries() {
   return useQuery({
     queryKey: ["entries"],
-    queryFn: async () => invoke<EntryListItem[]>("db_list_entries", { p: { limit: 100, offset: 0 } }),
+    queryFn: async () =>
+      (await invoke<{ items: EntryListItem[]; total: number }>("db_list_entries", { p: { limit: 100, offset: 0 } })).items,
   });
 }
 
@@ -127,7 +131,7 @@ export default function App() {
 
   const removeEntry = useMutation({
     mutationFn: async (id: string) => {
-      return invoke<void>("db_delete_entry", { id });
+      return invoke<{ files: number; bytes: number }>("db_delete_entry", { id });
     },
     onMutate: async (id: string) => {
       await qc.cancelQueries({ queryKey: ["entries"] });
@@ -139,7 +143,7 @@ export default function App() {
     },
     onError: (err, _id, ctx) => {
       if (ctx?.prev) qc.setQueryData(["entries"], ctx.prev);
-      window.alert(`Delete failed: ${String(err)}`);
+      window.alert(`Delete failed: ${errorMessage(err)}`);
     },
     onSuccess: async (_, id) => {
       if (selectedId === id) {
@@ -199,7 +203,7 @@ export default function App() {
         job_id: "local",
         entry_id: selectedId,
         style: "nano-banana",
-        stage: { stage: "failed", error: String(e) },
+        stage: { stage: "failed", error: errorMessage(e), kind: isToonanaError(e) ? e.kind : undefined },
         updated_at: new Date().toISOString(),
         result_image_path: null,
         storyboard_text: null,
@@ -227,6 +231,14 @@ export default function App() {
     };
   }, []);
 
+  // Load the storyboard model as soon as Ollama is reachable so the first comic starts faster
+  const warmedUp = useRef(false);
+  useEffect(() => {
+    if (!ollamaHealth?.ok || warmedUp.current) return;
+    warmedUp.current = true;
+    invoke<string>("warm_up_ollama", { model: null }).catch(() => {});
+  }, [ollamaHealth?.ok]);
+
   useEffect(() => {
     if (!comicJobId || !isPolling) return;
     let stopped = false;
@@ -240,7 +252,7 @@ export default function App() {
           console.log("Comic status", s);
         } catch {}
         const stage = status.stage as ComicStage;
-        if (stage.stage === "done" || stage.stage === "failed") {
+        if (stage.stage === "done" || stage.stage === "storyboard_ready" || stage.stage === "failed") {
           setIsPolling(false);
           return;
         }
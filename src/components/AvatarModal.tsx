@@ -1,5 +1,6 @@
 import { useEffect, useState } from "react";
 import { invoke, convertFileSrc } from "@tauri-apps/api/core";
+import { errorMessage, isToonanaError } from "@/lib/utils";
 
 type Settings = {
   avatar_description?: string | null;
@@ -21,7 +22,9 @@ export function AvatarModal({ open, onClose, onSaved }: AvatarModalProps) {
   const [selectedIdx, setSelectedIdx] = useState<number | null>(null);
   const [existingPath, setExistingPath] = useState<string | null>(null);
   const [error, setError] = useState<string | null>(null);
-  const existingSrc = existingPath ? convertFileSrc(existingPath) : null;
+  // The avatar keeps its file name when replaced, so the webview would show a cached copy
+  const [existingVersion, setExistingVersion] = useState(0);
+  const existingSrc = existingPath ? `${convertFileSrc(existingPath)}?v=${existingVersion}` : null;
   const [lightboxSrc, setLightboxSrc] = useState<string | null>(null);
   const [selectedFilePreview, setSelectedFilePreview] = useState<string | null>(null);
 
@@ -43,6 +46,7 @@ export function AvatarModal({ open, onClose, onSaved }: AvatarModalProps) {
         const s = await invoke<Settings>("get_settings");
         if (cancelled) return;
         setExistingPath(s?.avatar_image_path || null);
+        setExistingVersion(Date.now());
         setPreviews([]);
         setSelectedIdx(null);
         setError(null);
@@ -66,13 +70,13 @@ export function AvatarModal({ open, onClose, onSaved }: AvatarModalProps) {
         const raw = b64.startsWith("data:") ? b64.slice(b64.indexOf(",") + 1) : b64;
         const savedPath = await invoke<string>("save_avatar_image", { base64Png: raw });
         setExistingPath(savedPath);
+        setExistingVersion(Date.now());
       }
       onSaved?.(null);
       onClose();
     } catch (e) {
       console.error("Save avatar failed", e);
-      const msg = String(e ?? "Unknown error");
-      setError(`Save failed: ${msg}`);
+      setError(`Save failed: ${errorMessage(e)}`);
     } finally {
       setSaving(false);
     }
@@ -133,7 +137,7 @@ export function AvatarModal({ open, onClose, onSaved }: AvatarModalProps) {
           setTimeout(poll, 500);
         } catch (e) {
           console.error("Avatar polling error", e);
-          setError(`Generation failed: ${String(e)}`);
+          setError(`Generation failed: ${errorMessage(e)}`);
           setGenerating(false);
           setGenStep(0);
         }
@@ -141,8 +145,8 @@ export function AvatarModal({ open, onClose, onSaved }: AvatarModalProps) {
       poll();
     } catch (e) {
       console.error("Avatar generation failed", e);
-      const msg = String(e ?? "Unknown error");
-      const hint = msg.toLowerCase().includes("gemini api key")
+      const msg = errorMessage(e);
+      const hint = isToonanaError(e) && e.kind === "missing_api_key"
         ? " – Set your Gemini API key in Settings."
         : "";
       setError(`Generation failed: ${msg}${hint}`);
@@ -232,7 +236,7 @@ export function AvatarModal({ open, onClose, onSaved }: AvatarModalProps) {
                                 setExistingPath(null);
                               } catch (e) {
                                 console.error("Delete avatar failed", e);
-                                setError(`Delete failed: ${String(e ?? "Unknown error")}`);
+                                setError(`Delete failed: ${errorMessage(e)}`);
                               }
                             }}
                             className="text-rose-300 hover:text-rose-400"
@@ -1,6 +1,6 @@
 import { motion, AnimatePresence } from "framer-motion";
 import { X, Sparkles, Loader2, OctagonX, PartyPopper } from "lucide-react";
-import { cn } from "@/lib/utils";
+import { cn, type ToonanaError } from "@/lib/utils";
 import { Button } from "./ui/button";
 import { convertFileSrc } from "@tauri-apps/api/core";
 import { useMemo, useState } from "react";
@@ -11,9 +11,11 @@ export type ComicStage =
   | { stage: "storyboarding" }
   | { stage: "prompting" }
   | { stage: "rendering"; completed: number; total: number }
+  | { stage: "rate_limited"; retry_in_secs: number; attempt: number }
   | { stage: "saving" }
   | { stage: "done" }
-  | { stage: "failed"; error: string };
+  | { stage: "storyboard_ready"; partial: boolean }
+  | { stage: "failed"; error: string; kind?: ToonanaError["kind"] };
 
 export type ComicJobStatus = {
   job_id: string;
@@ -38,8 +40,10 @@ export function ComicProgressModal({ open, status, onClose, onCancel }: Props) {
     if (!status) return "";
     const s = status.stage;
     if (s.stage === "rendering") return `Rendering ${s.completed}/${s.total} panels…`;
+    if (s.stage === "rate_limited") return `Rate limited, retrying in ${s.retry_in_secs}s…`;
     if (s.stage === "failed") return `Bummer: ${s.error}`;
     if (s.stage === "done") return "All set! Your comic is ready.";
+    if (s.stage === "storyboard_ready") return "Storyboard stopped early. Edit it and try again.";
     const words: Record<string, string> = {
       queued: "Queued up…",
       parsing: "Parsing your vibes…",
@@ -119,6 +123,7 @@ export function ComicProgressModal({ open, status, onClose, onCancel }: Props) {
                     if (s.stage === "storyboarding") return "28%";
                     if (s.stage === "prompting") return "42%";
                     if (s.stage === "rendering") return `${60 + Math.round((s.completed / s.total) * 30)}%`;
+                    if (s.stage === "rate_limited") return "60%";
                     if (s.stage === "saving") return "94%";
                     if (s.stage === "done") return "100%";
                     if (s.stage === "failed") return "100%";
@@ -2,7 +2,7 @@ import { AnimatePresence, motion } from "framer-motion";
 import { X, CalendarDays } from "lucide-react";
 import { useEffect, useState } from "react";
 import { Button } from "./ui/button";
-import { cn } from "@/lib/utils";
+import { cn, errorMessage } from "@/lib/utils";
 import { invoke, convertFileSrc } from "@tauri-apps/api/core";
 
 type ComicItem = {
@@ -38,7 +38,7 @@ export function GalleryModal({ open, onClose, onSelect }: Props) {
         if (!cancelled) setDays(res);
       })
       .catch((e) => {
-        if (!cancelled) setError(String(e));
+        if (!cancelled) setError(errorMessage(e));
       })
       .finally(() => {
         if (!cancelled) setLoading(false);
@@ -3,4 +3,29 @@ import { twMerge } from "tailwind-merge"
 
 export function cn(...inputs: ClassValue[]) {
   return twMerge(clsx(inputs))
-}
\ No newline at end of file
+}
+
+// Shape of errors returned by Tauri commands (`ToonanaError` on the Rust side)
+export type ToonanaError = {
+  kind:
+    | "ollama_unreachable"
+    | "missing_api_key"
+    | "safety_blocked"
+    | "unsupported_provider"
+    | "db"
+    | "io"
+    | "decode"
+    | "other"
+  message: string
+}
+
+export function isToonanaError(e: unknown): e is ToonanaError {
+  return typeof e === "object" && e !== null && "kind" in e && "message" in e
+}
+
+// Human-readable text for anything a failed `invoke` may throw
+export function errorMessage(e: unknown, fallback = "Unknown error"): string {
+  if (isToonanaError(e)) return e.message
+  if (e instanceof Error) return e.message
+  return e == null ? fallback : String(e)
+}